@@ -1,53 +1,250 @@
 use std::path::PathBuf;
+use std::sync::Arc;
+use cpal::traits::{DeviceTrait, HostTrait};
+use std::time::{Duration, Instant};
 use kira::{
     AudioManager, AudioManagerSettings, DefaultBackend,
-    sound::static_sound::{StaticSoundData, StaticSoundHandle},
+    backend::cpal::CpalBackendSettings,
+    sound::static_sound::{StaticSoundData, StaticSoundHandle, StaticSoundSettings},
     sound::PlaybackState,
-    Tween,
+    effect::eq_filter::{EqFilterBuilder, EqFilterHandle, EqFilterKind},
+    track::MainTrackBuilder,
+    Frame, Tween,
 };
+use super::clip::LoopClip;
+use super::crossfeed::{CrossfeedBuilder, CrossfeedShared};
+use super::equalizer::EQ_BANDS;
+use super::limiter::{LimiterBuilder, LimiterShared};
+use super::meter::{LevelMeterBuilder, LevelMeterShared};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackStatus {
+    Empty,
+    Playing,
+    Paused,
+    Stopped,
+    Finished,
+}
+
+impl PlaybackStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PlaybackStatus::Empty => "empty",
+            PlaybackStatus::Playing => "playing",
+            PlaybackStatus::Paused => "paused",
+            PlaybackStatus::Stopped => "stopped",
+            PlaybackStatus::Finished => "finished",
+        }
+    }
+}
+
+pub fn output_device_name() -> Option<String> {
+    cpal::default_host().default_output_device()?.name().ok()
+}
 
 pub struct AudioEngine {
     manager: AudioManager<DefaultBackend>,
     current_handle: Option<StaticSoundHandle>,
+    preview_handle: Option<StaticSoundHandle>,
     current_file: Option<PathBuf>,
     current_volume: f32,
+    track_gain: f32,
     duration: f64,
     stopped: bool,
+    levels: Arc<LevelMeterShared>,
+    limiter: Arc<LimiterShared>,
+    crossfeed: Arc<CrossfeedShared>,
+    eq: Vec<EqFilterHandle>,
+    eq_gains: [f32; 10],
+    sample_rate: Option<u32>,
+    buffer_frames: Option<u32>,
+    seek_fade: bool,
+    pending_seek: Option<(f64, Instant)>,
+    track_serial: u64,
+    skip_silence: Option<(f32, f64)>,
+    sound_start: f64,
+    sound_end: Option<f64>,
 }
 
+const SEEK_FADE: Duration = Duration::from_millis(10);
+const EQ_Q: f64 = 1.4;
+
 impl AudioEngine {
-    pub fn new() -> Self {
-        let manager = AudioManager::<DefaultBackend>::new(AudioManagerSettings::default())
+    pub fn new(volume_linear: f32, sample_rate: Option<u32>, buffer_frames: Option<u32>) -> Self {
+        let levels = Arc::new(LevelMeterShared::default());
+        let limiter = Arc::new(LimiterShared::new(false, -1.0, false));
+        let crossfeed = Arc::new(CrossfeedShared::new(false, 700.0, 6.0));
+        let (manager, eq, sample_rate, buffer_frames) =
+            Self::build_manager(sample_rate, buffer_frames, &limiter, &crossfeed, &levels)
+                .map(|(manager, eq)| (manager, eq, sample_rate, buffer_frames))
+                .or_else(|_| {
+                    Self::build_manager(None, None, &limiter, &crossfeed, &levels)
+                        .map(|(manager, eq)| (manager, eq, None, None))
+                })
             .expect("Failed to initialize audio manager");
 
         Self {
             manager,
             current_handle: None,
+            preview_handle: None,
             current_file: None,
-            current_volume: 0.0,
+            current_volume: Self::linear_to_db(volume_linear),
+            track_gain: 0.0,
             duration: 0.0,
             stopped: false,
+            levels,
+            limiter,
+            crossfeed,
+            eq,
+            eq_gains: [0.0; 10],
+            sample_rate,
+            buffer_frames,
+            seek_fade: true,
+            pending_seek: None,
+            track_serial: 0,
+            skip_silence: None,
+            sound_start: 0.0,
+            sound_end: None,
         }
     }
 
+    fn stream_config(sample_rate: Option<u32>, buffer_frames: Option<u32>) -> Option<cpal::StreamConfig> {
+        if sample_rate.is_none() && buffer_frames.is_none() {
+            return None;
+        }
+        let supported = cpal::default_host()
+            .default_output_device()
+            .and_then(|device| device.default_output_config().ok());
+        let buffer_size = match (buffer_frames, supported.as_ref().map(|s| s.buffer_size())) {
+            (Some(frames), Some(cpal::SupportedBufferSize::Range { min, max })) => {
+                cpal::BufferSize::Fixed(frames.clamp(*min, (*max).max(*min)))
+            }
+            (Some(frames), _) => cpal::BufferSize::Fixed(frames),
+            (None, _) => cpal::BufferSize::Default,
+        };
+        let default_rate = supported.as_ref().map(|s| s.sample_rate().0);
+        Some(cpal::StreamConfig {
+            channels: 2,
+            sample_rate: cpal::SampleRate(sample_rate.or(default_rate)?),
+            buffer_size,
+        })
+    }
+
+    fn build_manager(
+        sample_rate: Option<u32>,
+        buffer_frames: Option<u32>,
+        limiter: &Arc<LimiterShared>,
+        crossfeed: &Arc<CrossfeedShared>,
+        levels: &Arc<LevelMeterShared>,
+    ) -> Result<(AudioManager<DefaultBackend>, Vec<EqFilterHandle>), String> {
+        let mut main_track_builder = MainTrackBuilder::new();
+        let eq = EQ_BANDS
+            .iter()
+            .map(|&frequency| {
+                main_track_builder.add_effect(EqFilterBuilder::new(EqFilterKind::Bell, frequency as f64, 0.0, EQ_Q))
+            })
+            .collect();
+        let manager = AudioManager::<DefaultBackend>::new(AudioManagerSettings {
+            main_track_builder: main_track_builder
+                .with_effect(CrossfeedBuilder(Arc::clone(crossfeed)))
+                .with_effect(LimiterBuilder(Arc::clone(limiter)))
+                .with_effect(LevelMeterBuilder(Arc::clone(levels))),
+            backend_settings: CpalBackendSettings {
+                device: None,
+                config: Self::stream_config(sample_rate, buffer_frames),
+            },
+            ..Default::default()
+        })
+        .map_err(|e| format!("Failed to initialize audio output: {}", e))?;
+        Ok((manager, eq))
+    }
+
+    pub fn set_output(&mut self, sample_rate: Option<u32>, buffer_frames: Option<u32>) -> Result<(), String> {
+        if sample_rate == self.sample_rate && buffer_frames == self.buffer_frames {
+            return Ok(());
+        }
+        let (manager, eq) = Self::build_manager(sample_rate, buffer_frames, &self.limiter, &self.crossfeed, &self.levels)?;
+        self.finish_seek();
+        let resume = self
+            .current_file
+            .clone()
+            .map(|path| (path, self.get_position(), self.is_playing(), self.stopped));
+        self.current_handle = None;
+        self.manager = manager;
+        self.eq = eq;
+        self.sample_rate = sample_rate;
+        self.buffer_frames = buffer_frames;
+        self.set_eq(Some(self.eq_gains));
+        if let Some((path, position, playing, stopped)) = resume {
+            self.play_song(&path)?;
+            if let Some(handle) = &mut self.current_handle {
+                handle.seek_to(position);
+                if !playing {
+                    handle.pause(Tween::default());
+                }
+            }
+            self.stopped = stopped;
+        }
+        Ok(())
+    }
+
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.sample_rate
+    }
+
+    pub fn buffer_frames(&self) -> Option<u32> {
+        self.buffer_frames
+    }
+
     pub fn play_song(&mut self, path: &PathBuf) -> Result<(), String> {
         if let Some(handle) = &mut self.current_handle {
             let _ = handle.stop(Tween::default());
         }
         self.current_handle = None;
+        self.pending_seek = None;
 
         let sound_data = StaticSoundData::from_file(path)
-            .map_err(|e| format!("Failed to load audio file: {}", e))?;
+            .map_err(|e| format!("Failed to load audio file: {}", e))?
+            .volume(self.output_volume());
 
         self.duration = sound_data.duration().as_secs_f64();
+        let sound_data = self.trim_silence(sound_data);
 
-        let mut handle = self.manager
+        let handle = self.manager
             .play(sound_data)
             .map_err(|e| format!("Failed to play audio: {}", e))?;
 
-        let _ = handle.set_volume(self.current_volume, Tween::default());
+        self.current_handle = Some(handle);
+        self.track_serial += 1;
+        self.current_file = Some(path.clone());
+        self.stopped = false;
+        Ok(())
+    }
+
+    pub fn crossfade_to(&mut self, path: &PathBuf, track_gain: f32, fade: Duration) -> Result<(), String> {
+        let tween = Tween {
+            duration: fade,
+            ..Default::default()
+        };
+        let sound_data = StaticSoundData::from_file(path)
+            .map_err(|e| format!("Failed to load audio file: {}", e))?
+            .volume(-80.0);
+        if let Some(handle) = &mut self.current_handle {
+            handle.stop(if self.stopped { Tween::default() } else { tween });
+        }
+        self.current_handle = None;
+        self.pending_seek = None;
+        self.track_gain = track_gain;
+        self.duration = sound_data.duration().as_secs_f64();
+        let sound_data = self.trim_silence(sound_data);
+
+        let mut handle = self.manager
+            .play(sound_data)
+            .map_err(|e| format!("Failed to play audio: {}", e))?;
+        handle.set_volume(self.output_volume(), tween);
 
         self.current_handle = Some(handle);
+        self.track_serial += 1;
         self.current_file = Some(path.clone());
         self.stopped = false;
         Ok(())
@@ -56,7 +253,7 @@ impl AudioEngine {
     pub fn play(&mut self) {
         if let Some(handle) = &mut self.current_handle {
             if self.stopped {
-                let _ = handle.seek_to(0.0);
+                let _ = handle.seek_to(self.sound_start);
                 let _ = handle.resume(Tween::default());
                 self.stopped = false;
             } else {
@@ -78,12 +275,14 @@ impl AudioEngine {
     }
 
     pub fn pause(&mut self) {
+        self.finish_seek();
         if let Some(handle) = &mut self.current_handle {
             let _ = handle.pause(Tween::default());
         }
     }
 
     pub fn stop(&mut self) {
+        self.finish_seek();
         if let Some(handle) = &mut self.current_handle {
             let _ = handle.pause(Tween::default());
             let _ = handle.seek_to(0.0);
@@ -96,27 +295,73 @@ impl AudioEngine {
             let _ = handle.stop(Tween::default());
         }
         self.current_handle = None;
+        self.pending_seek = None;
+        self.track_serial += 1;
         self.current_file = None;
         self.duration = 0.0;
         self.stopped = false;
     }
 
-    pub fn set_volume(&mut self, volume_linear: f32) {
-        let db = if volume_linear > 0.0 {
+    fn linear_to_db(volume_linear: f32) -> f32 {
+        if volume_linear > 0.0 {
             20.0 * volume_linear.log10()
         } else {
             -80.0
-        };
-        self.current_volume = db;
+        }
+    }
+
+    pub fn set_volume(&mut self, volume_linear: f32) {
+        self.current_volume = Self::linear_to_db(volume_linear);
+        self.apply_volume();
+    }
 
+    pub fn set_track_gain(&mut self, gain_db: f32) {
+        self.track_gain = gain_db;
+        self.apply_volume();
+    }
+
+    fn output_volume(&self) -> f32 {
+        Self::output_db(self.current_volume, self.track_gain)
+    }
+
+    fn output_db(volume_db: f32, track_gain: f32) -> f32 {
+        if volume_db <= -80.0 {
+            -80.0
+        } else {
+            volume_db + track_gain
+        }
+    }
+
+    fn apply_volume(&mut self) {
+        if self.pending_seek.is_some() {
+            return;
+        }
+        let db = self.output_volume();
         if let Some(handle) = &mut self.current_handle {
-            let _ = handle.set_volume(db, Tween::default());
+            handle.set_volume(db, Tween::default());
+        }
+    }
+
+    pub fn set_seek_fade(&mut self, enabled: bool) {
+        self.seek_fade = enabled;
+    }
+
+    fn fade_tween() -> Tween {
+        Tween {
+            duration: SEEK_FADE,
+            ..Default::default()
         }
     }
 
     pub fn seek(&mut self, position: f64) {
+        let fade = self.seek_fade && self.is_playing();
         if let Some(handle) = &mut self.current_handle {
-            let _ = handle.seek_to(position);
+            if fade {
+                handle.set_volume(-80.0, Self::fade_tween());
+                self.pending_seek = Some((position, Instant::now() + SEEK_FADE));
+            } else {
+                handle.seek_to(position);
+            }
         } else if let Some(path) = self.current_file.clone() {
             if self.play_song(&path).is_ok() {
                 if let Some(handle) = &mut self.current_handle {
@@ -127,17 +372,111 @@ impl AudioEngine {
         }
     }
 
-    pub fn is_playing(&self) -> bool {
+    pub fn tick(&mut self) {
+        if self.pending_seek.is_some_and(|(_, due)| Instant::now() >= due) {
+            self.finish_seek();
+        }
+        if let Some(end) = self.sound_end
+            && self.pending_seek.is_none()
+            && self.is_playing()
+            && self.get_position() >= end
+            && let Some(handle) = &mut self.current_handle
+        {
+            handle.stop(Tween::default());
+        }
+    }
+
+    pub fn set_skip_silence(&mut self, skip: Option<(f32, f64)>) {
+        self.skip_silence = skip;
+        if skip.is_none() {
+            self.sound_end = None;
+        }
+    }
+
+    fn trim_silence(&mut self, data: StaticSoundData) -> StaticSoundData {
+        self.sound_start = 0.0;
+        self.sound_end = None;
+        let Some((threshold_db, min_secs)) = self.skip_silence else {
+            return data;
+        };
+        let threshold = 10f32.powf(threshold_db / 20.0);
+        let loud = |f: &Frame| f.left.abs().max(f.right.abs()) > threshold;
+        let Some(first) = data.frames.iter().position(loud) else {
+            return data;
+        };
+        let last = data.frames.iter().rposition(loud).unwrap_or(first);
+        let rate = data.sample_rate as f64;
+        let end = (last + 1) as f64 / rate;
+        if self.duration - end >= min_secs {
+            self.sound_end = Some(end);
+        }
+        let start = first as f64 / rate;
+        if start >= min_secs {
+            self.sound_start = start;
+            return data.start_position(start);
+        }
+        data
+    }
+
+    fn finish_seek(&mut self) {
+        let Some((position, _)) = self.pending_seek.take() else {
+            return;
+        };
+        let db = self.output_volume();
+        if let Some(handle) = &mut self.current_handle {
+            handle.seek_to(position);
+            handle.set_volume(db, Self::fade_tween());
+        }
+    }
+
+    pub fn recover(&mut self, position: f64) -> Result<(), String> {
+        let Some(path) = self.current_file.clone() else {
+            return Ok(());
+        };
+        self.finish_seek();
+        let alive = self.current_handle.as_ref().is_some_and(|handle| {
+            !matches!(handle.state(), PlaybackState::Stopped | PlaybackState::Stopping)
+        });
+        if !alive {
+            self.play_song(&path)?;
+        }
+        if let Some(handle) = &mut self.current_handle {
+            handle.pause(Tween::default());
+            if !alive || (handle.position() - position).abs() > 1.0 {
+                handle.seek_to(position);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn playback_state(&self) -> PlaybackStatus {
+        let Some(handle) = &self.current_handle else {
+            return if self.current_file.is_some() {
+                PlaybackStatus::Stopped
+            } else {
+                PlaybackStatus::Empty
+            };
+        };
         if self.stopped {
-            return false;
+            return PlaybackStatus::Stopped;
         }
-        self.current_handle
-            .as_ref()
-            .map(|h| matches!(h.state(), PlaybackState::Playing | PlaybackState::Resuming))
-            .unwrap_or(false)
+        match handle.state() {
+            PlaybackState::Playing | PlaybackState::Resuming => PlaybackStatus::Playing,
+            PlaybackState::Pausing | PlaybackState::Paused | PlaybackState::WaitingToResume => {
+                PlaybackStatus::Paused
+            }
+            PlaybackState::Stopping | PlaybackState::Stopped => PlaybackStatus::Finished,
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback_state() == PlaybackStatus::Playing
     }
 
     pub fn get_position(&self) -> f64 {
+        if let Some((position, _)) = self.pending_seek {
+            return position;
+        }
         self.current_handle
             .as_ref()
             .map(|h| h.position())
@@ -149,13 +488,93 @@ impl AudioEngine {
     }
 
     pub fn is_finished(&self) -> bool {
-        self.current_handle
-            .as_ref()
-            .map(|h| matches!(h.state(), PlaybackState::Stopped | PlaybackState::Stopping))
-            .unwrap_or(false)
+        self.playback_state() == PlaybackStatus::Finished
+    }
+
+    pub fn track_serial(&self) -> u64 {
+        self.track_serial
     }
 
     pub fn current_file(&self) -> Option<&PathBuf> {
         self.current_file.as_ref()
     }
+
+    pub fn levels(&self) -> (f32, f32) {
+        self.levels.take()
+    }
+
+    pub fn set_limiter(&mut self, enabled: bool, threshold_db: f32, compressor: bool) {
+        self.limiter.set_enabled(enabled);
+        self.limiter.set_threshold_db(threshold_db);
+        self.limiter.set_compressor(compressor);
+    }
+
+    pub fn play_preview(&mut self, clip: LoopClip) -> Result<(), String> {
+        self.stop_preview();
+        let data = StaticSoundData {
+            sample_rate: clip.sample_rate,
+            frames: clip.frames.into(),
+            settings: StaticSoundSettings::default(),
+            slice: None,
+        }
+        .loop_region(..)
+        .volume(self.output_volume());
+        let handle = self.manager.play(data).map_err(|e| format!("Failed to play preview: {}", e))?;
+        self.preview_handle = Some(handle);
+        Ok(())
+    }
+
+    pub fn stop_preview(&mut self) {
+        if let Some(mut handle) = self.preview_handle.take() {
+            handle.stop(Tween::default());
+        }
+    }
+
+    pub fn is_previewing(&self) -> bool {
+        self.preview_handle.is_some()
+    }
+
+    pub fn set_crossfeed(&mut self, enabled: bool, cutoff_hz: f32, level_db: f32) {
+        self.crossfeed.set_enabled(enabled);
+        self.crossfeed.set_cutoff_hz(cutoff_hz);
+        self.crossfeed.set_level_db(level_db);
+    }
+
+    pub fn set_eq(&mut self, gains: Option<[f32; 10]>) {
+        let gains = gains.unwrap_or_default();
+        self.eq_gains = gains;
+        for (handle, gain) in self.eq.iter_mut().zip(gains) {
+            handle.set_gain(gain, Tween::default());
+        }
+    }
+
+    pub fn limiter_reduction(&self) -> f32 {
+        self.limiter.take_reduction()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+
+    #[test]
+    fn startup_volume_follows_the_output_device() {
+        let mut settings = Settings::default();
+        settings.device_volumes.insert("Speakers".to_string(), 0.8);
+        settings.device_volumes.insert("Broken".to_string(), 5.0);
+        assert_eq!(settings.startup_volume(None), 0.5);
+        assert_eq!(settings.startup_volume(Some("Headphones")), 0.5);
+        assert_eq!(settings.startup_volume(Some("Speakers")), 0.8);
+        assert_eq!(settings.startup_volume(Some("Broken")), settings.max_volume);
+
+        let db = AudioEngine::output_db(AudioEngine::linear_to_db(settings.startup_volume(None)), 0.0);
+        assert!((db - -6.0206).abs() < 0.001, "{}", db);
+    }
+
+    #[test]
+    fn track_gain_adds_to_the_volume_but_never_unmutes() {
+        assert_eq!(AudioEngine::output_db(AudioEngine::linear_to_db(1.0), -3.0), -3.0);
+        assert_eq!(AudioEngine::output_db(AudioEngine::linear_to_db(0.0), 6.0), -80.0);
+    }
 }
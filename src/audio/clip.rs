@@ -0,0 +1,120 @@
+use kira::{Frame, sound::static_sound::StaticSoundData};
+use std::io::Write;
+use std::path::Path;
+
+const CHANNELS: u16 = 2;
+const BITS_PER_SAMPLE: u16 = 16;
+const LOOP_CROSSFADE_SECS: f64 = 0.03;
+const MIN_LOOP_SECS: f64 = 0.05;
+
+pub fn export_clip(
+    source: &Path,
+    dest: &Path,
+    start: Option<f64>,
+    end: Option<f64>,
+    progress: impl Fn(usize, usize),
+) -> Result<f64, String> {
+    let data = StaticSoundData::from_file(source).map_err(|e| format!("Failed to load audio file: {}", e))?;
+    let rate = data.sample_rate;
+    let frames = &data.frames[..];
+    let to_frame = |secs: f64| ((secs.max(0.0) * rate as f64) as usize).min(frames.len());
+    let first = start.map(to_frame).unwrap_or(0);
+    let last = end.map(to_frame).unwrap_or(frames.len()).max(first);
+    if last == first {
+        return Err("The clip is empty; set the end point after the start point".to_string());
+    }
+    write_wav(dest, &frames[first..last], rate, progress)?;
+    Ok((last - first) as f64 / rate as f64)
+}
+
+pub struct LoopClip {
+    pub frames: Vec<Frame>,
+    pub sample_rate: u32,
+}
+
+pub fn render_loop(source: &Path, start: f64, end: f64) -> Result<LoopClip, String> {
+    if end - start < MIN_LOOP_SECS {
+        return Err(format!("The selection must be at least {} ms long to loop", (MIN_LOOP_SECS * 1000.0) as u32));
+    }
+    let data = StaticSoundData::from_file(source).map_err(|e| format!("Failed to load audio file: {}", e))?;
+    let rate = data.sample_rate;
+    let frames = &data.frames[..];
+    let to_frame = |secs: f64| ((secs.max(0.0) * rate as f64) as usize).min(frames.len());
+    let section = &frames[to_frame(start)..to_frame(end)];
+    if section.len() < 4 {
+        return Err("The selection is past the end of the track".to_string());
+    }
+    let fade = ((LOOP_CROSSFADE_SECS * rate as f64) as usize).min(section.len() / 4).max(1);
+    let body = section.len() - fade;
+    let mut looped = section[..body].to_vec();
+    for (i, frame) in looped.iter_mut().take(fade).enumerate() {
+        let t = (i as f32 + 0.5) / fade as f32 * std::f32::consts::FRAC_PI_2;
+        *frame = *frame * t.sin() + section[body + i] * t.cos();
+    }
+    Ok(LoopClip { frames: looped, sample_rate: rate })
+}
+
+pub fn export_loop(clip: &LoopClip, dest: &Path, progress: impl Fn(usize, usize)) -> Result<(), String> {
+    write_wav(dest, &clip.frames, clip.sample_rate, progress)
+}
+
+fn wav_data_len(frames: usize, block_align: u16) -> Result<u32, String> {
+    if frames == 0 {
+        return Err("The clip is empty".to_string());
+    }
+    (frames as u64)
+        .checked_mul(block_align as u64)
+        .and_then(|len| u32::try_from(len).ok())
+        .filter(|len| len.checked_add(36).is_some())
+        .ok_or_else(|| "The clip is too long to save as a WAV file".to_string())
+}
+
+fn write_wav(path: &Path, frames: &[Frame], rate: u32, progress: impl Fn(usize, usize)) -> Result<(), String> {
+    let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+    let data_len = wav_data_len(frames.len(), block_align)?;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&CHANNELS.to_le_bytes());
+    bytes.extend_from_slice(&rate.to_le_bytes());
+    bytes.extend_from_slice(&(rate * block_align as u32).to_le_bytes());
+    bytes.extend_from_slice(&block_align.to_le_bytes());
+    bytes.extend_from_slice(&BITS_PER_SAMPLE.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    let to_pcm = |sample: f32| (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+    for (i, chunk) in frames.chunks(rate as usize).enumerate() {
+        for frame in chunk {
+            bytes.extend_from_slice(&to_pcm(frame.left).to_le_bytes());
+            bytes.extend_from_slice(&to_pcm(frame.right).to_le_bytes());
+        }
+        progress(i + 1, frames.len().div_ceil(rate as usize));
+    }
+    let mut file = std::fs::File::create(path).map_err(|e| format!("Failed to create clip: {}", e))?;
+    file.write_all(&bytes).map_err(|e| format!("Failed to write clip: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_clips_are_rejected() {
+        assert!(wav_data_len(0, 4).is_err());
+        let dest = std::env::temp_dir().join(format!("kiraboshi-empty-clip-{}.wav", std::process::id()));
+        assert!(write_wav(&dest, &[], 44100, |_, _| {}).is_err());
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn clips_past_the_wav_size_limit_are_rejected() {
+        assert_eq!(wav_data_len(1000, 4), Ok(4000));
+        let limit = (u32::MAX as usize - 36) / 4;
+        assert!(wav_data_len(limit, 4).is_ok());
+        assert!(wav_data_len(limit + 1, 4).is_err());
+        assert!(wav_data_len(usize::MAX, 4).is_err());
+    }
+}
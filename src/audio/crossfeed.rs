@@ -0,0 +1,87 @@
+use kira::{
+    Frame,
+    effect::{Effect, EffectBuilder},
+    info::Info,
+};
+use std::collections::VecDeque;
+use std::f64::consts::TAU;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+const DELAY_SECONDS: f64 = 0.0003;
+
+pub struct CrossfeedShared {
+    enabled: AtomicBool,
+    cutoff_hz: AtomicU32,
+    level_db: AtomicU32,
+}
+
+impl CrossfeedShared {
+    pub fn new(enabled: bool, cutoff_hz: f32, level_db: f32) -> Self {
+        Self {
+            enabled: AtomicBool::new(enabled),
+            cutoff_hz: AtomicU32::new(cutoff_hz.to_bits()),
+            level_db: AtomicU32::new(level_db.to_bits()),
+        }
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn set_cutoff_hz(&self, cutoff_hz: f32) {
+        self.cutoff_hz.store(cutoff_hz.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn set_level_db(&self, level_db: f32) {
+        self.level_db.store(level_db.to_bits(), Ordering::Relaxed);
+    }
+}
+
+pub struct CrossfeedBuilder(pub Arc<CrossfeedShared>);
+
+impl EffectBuilder for CrossfeedBuilder {
+    type Handle = ();
+
+    fn build(self) -> (Box<dyn Effect>, Self::Handle) {
+        let crossfeed = Crossfeed {
+            shared: self.0,
+            low: Frame::ZERO,
+            delay: VecDeque::new(),
+        };
+        (Box::new(crossfeed), ())
+    }
+}
+
+struct Crossfeed {
+    shared: Arc<CrossfeedShared>,
+    low: Frame,
+    delay: VecDeque<Frame>,
+}
+
+impl Effect for Crossfeed {
+    fn process(&mut self, input: &mut [Frame], dt: f64, _info: &Info) {
+        if !self.shared.enabled.load(Ordering::Relaxed) {
+            self.low = Frame::ZERO;
+            self.delay.clear();
+            return;
+        }
+        let cutoff_hz = f32::from_bits(self.shared.cutoff_hz.load(Ordering::Relaxed)) as f64;
+        let level_db = f32::from_bits(self.shared.level_db.load(Ordering::Relaxed));
+        let feed = 10.0f32.powf(-level_db / 20.0);
+        let norm = 1.0 / (1.0 + feed);
+        let smoothing = (-TAU * cutoff_hz * dt).exp() as f32;
+        let delay_frames = ((DELAY_SECONDS / dt).round() as usize).max(1);
+        while self.delay.len() < delay_frames {
+            self.delay.push_front(Frame::ZERO);
+        }
+        self.delay.truncate(delay_frames);
+        for frame in input.iter_mut() {
+            self.low = *frame + (self.low - *frame) * smoothing;
+            self.delay.push_back(self.low);
+            let crossed = self.delay.pop_front().unwrap_or(Frame::ZERO);
+            frame.left = (frame.left + crossed.right * feed) * norm;
+            frame.right = (frame.right + crossed.left * feed) * norm;
+        }
+    }
+}
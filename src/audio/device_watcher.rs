@@ -0,0 +1,43 @@
+use super::output_device_name;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub struct DeviceWatcher {
+    receiver: Receiver<String>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl DeviceWatcher {
+    pub fn start(current: Option<String>, interval: Duration) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let thread_shutdown = Arc::clone(&shutdown);
+        std::thread::spawn(move || {
+            let mut last = current;
+            while !thread_shutdown.load(Ordering::Relaxed) {
+                std::thread::sleep(interval);
+                if let Some(device) = output_device_name()
+                    && last.as_ref() != Some(&device)
+                {
+                    if sender.send(device.clone()).is_err() {
+                        break;
+                    }
+                    last = Some(device);
+                }
+            }
+        });
+        Self { receiver, shutdown }
+    }
+
+    pub fn poll(&self) -> Option<String> {
+        self.receiver.try_iter().last()
+    }
+}
+
+impl Drop for DeviceWatcher {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+pub const EQ_BANDS: [f32; 10] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+pub const EQ_MIN_FREQ: f32 = 20.0;
+pub const EQ_MAX_FREQ: f32 = 20000.0;
+pub const EQ_MAX_GAIN: f32 = 12.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct EqPoint {
+    pub frequency: f32,
+    pub gain_db: f32,
+}
+
+impl EqPoint {
+    pub fn clamped(self) -> Self {
+        Self {
+            frequency: self.frequency.clamp(EQ_MIN_FREQ, EQ_MAX_FREQ),
+            gain_db: self.gain_db.clamp(-EQ_MAX_GAIN, EQ_MAX_GAIN),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EqPreset {
+    pub name: String,
+    pub points: Vec<EqPoint>,
+}
+
+pub fn sort_points(points: &mut [EqPoint]) {
+    points.sort_by(|a, b| a.frequency.total_cmp(&b.frequency));
+}
+
+pub fn curve_gain(points: &[EqPoint], frequency: f32) -> f32 {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return 0.0;
+    };
+    if frequency <= first.frequency {
+        return first.gain_db;
+    }
+    if frequency >= last.frequency {
+        return last.gain_db;
+    }
+    let position = frequency.log10();
+    for pair in points.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        if frequency <= b.frequency {
+            let span = b.frequency.log10() - a.frequency.log10();
+            if span <= 0.0 {
+                return b.gain_db;
+            }
+            let t = (position - a.frequency.log10()) / span;
+            return a.gain_db + (b.gain_db - a.gain_db) * t;
+        }
+    }
+    last.gain_db
+}
+
+pub fn band_gains(points: &[EqPoint]) -> [f32; 10] {
+    EQ_BANDS.map(|frequency| curve_gain(points, frequency))
+}
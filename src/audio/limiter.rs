@@ -0,0 +1,103 @@
+use kira::{
+    Frame,
+    effect::{Effect, EffectBuilder},
+    info::Info,
+};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+const RELEASE_SECONDS: f64 = 0.1;
+const COMPRESSOR_KNEE_DB: f32 = 6.0;
+const COMPRESSOR_RATIO: f32 = 4.0;
+
+fn db_to_amplitude(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
+
+pub struct LimiterShared {
+    enabled: AtomicBool,
+    compressor: AtomicBool,
+    threshold_db: AtomicU32,
+    reduction_db: AtomicU32,
+}
+
+impl LimiterShared {
+    pub fn new(enabled: bool, threshold_db: f32, compressor: bool) -> Self {
+        Self {
+            enabled: AtomicBool::new(enabled),
+            compressor: AtomicBool::new(compressor),
+            threshold_db: AtomicU32::new(threshold_db.to_bits()),
+            reduction_db: AtomicU32::new(0),
+        }
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn set_compressor(&self, compressor: bool) {
+        self.compressor.store(compressor, Ordering::Relaxed);
+    }
+
+    pub fn set_threshold_db(&self, threshold_db: f32) {
+        self.threshold_db.store(threshold_db.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn take_reduction(&self) -> f32 {
+        f32::from_bits(self.reduction_db.swap(0, Ordering::Relaxed))
+    }
+}
+
+pub struct LimiterBuilder(pub Arc<LimiterShared>);
+
+impl EffectBuilder for LimiterBuilder {
+    type Handle = ();
+
+    fn build(self) -> (Box<dyn Effect>, Self::Handle) {
+        (Box::new(Limiter { shared: self.0, gain: 1.0 }), ())
+    }
+}
+
+struct Limiter {
+    shared: Arc<LimiterShared>,
+    gain: f32,
+}
+
+impl Effect for Limiter {
+    fn process(&mut self, input: &mut [Frame], dt: f64, _info: &Info) {
+        if !self.shared.enabled.load(Ordering::Relaxed) {
+            self.gain = 1.0;
+            return;
+        }
+        let threshold_db = f32::from_bits(self.shared.threshold_db.load(Ordering::Relaxed));
+        let threshold = db_to_amplitude(threshold_db);
+        let compressor = self.shared.compressor.load(Ordering::Relaxed);
+        let knee_db = threshold_db - COMPRESSOR_KNEE_DB;
+        let release = (-dt / RELEASE_SECONDS).exp() as f32;
+        let mut min_gain = 1.0f32;
+        for frame in input.iter_mut() {
+            let peak = frame.left.abs().max(frame.right.abs());
+            let mut target = 1.0;
+            if compressor && peak > 0.0 {
+                let level_db = 20.0 * peak.log10();
+                if level_db > knee_db {
+                    let out_db = knee_db + (level_db - knee_db) / COMPRESSOR_RATIO;
+                    target = db_to_amplitude(out_db - level_db);
+                }
+            }
+            if peak * target > threshold {
+                target = threshold / peak;
+            }
+            self.gain = if target < self.gain {
+                target
+            } else {
+                target + (self.gain - target) * release
+            };
+            frame.left *= self.gain;
+            frame.right *= self.gain;
+            min_gain = min_gain.min(self.gain);
+        }
+        let reduction = -20.0 * min_gain.log10();
+        self.shared.reduction_db.fetch_max(reduction.max(0.0).to_bits(), Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,71 @@
+use std::path::Path;
+
+pub struct LyricLine {
+    pub time: f64,
+    pub text: String,
+}
+
+pub enum Lyrics {
+    Synced(Vec<LyricLine>),
+    Plain(String),
+}
+
+impl Lyrics {
+    pub fn load(path: &Path, embedded: Option<&str>) -> Option<Self> {
+        std::fs::read_to_string(path.with_extension("lrc"))
+            .ok()
+            .and_then(|text| Self::parse(&text))
+            .or_else(|| embedded.and_then(Self::parse))
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut offset = 0.0;
+        let mut lines = Vec::new();
+        for raw in text.lines() {
+            let mut rest = raw.trim();
+            let mut times = Vec::new();
+            while let Some(tag) = rest.strip_prefix('[') {
+                let Some(end) = tag.find(']') else {
+                    break;
+                };
+                let inner = &tag[..end];
+                if let Some(ms) = inner.strip_prefix("offset:") {
+                    offset = ms.trim().parse::<f64>().unwrap_or(0.0) / 1000.0;
+                } else if let Some(time) = Self::parse_timestamp(inner) {
+                    times.push(time);
+                }
+                rest = tag[end + 1..].trim_start();
+            }
+            for time in times {
+                lines.push(LyricLine {
+                    time,
+                    text: rest.to_string(),
+                });
+            }
+        }
+
+        if lines.is_empty() {
+            let plain = text.trim();
+            return (!plain.is_empty()).then(|| Lyrics::Plain(plain.to_string()));
+        }
+        for line in &mut lines {
+            line.time = (line.time - offset).max(0.0);
+        }
+        lines.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Some(Lyrics::Synced(lines))
+    }
+
+    fn parse_timestamp(tag: &str) -> Option<f64> {
+        let (mins, secs) = tag.split_once(':')?;
+        let mins: u32 = mins.trim().parse().ok()?;
+        let secs: f64 = secs.trim().replacen(':', ".", 1).parse().ok()?;
+        Some(mins as f64 * 60.0 + secs)
+    }
+
+    pub fn current_line(&self, position: f64) -> Option<usize> {
+        match self {
+            Lyrics::Synced(lines) => lines.iter().rposition(|l| l.time <= position),
+            Lyrics::Plain(_) => None,
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use symphonia::core::{
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::{MetadataOptions, MetadataRevision, StandardTagKey, StandardVisualKey},
+    probe::{Hint, ProbeResult},
+};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Chapter {
+    pub title: String,
+    pub start: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TrackMetadata {
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    pub album: Option<String>,
+    pub lyrics: Option<String>,
+    pub duration: Option<f64>,
+    pub chapters: Vec<Chapter>,
+    pub bpm: Option<f32>,
+    pub key: Option<String>,
+}
+
+impl TrackMetadata {
+    pub(super) fn probe(path: &Path) -> Option<ProbeResult> {
+        let file = std::fs::File::open(path).ok()?;
+        let mss = MediaSourceStream::new(Box::new(file), Default::default());
+        let mut hint = Hint::new();
+        if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+            hint.with_extension(ext);
+        }
+        symphonia::default::get_probe()
+            .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())
+            .ok()
+    }
+
+    pub fn read_artwork(path: &Path) -> Option<Vec<u8>> {
+        let mut probed = Self::probe(path)?;
+        let pick = |rev: &MetadataRevision| {
+            let visuals = rev.visuals();
+            visuals
+                .iter()
+                .find(|v| v.usage == Some(StandardVisualKey::FrontCover))
+                .or(visuals.first())
+                .map(|v| v.data.to_vec())
+        };
+        let from_container = probed.metadata.get().and_then(|log| log.current().and_then(pick));
+        from_container.or_else(|| probed.format.metadata().current().and_then(pick))
+    }
+
+    pub fn read(path: &Path) -> Self {
+        let mut metadata = Self::default();
+        let Some(mut probed) = Self::probe(path) else {
+            return metadata;
+        };
+        if let Some(log) = probed.metadata.get()
+            && let Some(rev) = log.current()
+        {
+            metadata.apply(rev);
+        }
+        if let Some(rev) = probed.format.metadata().current() {
+            metadata.apply(rev);
+        }
+        metadata.chapters.sort_by(|a, b| a.start.total_cmp(&b.start));
+        if let Some(track) = probed.format.default_track() {
+            let params = &track.codec_params;
+            if let (Some(frames), Some(rate)) = (params.n_frames, params.sample_rate) {
+                metadata.duration = Some(frames as f64 / rate as f64);
+            }
+        }
+        metadata
+    }
+
+    fn apply(&mut self, rev: &MetadataRevision) {
+        let mut chapters: Vec<(String, Chapter)> = Vec::new();
+        let mut chapter_names = Vec::new();
+        for tag in rev.tags() {
+            let value = tag.value.to_string().trim().to_string();
+            if value.is_empty() {
+                continue;
+            }
+            let key = tag.key.to_ascii_uppercase();
+            if let Some(id) = key.strip_prefix("CHAPTER") {
+                if let Some(id) = id.strip_suffix("NAME") {
+                    chapter_names.push((id.to_string(), value));
+                } else if let Some(start) = Self::parse_chapter_time(&value) {
+                    let title = format!("Chapter {}", id.trim_start_matches('0'));
+                    chapters.push((id.to_string(), Chapter { title, start }));
+                }
+                continue;
+            }
+            if tag.std_key == Some(StandardTagKey::Bpm) || key == "TBPM" || key == "BPM" {
+                if self.bpm.is_none() {
+                    self.bpm = value.replace(',', ".").parse::<f32>().ok().filter(|bpm| *bpm > 0.0);
+                }
+                continue;
+            }
+            if matches!(key.as_str(), "TKEY" | "KEY" | "INITIALKEY") || key.ends_with(":INITIALKEY") {
+                if self.key.is_none() {
+                    self.key = Some(value);
+                }
+                continue;
+            }
+            let slot = match tag.std_key {
+                Some(StandardTagKey::Artist) => &mut self.artist,
+                Some(StandardTagKey::TrackTitle) => &mut self.title,
+                Some(StandardTagKey::Album) => &mut self.album,
+                Some(StandardTagKey::Lyrics) => &mut self.lyrics,
+                _ => continue,
+            };
+            if slot.is_none() {
+                *slot = Some(value);
+            }
+        }
+        for (id, name) in chapter_names {
+            if let Some((_, chapter)) = chapters.iter_mut().find(|(c, _)| *c == id) {
+                chapter.title = name;
+            }
+        }
+        self.chapters.extend(chapters.into_iter().map(|(_, chapter)| chapter));
+    }
+
+    fn parse_chapter_time(value: &str) -> Option<f64> {
+        value
+            .split(':')
+            .try_fold(0.0, |acc, part| Some(acc * 60.0 + part.trim().parse::<f64>().ok()?))
+    }
+}
@@ -0,0 +1,46 @@
+use kira::{
+    Frame,
+    effect::{Effect, EffectBuilder},
+    info::Info,
+};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+#[derive(Default)]
+pub struct LevelMeterShared {
+    left: AtomicU32,
+    right: AtomicU32,
+}
+
+impl LevelMeterShared {
+    pub fn take(&self) -> (f32, f32) {
+        (
+            f32::from_bits(self.left.swap(0, Ordering::Relaxed)),
+            f32::from_bits(self.right.swap(0, Ordering::Relaxed)),
+        )
+    }
+}
+
+pub struct LevelMeterBuilder(pub Arc<LevelMeterShared>);
+
+impl EffectBuilder for LevelMeterBuilder {
+    type Handle = ();
+
+    fn build(self) -> (Box<dyn Effect>, Self::Handle) {
+        (Box::new(LevelMeter { shared: self.0 }), ())
+    }
+}
+
+struct LevelMeter {
+    shared: Arc<LevelMeterShared>,
+}
+
+impl Effect for LevelMeter {
+    fn process(&mut self, input: &mut [Frame], _dt: f64, _info: &Info) {
+        let (left, right) = input.iter().fold((0.0f32, 0.0f32), |(l, r), frame| {
+            (l.max(frame.left.abs()), r.max(frame.right.abs()))
+        });
+        self.shared.left.fetch_max(left.to_bits(), Ordering::Relaxed);
+        self.shared.right.fetch_max(right.to_bits(), Ordering::Relaxed);
+    }
+}
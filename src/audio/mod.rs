@@ -1,3 +1,22 @@
 mod audio;
+mod clip;
+mod crossfeed;
+mod device_watcher;
+mod equalizer;
+mod limiter;
+mod lyrics;
+mod metadata;
+mod meter;
+mod tempo;
+mod transitions;
+mod waveform;
 
 pub use audio::*;
+pub use clip::*;
+pub use device_watcher::*;
+pub use equalizer::*;
+pub use lyrics::*;
+pub use metadata::*;
+pub use tempo::*;
+pub use transitions::*;
+pub use waveform::*;
@@ -0,0 +1,86 @@
+use super::TrackMetadata;
+use std::path::Path;
+use symphonia::core::{audio::SampleBuffer, codecs::DecoderOptions, errors::Error};
+
+const HOPS_PER_SEC: f64 = 200.0;
+const ANALYSIS_SECS: f64 = 120.0;
+const MIN_BPM: f64 = 70.0;
+const MAX_BPM: f64 = 180.0;
+
+pub fn estimate_bpm(path: &Path) -> Result<Option<f32>, String> {
+    let energy = hop_energy(path)?;
+    let onsets: Vec<f64> = energy
+        .windows(2)
+        .map(|w| ((w[1] + 1e-9).ln() - (w[0] + 1e-9).ln()).max(0.0))
+        .collect();
+    let mean = onsets.iter().sum::<f64>() / onsets.len().max(1) as f64;
+    let onsets: Vec<f64> = onsets.iter().map(|o| o - mean).collect();
+    let min_lag = (60.0 * HOPS_PER_SEC / MAX_BPM).floor() as usize;
+    let max_lag = (60.0 * HOPS_PER_SEC / MIN_BPM).ceil() as usize;
+    if onsets.len() < max_lag * 4 {
+        return Ok(None);
+    }
+    let correlation = |lag: usize| -> f64 { onsets.iter().zip(&onsets[lag..]).map(|(a, b)| a * b).sum() };
+    let scores: Vec<f64> = (min_lag - 1..=max_lag + 1).map(correlation).collect();
+    let Some(best) = (1..scores.len() - 1).max_by(|&a, &b| scores[a].total_cmp(&scores[b])) else {
+        return Ok(None);
+    };
+    if scores[best] <= 0.0 {
+        return Ok(None);
+    }
+    let (left, center, right) = (scores[best - 1], scores[best], scores[best + 1]);
+    let curvature = left - 2.0 * center + right;
+    let offset = if curvature < 0.0 { 0.5 * (left - right) / curvature } else { 0.0 };
+    let lag = (min_lag - 1 + best) as f64 + offset;
+    let bpm = 60.0 * HOPS_PER_SEC / lag;
+    Ok(Some(((bpm * 10.0).round() / 10.0) as f32))
+}
+
+fn hop_energy(path: &Path) -> Result<Vec<f64>, String> {
+    let mut probed = TrackMetadata::probe(path).ok_or("Unsupported audio file")?;
+    let track = probed.format.default_track().ok_or("No audio track found")?;
+    let track_id = track.id;
+    let rate = track.codec_params.sample_rate.ok_or("Unknown sample rate")? as f64;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| format!("Failed to open decoder: {}", e))?;
+
+    let hop_frames = (rate / HOPS_PER_SEC).max(1.0) as usize;
+    let max_hops = (ANALYSIS_SECS * HOPS_PER_SEC) as usize;
+    let mut energy = Vec::new();
+    let mut sum = 0.0f64;
+    let mut count = 0usize;
+    let mut buffer: Option<SampleBuffer<f32>> = None;
+    while energy.len() < max_hops {
+        let packet = match probed.format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(_)) => break,
+            Err(e) => return Err(format!("Failed to read audio: {}", e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(Error::DecodeError(_)) => continue,
+            Err(e) => return Err(format!("Failed to decode audio: {}", e)),
+        };
+        let channels = decoded.spec().channels.count().max(1);
+        let samples = buffer.get_or_insert_with(|| SampleBuffer::new(decoded.capacity() as u64, *decoded.spec()));
+        if samples.capacity() < decoded.capacity() * channels {
+            *samples = SampleBuffer::new(decoded.capacity() as u64, *decoded.spec());
+        }
+        samples.copy_interleaved_ref(decoded);
+        for frame in samples.samples().chunks(channels) {
+            let mono = frame.iter().sum::<f32>() as f64 / channels as f64;
+            sum += mono * mono;
+            count += 1;
+            if count == hop_frames {
+                energy.push(sum / count as f64);
+                sum = 0.0;
+                count = 0;
+            }
+        }
+    }
+    Ok(energy)
+}
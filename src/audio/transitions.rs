@@ -0,0 +1,107 @@
+use super::TrackMetadata;
+use std::path::Path;
+use symphonia::core::{audio::SampleBuffer, codecs::DecoderOptions, errors::Error};
+
+const WINDOW_SECS: f64 = 1.0;
+const CONTEXT_WINDOWS: usize = 16;
+const MIN_GAP_WINDOWS: usize = 60;
+const THRESHOLD_DB: f32 = 4.0;
+
+pub fn detect_transitions(path: &Path, progress: impl Fn(usize, usize)) -> Result<Vec<f64>, String> {
+    let energy = window_energy(path, progress)?;
+    Ok(find_boundaries(&energy))
+}
+
+fn window_energy(path: &Path, progress: impl Fn(usize, usize)) -> Result<Vec<f32>, String> {
+    let mut probed = TrackMetadata::probe(path).ok_or("Unsupported audio file")?;
+    let track = probed.format.default_track().ok_or("No audio track found")?;
+    let track_id = track.id;
+    let rate = track.codec_params.sample_rate.ok_or("Unknown sample rate")? as f64;
+    let total = track
+        .codec_params
+        .n_frames
+        .map(|n| (n as f64 / rate / WINDOW_SECS).ceil() as usize)
+        .unwrap_or(0);
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| format!("Failed to open decoder: {}", e))?;
+
+    let window_frames = (rate * WINDOW_SECS) as usize;
+    let mut energy = Vec::new();
+    let mut sum = 0.0f64;
+    let mut count = 0usize;
+    let mut buffer: Option<SampleBuffer<f32>> = None;
+    loop {
+        let packet = match probed.format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(_)) => break,
+            Err(e) => return Err(format!("Failed to read audio: {}", e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(Error::DecodeError(_)) => continue,
+            Err(e) => return Err(format!("Failed to decode audio: {}", e)),
+        };
+        let channels = decoded.spec().channels.count().max(1);
+        let samples = buffer.get_or_insert_with(|| SampleBuffer::new(decoded.capacity() as u64, *decoded.spec()));
+        if samples.capacity() < decoded.capacity() * channels {
+            *samples = SampleBuffer::new(decoded.capacity() as u64, *decoded.spec());
+        }
+        samples.copy_interleaved_ref(decoded);
+        for frame in samples.samples().chunks(channels) {
+            sum += frame.iter().map(|s| (*s as f64) * (*s as f64)).sum::<f64>() / channels as f64;
+            count += 1;
+            if count == window_frames {
+                energy.push(to_db(sum / count as f64));
+                sum = 0.0;
+                count = 0;
+                progress(energy.len(), total.max(energy.len()));
+            }
+        }
+    }
+    if count > 0 {
+        energy.push(to_db(sum / count as f64));
+    }
+    Ok(energy)
+}
+
+fn to_db(mean_square: f64) -> f32 {
+    (10.0 * (mean_square + 1e-10).log10()) as f32
+}
+
+fn mean(values: &[f32]) -> f32 {
+    values.iter().sum::<f32>() / values.len().max(1) as f32
+}
+
+fn find_boundaries(energy: &[f32]) -> Vec<f64> {
+    if energy.len() < CONTEXT_WINDOWS * 2 + 1 {
+        return Vec::new();
+    }
+    let scores: Vec<f32> = (0..energy.len())
+        .map(|i| {
+            if i < CONTEXT_WINDOWS || i + CONTEXT_WINDOWS > energy.len() {
+                return 0.0;
+            }
+            let before = mean(&energy[i - CONTEXT_WINDOWS..i]);
+            let after = mean(&energy[i..i + CONTEXT_WINDOWS]);
+            let dip = before.min(after) - mean(&energy[i..(i + 2).min(energy.len())]);
+            (after - before).abs().max(dip)
+        })
+        .collect();
+    let mut boundaries = Vec::new();
+    for (i, &score) in scores.iter().enumerate() {
+        if score < THRESHOLD_DB {
+            continue;
+        }
+        let start = i.saturating_sub(MIN_GAP_WINDOWS / 2);
+        let end = (i + MIN_GAP_WINDOWS / 2).min(scores.len());
+        let strongest = (start..end).all(|j| scores[j] < score || (scores[j] == score && j >= i));
+        if strongest {
+            boundaries.push(i as f64 * WINDOW_SECS);
+        }
+    }
+    boundaries
+}
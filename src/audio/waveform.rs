@@ -0,0 +1,73 @@
+use super::TrackMetadata;
+use std::path::Path;
+use symphonia::core::{audio::SampleBuffer, codecs::DecoderOptions, errors::Error};
+
+pub const WAVEFORM_POINTS: usize = 48;
+const BLOCK_FRAMES: usize = 4096;
+
+pub fn waveform_peaks(path: &Path) -> Result<Vec<u8>, String> {
+    let blocks = block_peaks(path)?;
+    if blocks.is_empty() {
+        return Ok(Vec::new());
+    }
+    let peaks: Vec<f32> = (0..WAVEFORM_POINTS)
+        .map(|i| {
+            let start = i * blocks.len() / WAVEFORM_POINTS;
+            let end = ((i + 1) * blocks.len() / WAVEFORM_POINTS).max(start + 1).min(blocks.len());
+            blocks[start..end].iter().copied().fold(0.0, f32::max)
+        })
+        .collect();
+    let loudest = peaks.iter().copied().fold(0.0, f32::max);
+    if loudest <= 0.0 {
+        return Ok(vec![0; WAVEFORM_POINTS]);
+    }
+    Ok(peaks.iter().map(|p| (p / loudest * 255.0).round() as u8).collect())
+}
+
+fn block_peaks(path: &Path) -> Result<Vec<f32>, String> {
+    let mut probed = TrackMetadata::probe(path).ok_or("Unsupported audio file")?;
+    let track = probed.format.default_track().ok_or("No audio track found")?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| format!("Failed to open decoder: {}", e))?;
+
+    let mut peaks = Vec::new();
+    let mut peak = 0.0f32;
+    let mut count = 0usize;
+    let mut buffer: Option<SampleBuffer<f32>> = None;
+    loop {
+        let packet = match probed.format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(_)) => break,
+            Err(e) => return Err(format!("Failed to read audio: {}", e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(Error::DecodeError(_)) => continue,
+            Err(e) => return Err(format!("Failed to decode audio: {}", e)),
+        };
+        let channels = decoded.spec().channels.count().max(1);
+        let samples = buffer.get_or_insert_with(|| SampleBuffer::new(decoded.capacity() as u64, *decoded.spec()));
+        if samples.capacity() < decoded.capacity() * channels {
+            *samples = SampleBuffer::new(decoded.capacity() as u64, *decoded.spec());
+        }
+        samples.copy_interleaved_ref(decoded);
+        for frame in samples.samples().chunks(channels) {
+            peak = frame.iter().fold(peak, |p, s| p.max(s.abs()));
+            count += 1;
+            if count == BLOCK_FRAMES {
+                peaks.push(peak);
+                peak = 0.0;
+                count = 0;
+            }
+        }
+    }
+    if count > 0 {
+        peaks.push(peak);
+    }
+    Ok(peaks)
+}
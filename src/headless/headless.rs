@@ -0,0 +1,296 @@
+use crate::audio::{AudioEngine, TrackMetadata, band_gains, output_device_name};
+use crate::library::{NamedPlaylist, PlaylistFile, data_dir};
+use crate::playback::{LoopMode, PlayerCore};
+use crate::settings::Settings;
+use crate::status::{StatusServer, StatusSnapshot};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+const TICK: Duration = Duration::from_millis(50);
+const LIBRARY_SAVE_DELAY: Duration = Duration::from_secs(2);
+
+enum Command {
+    Play,
+    Pause,
+    Toggle,
+    Stop,
+    Next,
+    Previous,
+    Random,
+    Volume(f32),
+    Shuffle,
+    Reverse,
+    Loop,
+    Quit,
+}
+
+impl Command {
+    fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.split_whitespace();
+        let command = match parts.next()?.to_lowercase().as_str() {
+            "play" => Command::Play,
+            "pause" => Command::Pause,
+            "toggle" => Command::Toggle,
+            "stop" => Command::Stop,
+            "next" => Command::Next,
+            "prev" | "previous" => Command::Previous,
+            "random" | "lucky" => Command::Random,
+            "volume" => Command::Volume(parts.next()?.parse::<f32>().ok()? / 100.0),
+            "shuffle" => Command::Shuffle,
+            "reverse" => Command::Reverse,
+            "loop" => Command::Loop,
+            "quit" | "exit" => Command::Quit,
+            _ => return None,
+        };
+        Some(command)
+    }
+}
+
+struct Headless {
+    core: PlayerCore,
+    settings: Settings,
+    library: Vec<PathBuf>,
+    volume: f32,
+    status_server: Option<StatusServer>,
+    tags: Option<(PathBuf, TrackMetadata)>,
+    read_only: bool,
+    library_dirty: Option<Instant>,
+    library_load_failed: bool,
+}
+
+impl Headless {
+    fn new(status_port: Option<u16>, read_only: bool) -> Result<Self, String> {
+        let data_dir = data_dir();
+        let settings = Settings::load(&data_dir.join(".kiraboshi_settings"));
+        let mut library_load_failed = false;
+        let library = PlaylistFile::load(&data_dir.join(".kiraboshi")).unwrap_or_else(|e| {
+            eprintln!("{} Library changes will not be saved in this session.", e);
+            library_load_failed = true;
+            PlaylistFile::default()
+        });
+        let playlist = settings
+            .active_playlist
+            .as_deref()
+            .and_then(|name| NamedPlaylist::load(&data_dir.join(".kiraboshi_playlists"), name))
+            .map(|named| named.paths)
+            .unwrap_or_else(|| library.paths.clone());
+        let port = status_port.or(settings.status_server.then_some(settings.status_port));
+        let status_server = port.map(|port| StatusServer::start(port, settings.status_cors)).transpose()?;
+        let volume = settings.startup_volume(output_device_name().as_deref());
+        let mut audio = AudioEngine::new(volume, settings.output_sample_rate, settings.output_latency.buffer_frames());
+        if (audio.sample_rate(), audio.buffer_frames()) != (settings.output_sample_rate, settings.output_latency.buffer_frames()) {
+            eprintln!("The output device rejected the configured sample rate or latency, so device defaults are in use");
+        }
+        audio.set_seek_fade(settings.seek_fade);
+        audio.set_limiter(settings.limiter, settings.limiter_threshold_db, settings.compressor);
+        audio.set_crossfeed(settings.crossfeed, settings.crossfeed_cutoff_hz, settings.crossfeed_level_db);
+        audio.set_eq(settings.eq_enabled.then(|| band_gains(&settings.eq_points)));
+        audio.set_skip_silence(
+            settings
+                .skip_silence
+                .then_some((settings.silence_threshold_db, settings.silence_min_secs as f64)),
+        );
+        let playlist = playlist.into_iter().filter(|p| p.exists()).collect();
+        let mut core = PlayerCore::new(audio, playlist, library.tracks);
+        core.shuffle_mode = settings.shuffle_mode;
+        core.end_tolerance = settings.end_tolerance_secs as f64;
+        Ok(Self {
+            core,
+            volume,
+            settings,
+            library: library.paths,
+            status_server,
+            tags: None,
+            read_only,
+            library_dirty: None,
+            library_load_failed,
+        })
+    }
+
+    fn report(&mut self, serial: u64, result: Result<(), String>) {
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            return;
+        }
+        if self.core.audio.track_serial() == serial {
+            return;
+        }
+        if let Some(path) = self.core.audio.current_file() {
+            println!("Playing {}", path.display());
+        }
+        if !self.read_only && !self.library_load_failed {
+            self.library_dirty.get_or_insert_with(Instant::now);
+        }
+    }
+
+    fn flush_library(&mut self) {
+        if self.library_dirty.take().is_none() || self.library_load_failed {
+            return;
+        }
+        if let Err(e) = PlaylistFile::save(&data_dir().join(".kiraboshi"), &self.library, &self.core.track_data) {
+            eprintln!("{}", e);
+        }
+    }
+
+    fn start_track(&mut self, path: &Path) {
+        let serial = self.core.audio.track_serial();
+        let result = self.core.start_track(&path.to_path_buf(), None);
+        self.report(serial, result);
+    }
+
+    fn play_next(&mut self, fade: Option<Duration>) {
+        if self.core.audio.current_file().is_none() {
+            if let Some(first) = self.core.playlist.first().cloned() {
+                self.start_track(&first);
+            }
+            return;
+        }
+        let serial = self.core.audio.track_serial();
+        let result = self.core.play_next(&self.library, fade);
+        self.report(serial, result);
+    }
+
+    fn skip_next(&mut self) {
+        if self.core.audio.current_file().is_none() {
+            self.play_next(None);
+            return;
+        }
+        let fade = (self.settings.crossfade_on_next && self.core.audio.is_playing())
+            .then(|| Duration::from_secs_f32(self.settings.crossfade_secs));
+        let serial = self.core.audio.track_serial();
+        let result = self.core.skip_next(&self.library, fade);
+        self.report(serial, result);
+    }
+
+    fn play_previous(&mut self) {
+        if let Some(previous) = self.core.previous_index() {
+            let path = self.core.playlist[previous].clone();
+            self.start_track(&path);
+        }
+    }
+
+    fn handle(&mut self, command: Command) -> bool {
+        match command {
+            Command::Play if self.core.audio.current_file().is_none() => self.play_next(None),
+            Command::Play => self.core.audio.play(),
+            Command::Pause => self.core.audio.pause(),
+            Command::Toggle if self.core.audio.is_playing() => self.core.audio.pause(),
+            Command::Toggle => return self.handle(Command::Play),
+            Command::Stop => self.core.audio.stop(),
+            Command::Next => self.skip_next(),
+            Command::Previous => self.play_previous(),
+            Command::Random => {
+                if let Some(path) = self.core.radio_pick(&self.core.playlist) {
+                    self.start_track(&path);
+                }
+            }
+            Command::Volume(volume) => {
+                self.volume = volume.clamp(0.0, self.settings.max_volume);
+                self.core.audio.set_volume(self.volume);
+            }
+            Command::Shuffle => {
+                self.core.shuffle = !self.core.shuffle;
+                self.core.sequential_run = false;
+                println!("Shuffle {}", if self.core.shuffle { "on" } else { "off" });
+            }
+            Command::Reverse => {
+                self.core.reverse = !self.core.reverse;
+                println!("Reverse {}", if self.core.reverse { "on" } else { "off" });
+            }
+            Command::Loop => {
+                self.core.loop_mode = match self.core.loop_mode {
+                    LoopMode::Off => LoopMode::One,
+                    LoopMode::One => LoopMode::Album,
+                    LoopMode::Album => LoopMode::All,
+                    LoopMode::All => LoopMode::Radio,
+                    LoopMode::Radio => LoopMode::Off,
+                };
+                println!("Loop {}", self.core.loop_mode.as_str());
+            }
+            Command::Quit => return false,
+        }
+        true
+    }
+
+    fn tick(&mut self) {
+        if let Some(Err(e)) = self.core.detect_wake() {
+            eprintln!("Playback could not be restored after sleep: {}", e);
+        }
+        if self.core.tick() {
+            self.play_next(None);
+        }
+        if self.library_dirty.is_some_and(|since| since.elapsed() >= LIBRARY_SAVE_DELAY) {
+            self.flush_library();
+        }
+        self.update_status_snapshot();
+    }
+
+    fn update_status_snapshot(&mut self) {
+        if self.status_server.is_none() {
+            return;
+        }
+        let path = self.core.audio.current_file();
+        if let Some(path) = path
+            && self.tags.as_ref().is_none_or(|(p, _)| p != path)
+        {
+            self.tags = Some((path.clone(), TrackMetadata::read(path)));
+        }
+        let tags = self.tags.as_ref().filter(|(p, _)| Some(p) == path).map(|(_, t)| t.clone()).unwrap_or_default();
+        let Some(server) = &self.status_server else {
+            return;
+        };
+        server.update(StatusSnapshot {
+            track: path.and_then(|p| p.file_stem()).map(|n| n.to_string_lossy().into_owned()),
+            artist: tags.artist,
+            title: tags.title,
+            path: path.filter(|_| self.settings.status_paths).map(|p| p.display().to_string()),
+            playing: self.core.audio.is_playing(),
+            state: self.core.audio.playback_state().as_str().to_string(),
+            position: self.core.audio.get_position(),
+            duration: self.core.audio.get_duration(),
+            volume: self.volume,
+            loop_mode: self.core.loop_mode.as_str().to_string(),
+            shuffle: self.core.shuffle,
+            reverse: self.core.reverse,
+        });
+    }
+}
+
+fn read_commands() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+pub fn run(status_port: Option<u16>, read_only: bool) -> Result<(), String> {
+    let mut player = Headless::new(status_port, read_only)?;
+    println!("Kiraboshi headless: {} tracks", player.core.playlist.len());
+    player.play_next(None);
+    let commands = read_commands();
+    loop {
+        match commands.try_recv() {
+            Ok(line) => match Command::parse(&line) {
+                Some(command) => {
+                    if !player.handle(command) {
+                        break;
+                    }
+                }
+                None => eprintln!("Unknown command: {}", line.trim()),
+            },
+            Err(_) => {
+                player.tick();
+                std::thread::sleep(TICK);
+            }
+        }
+    }
+    player.flush_library();
+    Ok(())
+}
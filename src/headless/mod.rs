@@ -0,0 +1,3 @@
+mod headless;
+
+pub use headless::*;
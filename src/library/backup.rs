@@ -0,0 +1,243 @@
+use super::playlist_file::{deserialize_path, serialize_path};
+use serde::{Deserialize, Serialize};
+use std::io::BufReader;
+use std::path::{Component, Path, PathBuf};
+
+const BACKUP_VERSION: u32 = 1;
+const BASE64_CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64_INVALID: u8 = 0xFF;
+const BASE64_VALUES: [u8; 256] = {
+    let mut table = [BASE64_INVALID; 256];
+    let mut i = 0;
+    while i < BASE64_CHARS.len() {
+        table[BASE64_CHARS[i] as usize] = i as u8;
+        i += 1;
+    }
+    table
+};
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_CHARS[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in text.bytes().filter(|&c| c != b'=') {
+        let value = match BASE64_VALUES[c as usize] {
+            BASE64_INVALID => return None,
+            value => value as u32,
+        };
+        buffer = buffer << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RestoreMode {
+    Merge,
+    Replace,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupEntry {
+    #[serde(serialize_with = "serialize_path", deserialize_with = "deserialize_path")]
+    path: PathBuf,
+    data: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Backup {
+    version: u32,
+    files: Vec<BackupEntry>,
+}
+
+impl Backup {
+    fn is_metadata(relative: &Path) -> bool {
+        relative
+            .components()
+            .next()
+            .is_some_and(|c| c.as_os_str().to_string_lossy().starts_with(".kiraboshi"))
+            && !relative.to_string_lossy().ends_with(".tmp")
+    }
+
+    fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) {
+        for path in std::fs::read_dir(dir).into_iter().flatten().filter_map(|e| e.ok()).map(|e| e.path()) {
+            if path.is_dir() {
+                Self::collect_files(&path, out);
+            } else {
+                out.push(path);
+            }
+        }
+    }
+
+    pub fn export(
+        data_dir: &Path,
+        include_media: bool,
+        dest: &Path,
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<usize, String> {
+        let mut paths = Vec::new();
+        Self::collect_files(data_dir, &mut paths);
+        paths.retain(|p| {
+            p.strip_prefix(data_dir)
+                .is_ok_and(|relative| include_media || Self::is_metadata(relative))
+        });
+        paths.sort();
+        super::atomic_write_with(dest, |out| {
+            write!(out, "{{\"version\":{},\"files\":[", BACKUP_VERSION)?;
+            for (done, path) in paths.iter().enumerate() {
+                progress(done, paths.len());
+                let bytes = std::fs::read(path)
+                    .map_err(|e| std::io::Error::other(format!("could not read {}: {}", path.display(), e)))?;
+                if done > 0 {
+                    out.write_all(b",")?;
+                }
+                let entry = BackupEntry {
+                    path: path.strip_prefix(data_dir).unwrap_or(path).to_path_buf(),
+                    data: encode_base64(&bytes),
+                };
+                serde_json::to_writer(&mut *out, &entry)?;
+            }
+            out.write_all(b"]}")
+        })
+        .map_err(|e| format!("Failed to write backup: {}", e))?;
+        progress(paths.len(), paths.len());
+        Ok(paths.len())
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| format!("Failed to read backup: {}", e))?;
+        let backup: Self = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| format!("Invalid backup file: {}", e))?;
+        if backup
+            .files
+            .iter()
+            .any(|f| !f.path.components().all(|c| matches!(c, Component::Normal(_))))
+        {
+            return Err("Backup contains paths outside the data directory".to_string());
+        }
+        Ok(backup)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn media_count(&self) -> usize {
+        self.files.iter().filter(|f| !Self::is_metadata(&f.path)).count()
+    }
+
+    pub fn file(&self, relative: &Path) -> Option<Vec<u8>> {
+        self.files
+            .iter()
+            .find(|f| f.path == relative)
+            .and_then(|f| decode_base64(&f.data))
+    }
+
+    pub fn restore(
+        &self,
+        data_dir: &Path,
+        mode: RestoreMode,
+        skip: &[&Path],
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<(), String> {
+        let entries: Vec<&BackupEntry> = self.files.iter().filter(|f| !skip.contains(&f.path.as_path())).collect();
+        for (done, entry) in entries.iter().enumerate() {
+            progress(done, entries.len());
+            let dest = data_dir.join(&entry.path);
+            if mode == RestoreMode::Merge && dest.exists() {
+                continue;
+            }
+            let bytes = decode_base64(&entry.data)
+                .ok_or_else(|| format!("Corrupt entry in backup: {}", entry.path.display()))?;
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+            }
+            super::atomic_write(&dest, bytes)
+                .map_err(|e| format!("Failed to restore {}: {}", entry.path.display(), e))?;
+        }
+        progress(entries.len(), entries.len());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kiraboshi-backup-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn base64_round_trips_every_length() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        for len in 0..bytes.len() {
+            assert_eq!(decode_base64(&encode_base64(&bytes[..len])).unwrap(), &bytes[..len]);
+        }
+        assert_eq!(encode_base64(b"Kiraboshi"), "S2lyYWJvc2hp");
+        assert!(decode_base64("S2ly*WJv").is_none());
+    }
+
+    #[test]
+    fn base64_pads_one_and_two_byte_tails() {
+        for (bytes, text) in [(&b"f"[..], "Zg=="), (b"fo", "Zm8="), (b"foo", "Zm9v"), (b"foob", "Zm9vYg=="), (b"fooba", "Zm9vYmE=")] {
+            assert_eq!(encode_base64(bytes), text);
+            assert_eq!(decode_base64(text).unwrap(), bytes);
+        }
+        assert_eq!(decode_base64(&encode_base64(&[0xFF])).unwrap(), [0xFF]);
+        assert_eq!(decode_base64(&encode_base64(&[0xFF, 0x00])).unwrap(), [0xFF, 0x00]);
+    }
+
+    #[test]
+    fn exported_backup_restores_into_an_empty_folder() {
+        let dir = temp_dir("round-trip");
+        let data = dir.join("data");
+        std::fs::create_dir_all(data.join(".kiraboshi_playlists")).unwrap();
+        std::fs::create_dir_all(data.join("album")).unwrap();
+        std::fs::write(data.join(".kiraboshi"), "library").unwrap();
+        std::fs::write(data.join(".kiraboshi_playlists").join("Mix.json"), "mix").unwrap();
+        std::fs::write(data.join("album").join("song.mp3"), [0u8, 1, 2, 255]).unwrap();
+
+        let archive = dir.join("backup.json");
+        assert_eq!(Backup::export(&data, false, &archive, |_, _| {}).unwrap(), 2);
+        assert_eq!(Backup::read(&archive).unwrap().media_count(), 0);
+        assert_eq!(Backup::export(&data, true, &archive, |_, _| {}).unwrap(), 3);
+
+        let backup = Backup::read(&archive).unwrap();
+        assert_eq!((backup.len(), backup.media_count()), (3, 1));
+        let restored = dir.join("restored");
+        let mut last = (0, 0);
+        backup.restore(&restored, RestoreMode::Replace, &[], |done, total| last = (done, total)).unwrap();
+        assert_eq!(last, (3, 3));
+        assert_eq!(std::fs::read(restored.join("album").join("song.mp3")).unwrap(), [0u8, 1, 2, 255]);
+        assert_eq!(std::fs::read_to_string(restored.join(".kiraboshi")).unwrap(), "library");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::audio::Chapter;
+use std::path::{Path, PathBuf};
+
+pub struct CueTrack {
+    pub file: String,
+    pub number: u32,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub start: f64,
+}
+
+pub struct CueSheet {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub tracks: Vec<CueTrack>,
+}
+
+impl CueSheet {
+    pub fn parse(text: &str) -> Self {
+        let mut sheet = CueSheet {
+            title: None,
+            performer: None,
+            tracks: Vec::new(),
+        };
+        let mut file = String::new();
+        let mut current: Option<CueTrack> = None;
+        for line in text.lines() {
+            let line = line.trim();
+            let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+            match command.to_ascii_uppercase().as_str() {
+                "FILE" => file = Self::unquote(Self::strip_file_type(rest)),
+                "TRACK" => {
+                    if let Some(track) = current.take() {
+                        sheet.tracks.push(track);
+                    }
+                    let number = rest.split_whitespace().next().and_then(|n| n.parse().ok()).unwrap_or(0);
+                    current = Some(CueTrack {
+                        file: file.clone(),
+                        number,
+                        title: None,
+                        performer: None,
+                        start: 0.0,
+                    });
+                }
+                "TITLE" => match &mut current {
+                    Some(track) => track.title = Some(Self::unquote(rest)),
+                    None => sheet.title = Some(Self::unquote(rest)),
+                },
+                "PERFORMER" => match &mut current {
+                    Some(track) => track.performer = Some(Self::unquote(rest)),
+                    None => sheet.performer = Some(Self::unquote(rest)),
+                },
+                "INDEX" => {
+                    let mut parts = rest.split_whitespace();
+                    if parts.next() == Some("01")
+                        && let (Some(track), Some(time)) = (&mut current, parts.next().and_then(Self::parse_time))
+                    {
+                        track.start = time;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(track) = current.take() {
+            sheet.tracks.push(track);
+        }
+        sheet
+    }
+
+    pub fn find_for(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_string_lossy();
+        let mut cue_name = path.file_name()?.to_os_string();
+        cue_name.push(".cue");
+        let candidates: [PathBuf; 2] = [path.with_extension("cue"), path.with_file_name(cue_name)];
+        candidates
+            .iter()
+            .filter_map(|c| std::fs::read(c).ok())
+            .map(|bytes| Self::parse(&String::from_utf8_lossy(&bytes)))
+            .find(|sheet| !sheet.tracks_for(&file_name).is_empty())
+    }
+
+    pub fn tracks_for(&self, file_name: &str) -> Vec<&CueTrack> {
+        let single_file = self.tracks.iter().all(|t| t.file == self.tracks[0].file);
+        self.tracks
+            .iter()
+            .filter(|t| single_file || t.file.eq_ignore_ascii_case(file_name))
+            .collect()
+    }
+
+    pub fn chapters_for(&self, file_name: &str) -> Vec<Chapter> {
+        self.tracks_for(file_name)
+            .into_iter()
+            .map(|t| Chapter {
+                title: t.title.clone().unwrap_or_else(|| format!("Track {:02}", t.number)),
+                start: t.start,
+            })
+            .collect()
+    }
+
+    pub fn split_chapters(path: &Path) -> Vec<Chapter> {
+        let file_name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        Self::find_for(path)
+            .map(|sheet| sheet.chapters_for(&file_name))
+            .filter(|chapters| chapters.len() > 1)
+            .unwrap_or_default()
+    }
+
+    fn strip_file_type(rest: &str) -> &str {
+        match rest.rsplit_once(char::is_whitespace) {
+            Some((name, kind))
+                if kind.starts_with(|c: char| c.is_ascii_uppercase())
+                    && kind.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) =>
+            {
+                name.trim()
+            }
+            _ => rest,
+        }
+    }
+
+    fn unquote(value: &str) -> String {
+        value.trim().trim_matches('"').to_string()
+    }
+
+    fn parse_time(value: &str) -> Option<f64> {
+        let mut parts = value.split(':');
+        let mins: f64 = parts.next()?.parse().ok()?;
+        let secs: f64 = parts.next()?.parse().ok()?;
+        let frames: f64 = parts.next()?.parse().ok()?;
+        Some(mins * 60.0 + secs + frames / 75.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_file_sheet_starts_tracks_at_index_01() {
+        let sheet = CueSheet::parse(
+            "PERFORMER \"Band\"\nTITLE \"Live\"\nFILE \"Live Set.flac\" WAVE\n  TRACK 01 AUDIO\n    TITLE \"Intro\"\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    PERFORMER \"Guest\"\n    INDEX 00 03:58:00\n    INDEX 01 04:00:37\n",
+        );
+        assert_eq!((sheet.title.as_deref(), sheet.performer.as_deref()), (Some("Live"), Some("Band")));
+        assert_eq!(sheet.tracks.len(), 2);
+        assert_eq!(sheet.tracks[0].file, "Live Set.flac");
+        assert_eq!(sheet.tracks[1].performer.as_deref(), Some("Guest"));
+        assert!((sheet.tracks[1].start - (240.0 + 37.0 / 75.0)).abs() < 1e-9);
+
+        let chapters = sheet.chapters_for("renamed.flac");
+        assert_eq!(chapters.len(), 2);
+        assert_eq!((chapters[0].title.as_str(), chapters[1].title.as_str()), ("Intro", "Track 02"));
+    }
+
+    #[test]
+    fn multi_file_sheet_only_matches_the_named_file() {
+        let sheet = CueSheet::parse(
+            "FILE \"Disc 1.wav\" WAVE\nTRACK 01 AUDIO\nINDEX 01 00:00:00\nTRACK 02 AUDIO\nINDEX 01 02:00:00\nFILE disc2.mp3 MP3\nTRACK 03 AUDIO\nINDEX 01 00:00:00\n",
+        );
+        let numbers = |name| sheet.tracks_for(name).iter().map(|t| t.number).collect::<Vec<_>>();
+        assert_eq!(numbers("disc 1.WAV"), [1, 2]);
+        assert_eq!(numbers("disc2.mp3"), [3]);
+        assert!(numbers("other.flac").is_empty());
+    }
+
+    #[test]
+    fn file_type_is_stripped_only_when_present() {
+        assert_eq!(CueSheet::strip_file_type("\"My Album.flac\" WAVE"), "\"My Album.flac\"");
+        assert_eq!(CueSheet::strip_file_type("album.mp3 MP3"), "album.mp3");
+        assert_eq!(CueSheet::strip_file_type("\"My Album.flac\""), "\"My Album.flac\"");
+        assert_eq!(CueSheet::unquote(CueSheet::strip_file_type("\"A B.wav\" WAVE")), "A B.wav");
+    }
+
+    #[test]
+    fn split_chapters_reads_the_sheet_next_to_the_file() {
+        let dir = std::env::temp_dir().join(format!("kiraboshi-cue-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let audio = dir.join("album.flac");
+        std::fs::write(&audio, []).unwrap();
+        assert!(CueSheet::split_chapters(&audio).is_empty());
+
+        std::fs::write(
+            dir.join("album.cue"),
+            "FILE \"album.flac\" WAVE\nTRACK 01 AUDIO\nINDEX 01 00:00:00\nTRACK 02 AUDIO\nINDEX 01 01:00:00\n",
+        )
+        .unwrap();
+        let starts: Vec<f64> = CueSheet::split_chapters(&audio).iter().map(|c| c.start).collect();
+        assert_eq!(starts, [0.0, 60.0]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
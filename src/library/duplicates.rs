@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hasher};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const HASH_PREFIX_BYTES: u64 = 1024 * 1024;
+
+pub fn content_hash(path: &Path) -> Option<u64> {
+    let file = std::fs::File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    let mut prefix = Vec::new();
+    file.take(HASH_PREFIX_BYTES).read_to_end(&mut prefix).ok()?;
+    let mut hasher = DefaultHasher::new();
+    hasher.write_u64(len);
+    hasher.write(&prefix);
+    Some(hasher.finish())
+}
+
+pub fn find_duplicates(
+    paths: &[PathBuf],
+    hash_cache: &mut HashMap<PathBuf, u64>,
+    mut progress: impl FnMut(usize, usize),
+) -> Vec<Vec<PathBuf>> {
+    let mut by_size: HashMap<u64, Vec<&PathBuf>> = HashMap::new();
+    for path in paths {
+        if let Ok(meta) = std::fs::metadata(path) {
+            by_size.entry(meta.len()).or_default().push(path);
+        }
+    }
+    let candidates: Vec<&PathBuf> = by_size.into_values().filter(|c| c.len() > 1).flatten().collect();
+    let mut by_hash: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for (done, &path) in candidates.iter().enumerate() {
+        progress(done, candidates.len());
+        let hash = match hash_cache.get(path) {
+            Some(hash) => *hash,
+            None => {
+                let Some(hash) = content_hash(path) else {
+                    continue;
+                };
+                hash_cache.insert(path.clone(), hash);
+                hash
+            }
+        };
+        by_hash.entry(hash).or_default().push(path.clone());
+    }
+    progress(candidates.len(), candidates.len());
+    let mut groups: Vec<Vec<PathBuf>> = by_hash.into_values().filter(|g| g.len() > 1).collect();
+    for group in &mut groups {
+        group.sort_by_key(|p| paths.iter().position(|q| q == p));
+    }
+    groups.sort_by_key(|g| paths.iter().position(|q| *q == g[0]));
+    groups
+}
@@ -0,0 +1,54 @@
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CollisionMode {
+    Skip,
+    Overwrite,
+    KeepBoth,
+}
+
+impl CollisionMode {
+    pub const ALL: [CollisionMode; 3] = [CollisionMode::Skip, CollisionMode::Overwrite, CollisionMode::KeepBoth];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CollisionMode::Skip => "Skip",
+            CollisionMode::Overwrite => "Overwrite",
+            CollisionMode::KeepBoth => "Keep both",
+        }
+    }
+}
+
+fn numbered(dest: &Path) -> PathBuf {
+    let stem = dest.file_stem().map(|s| s.to_os_string()).unwrap_or_default();
+    let ext = dest.extension().map(|e| e.to_os_string());
+    let mut n = 2;
+    loop {
+        let mut name = stem.clone();
+        name.push(format!(" ({})", n));
+        if let Some(ext) = &ext {
+            name.push(".");
+            name.push(ext);
+        }
+        let candidate = dest.with_file_name(name);
+        if !candidate.exists() {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+pub fn export_track(source: &Path, folder: &Path, mode: CollisionMode) -> Result<bool, String> {
+    let file_name = source.file_name().ok_or("Invalid file name")?;
+    let mut dest = folder.join(file_name);
+    if dest.exists() {
+        match mode {
+            CollisionMode::Skip => return Ok(false),
+            CollisionMode::Overwrite => {}
+            CollisionMode::KeepBoth => dest = numbered(&dest),
+        }
+    }
+    std::fs::copy(source, &dest)
+        .map_err(|e| format!("Failed to copy {}: {}", file_name.to_string_lossy(), e))?;
+    Ok(true)
+}
@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SmartFilter {
+    pub name: String,
+    pub format: Option<String>,
+    pub name_contains: String,
+    pub min_duration: Option<f64>,
+    pub max_duration: Option<f64>,
+    pub min_plays: Option<u32>,
+    pub max_plays: Option<u32>,
+    pub min_rating: Option<u8>,
+    pub favorites_only: bool,
+    pub min_bpm: Option<f32>,
+    pub max_bpm: Option<f32>,
+}
+
+impl SmartFilter {
+    pub fn needs_duration(&self) -> bool {
+        self.min_duration.is_some() || self.max_duration.is_some()
+    }
+
+    pub fn needs_bpm(&self) -> bool {
+        self.min_bpm.is_some() || self.max_bpm.is_some()
+    }
+
+    pub fn matches(
+        &self,
+        path: &Path,
+        duration: Option<f64>,
+        bpm: Option<f32>,
+        play_count: u32,
+        rating: u8,
+        favorite: bool,
+    ) -> bool {
+        if let Some(format) = &self.format {
+            let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+            if !ext.eq_ignore_ascii_case(format) {
+                return false;
+            }
+        }
+        if !self.name_contains.is_empty() {
+            let name = path.file_stem().map(|n| n.to_string_lossy()).unwrap_or_default();
+            if !name.to_lowercase().contains(&self.name_contains.to_lowercase()) {
+                return false;
+            }
+        }
+        if self.needs_duration() {
+            let Some(duration) = duration else {
+                return false;
+            };
+            if self.min_duration.is_some_and(|min| duration < min)
+                || self.max_duration.is_some_and(|max| duration > max)
+            {
+                return false;
+            }
+        }
+        if self.needs_bpm() {
+            let Some(bpm) = bpm else {
+                return false;
+            };
+            if self.min_bpm.is_some_and(|min| bpm < min) || self.max_bpm.is_some_and(|max| bpm > max) {
+                return false;
+            }
+        }
+        if self.min_plays.is_some_and(|min| play_count < min)
+            || self.max_plays.is_some_and(|max| play_count > max)
+        {
+            return false;
+        }
+        if self.min_rating.is_some_and(|min| rating < min) || (self.favorites_only && !favorite) {
+            return false;
+        }
+        true
+    }
+}
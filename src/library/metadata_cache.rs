@@ -0,0 +1,168 @@
+use super::playlist_file::{deserialize_path, serialize_path};
+use crate::audio::TrackMetadata;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CachedTrack {
+    #[serde(serialize_with = "serialize_path", deserialize_with = "deserialize_path")]
+    pub path: PathBuf,
+    pub modified: u64,
+    pub metadata: TrackMetadata,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artwork_color: Option<Option<[u8; 3]>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub waveform: Option<Vec<u8>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_bpm: Option<Option<f32>>,
+}
+
+#[derive(Default, Clone)]
+pub struct MetadataCache {
+    entries: HashMap<PathBuf, CachedTrack>,
+    dirty: bool,
+}
+
+impl MetadataCache {
+    pub fn load(path: &Path) -> Self {
+        let entries: Vec<CachedTrack> = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Self {
+            entries: entries.into_iter().map(|e| (e.path.clone(), e)).collect(),
+            dirty: false,
+        }
+    }
+
+    pub fn save(&mut self, path: &Path) -> Result<(), String> {
+        if !self.dirty {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create cache folder: {}", e))?;
+        }
+        let mut entries: Vec<&CachedTrack> = self.entries.values().collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        let contents = serde_json::to_string(&entries)
+            .map_err(|e| format!("Failed to serialize metadata cache: {}", e))?;
+        super::atomic_write(path, contents).map_err(|e| format!("Failed to save metadata cache: {}", e))?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&CachedTrack> {
+        let entry = self.entries.get(path)?;
+        (modified_time(path)? == entry.modified).then_some(entry)
+    }
+
+    pub fn stored(&self, path: &Path) -> Option<&TrackMetadata> {
+        self.entries.get(path).map(|e| &e.metadata)
+    }
+
+    pub fn insert(&mut self, path: &Path, metadata: TrackMetadata) {
+        let Some(modified) = modified_time(path) else {
+            return;
+        };
+        let previous = self.entries.remove(path).filter(|e| e.modified == modified);
+        self.entries.insert(path.to_path_buf(), CachedTrack {
+            path: path.to_path_buf(),
+            modified,
+            metadata,
+            artwork_color: previous.as_ref().and_then(|e| e.artwork_color),
+            estimated_bpm: previous.as_ref().and_then(|e| e.estimated_bpm),
+            waveform: previous.and_then(|e| e.waveform),
+        });
+        self.dirty = true;
+    }
+
+    pub fn set_artwork_color(&mut self, path: &Path, color: Option<[u8; 3]>) {
+        if let Some(entry) = self.entries.get_mut(path)
+            && Some(entry.modified) == modified_time(path)
+        {
+            entry.artwork_color = Some(color);
+            self.dirty = true;
+        }
+    }
+
+    pub fn set_waveform(&mut self, path: &Path, peaks: Vec<u8>) {
+        if let Some(entry) = self.entries.get_mut(path)
+            && Some(entry.modified) == modified_time(path)
+        {
+            entry.waveform = Some(peaks);
+            self.dirty = true;
+        }
+    }
+
+    pub fn set_estimated_bpm(&mut self, path: &Path, bpm: Option<f32>) {
+        if let Some(entry) = self.entries.get_mut(path)
+            && Some(entry.modified) == modified_time(path)
+        {
+            entry.estimated_bpm = Some(bpm);
+            self.dirty = true;
+        }
+    }
+
+    pub fn merge(&mut self, scanned: MetadataCache) {
+        for (path, entry) in scanned.entries {
+            match self.entries.get_mut(&path) {
+                Some(live) if live.modified == entry.modified => {
+                    live.artwork_color = live.artwork_color.or(entry.artwork_color);
+                    live.estimated_bpm = live.estimated_bpm.or(entry.estimated_bpm);
+                    if live.waveform.is_none() {
+                        live.waveform = entry.waveform;
+                    }
+                }
+                Some(live) if live.modified > entry.modified => continue,
+                _ => {
+                    self.entries.insert(path, entry);
+                }
+            }
+            self.dirty = true;
+        }
+    }
+
+    pub fn retain(&mut self, keep: impl Fn(&Path) -> bool) {
+        let before = self.entries.len();
+        self.entries.retain(|path, _| keep(path));
+        self.dirty |= self.entries.len() != before;
+    }
+}
+
+fn modified_time(path: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).and_then(|meta| meta.modified()).ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merging_a_scan_keeps_entries_added_while_it_ran() {
+        let dir = std::env::temp_dir().join(format!("kiraboshi-metadata-merge-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("a.mp3"), dir.join("b.mp3"));
+        std::fs::write(&a, b"").unwrap();
+        std::fs::write(&b, b"").unwrap();
+
+        let mut scanned = MetadataCache::default();
+        scanned.insert(&a, TrackMetadata::default());
+        scanned.set_waveform(&a, vec![1, 2, 3]);
+        let mut live = MetadataCache::default();
+        live.insert(&a, TrackMetadata::default());
+        live.set_estimated_bpm(&a, Some(120.0));
+        live.insert(&b, TrackMetadata::default());
+        live.set_artwork_color(&b, Some([1, 2, 3]));
+
+        live.merge(scanned);
+
+        let merged = live.get(&a).unwrap();
+        assert_eq!(merged.estimated_bpm, Some(Some(120.0)));
+        assert_eq!(merged.waveform, Some(vec![1, 2, 3]));
+        assert_eq!(live.get(&b).unwrap().artwork_color, Some(Some([1, 2, 3])));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
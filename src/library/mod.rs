@@ -0,0 +1,25 @@
+mod backup;
+mod cue;
+mod duplicates;
+mod export;
+mod filter;
+mod metadata_cache;
+mod named_playlist;
+mod playlist_file;
+mod resume;
+mod session;
+mod storage;
+mod watcher;
+
+pub use backup::*;
+pub use cue::*;
+pub use duplicates::*;
+pub use export::*;
+pub use filter::*;
+pub use metadata_cache::*;
+pub use named_playlist::*;
+pub use playlist_file::*;
+pub use resume::*;
+pub use session::*;
+pub use storage::*;
+pub use watcher::*;
@@ -0,0 +1,80 @@
+use super::playlist_file::{deserialize_path, serialize_path};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize)]
+struct PlaylistEntry {
+    #[serde(serialize_with = "serialize_path", deserialize_with = "deserialize_path")]
+    path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct NamedPlaylistFormat {
+    version: u32,
+    name: String,
+    tracks: Vec<PlaylistEntry>,
+}
+
+pub struct NamedPlaylist {
+    pub name: String,
+    pub paths: Vec<PathBuf>,
+}
+
+impl NamedPlaylist {
+    pub fn file_for(dir: &Path, name: &str) -> PathBuf {
+        let stem: String = name
+            .trim()
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == ' ' || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        dir.join(format!("{}.json", stem))
+    }
+
+    pub fn list(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|p| Self::read(&p))
+            .map(|playlist| playlist.name)
+            .collect();
+        names.sort_by_key(|n| n.to_lowercase());
+        names
+    }
+
+    pub fn exists(dir: &Path, name: &str) -> bool {
+        Self::file_for(dir, name).exists()
+    }
+
+    pub fn load(dir: &Path, name: &str) -> Option<Self> {
+        Self::read(&Self::file_for(dir, name))
+    }
+
+    fn read(path: &Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        let format: NamedPlaylistFormat = serde_json::from_str(&contents).ok()?;
+        Some(Self {
+            name: format.name,
+            paths: format.tracks.into_iter().map(|t| t.path).collect(),
+        })
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create playlists folder: {}", e))?;
+        let format = NamedPlaylistFormat {
+            version: 1,
+            name: self.name.clone(),
+            tracks: self.paths.iter().map(|p| PlaylistEntry { path: p.clone() }).collect(),
+        };
+        let contents = serde_json::to_string_pretty(&format)
+            .map_err(|e| format!("Failed to serialize playlist: {}", e))?;
+        super::atomic_write(&Self::file_for(dir, &self.name), contents)
+            .map_err(|e| format!("Failed to save playlist: {}", e))
+    }
+
+    pub fn delete(dir: &Path, name: &str) -> Result<(), String> {
+        std::fs::remove_file(Self::file_for(dir, name)).map_err(|e| format!("Failed to delete playlist: {}", e))
+    }
+}
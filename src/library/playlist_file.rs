@@ -0,0 +1,294 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct TrackData {
+    #[serde(default, skip_serializing_if = "is_zero_gain")]
+    pub gain_db: f32,
+    #[serde(default, skip_serializing_if = "is_zero_count")]
+    pub play_count: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bookmarks: Vec<Bookmark>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub added: Option<u64>,
+    #[serde(default, skip_serializing_if = "is_unrated")]
+    pub rating: u8,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub markers: Vec<f64>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub favorite: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bookmark_loop: Option<(f64, f64)>,
+}
+
+impl TrackData {
+    pub fn shuffle_weight(&self) -> f64 {
+        let rating = if self.rating == 0 { 3.0 } else { self.rating as f64 };
+        rating / (1.0 + self.play_count as f64)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Bookmark {
+    pub name: String,
+    pub position: f64,
+}
+
+fn is_zero_gain(gain: &f32) -> bool {
+    *gain == 0.0
+}
+
+fn is_zero_count(count: &u32) -> bool {
+    *count == 0
+}
+
+fn is_unrated(rating: &u8) -> bool {
+    *rating == 0
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum StoredPath {
+    Text(String),
+    Raw { raw: Vec<u16> },
+}
+
+pub(super) fn serialize_path<S: serde::Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    let stored = match path.to_str() {
+        Some(text) => StoredPath::Text(text.to_string()),
+        None => StoredPath::Raw { raw: raw_units(path) },
+    };
+    stored.serialize(serializer)
+}
+
+pub(super) fn deserialize_path<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    Ok(match StoredPath::deserialize(deserializer)? {
+        StoredPath::Text(text) => PathBuf::from(text),
+        StoredPath::Raw { raw } => from_raw_units(raw),
+    })
+}
+
+#[cfg(unix)]
+fn raw_units(path: &Path) -> Vec<u16> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().iter().map(|&b| b as u16).collect()
+}
+
+#[cfg(unix)]
+fn from_raw_units(raw: Vec<u16>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(raw.into_iter().map(|u| u as u8).collect()))
+}
+
+#[cfg(windows)]
+fn raw_units(path: &Path) -> Vec<u16> {
+    use std::os::windows::ffi::OsStrExt;
+    path.as_os_str().encode_wide().collect()
+}
+
+#[cfg(windows)]
+fn from_raw_units(raw: Vec<u16>) -> PathBuf {
+    use std::os::windows::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_wide(&raw))
+}
+
+#[derive(Serialize, Deserialize)]
+struct TrackEntry {
+    #[serde(serialize_with = "serialize_path", deserialize_with = "deserialize_path")]
+    path: PathBuf,
+    #[serde(flatten)]
+    data: TrackData,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PlaylistFormat {
+    version: u32,
+    tracks: Vec<TrackEntry>,
+}
+
+fn backup_names(path: &Path) -> impl Iterator<Item = PathBuf> + '_ {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    (1..).map(move |n| match n {
+        1 => path.with_file_name(format!("{}.bak", name)),
+        n => path.with_file_name(format!("{}.{}.bak", name, n)),
+    })
+}
+
+fn keep_backup(path: &Path) -> Result<PathBuf, String> {
+    let backup = backup_names(path)
+        .find(|candidate| !candidate.exists())
+        .unwrap_or_else(|| path.with_extension("bak"));
+    std::fs::copy(path, &backup).map_err(|e| format!("Failed to back up the library file: {}", e))?;
+    Ok(backup)
+}
+
+#[derive(Default)]
+pub struct PlaylistFile {
+    pub paths: Vec<PathBuf>,
+    pub tracks: HashMap<PathBuf, TrackData>,
+    pub legacy: bool,
+}
+
+impl PlaylistFile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            return Ok(Self { legacy: true, ..Self::default() });
+        };
+        Self::parse(&contents).map_err(|e| match keep_backup(path) {
+            Ok(backup) => format!("{} A copy was kept at {}.", e, backup.display()),
+            Err(backup_error) => format!("{} {}", e, backup_error),
+        })
+    }
+
+    pub fn restore_backup(path: &Path) -> Result<PathBuf, String> {
+        let backups: Vec<PathBuf> = backup_names(path).take_while(|backup| backup.exists()).collect();
+        for backup in backups.into_iter().rev() {
+            let Ok(contents) = std::fs::read_to_string(&backup) else {
+                continue;
+            };
+            if Self::parse(&contents).is_ok() {
+                super::atomic_write(path, contents).map_err(|e| format!("Failed to restore the library file: {}", e))?;
+                return Ok(backup);
+            }
+        }
+        Err("None of the library backups could be read.".to_string())
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        if !contents.trim_start().starts_with('{') {
+            return Ok(Self {
+                paths: contents
+                    .lines()
+                    .filter(|l| !l.is_empty())
+                    .map(PathBuf::from)
+                    .collect(),
+                tracks: HashMap::new(),
+                legacy: true,
+            });
+        }
+        let format = serde_json::from_str::<PlaylistFormat>(contents)
+            .map_err(|e| format!("The library file is damaged ({}).", e))?;
+        let paths = format.tracks.iter().map(|t| t.path.clone()).collect();
+        let tracks = format.tracks.into_iter().map(|t| (t.path, t.data)).collect();
+        Ok(Self { paths, tracks, legacy: false })
+    }
+
+    pub fn save(path: &Path, paths: &[PathBuf], tracks: &HashMap<PathBuf, TrackData>) -> Result<(), String> {
+        let format = PlaylistFormat {
+            version: 1,
+            tracks: paths
+                .iter()
+                .map(|p| TrackEntry {
+                    path: p.clone(),
+                    data: tracks.get(p).cloned().unwrap_or_default(),
+                })
+                .collect(),
+        };
+        let contents = serde_json::to_string_pretty(&format)
+            .map_err(|e| format!("Failed to serialize playlist: {}", e))?;
+        super::atomic_write(path, contents).map_err(|e| format!("Failed to save playlist: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = std::env::temp_dir().join(format!("kiraboshi-playlist-file-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(".kiraboshi");
+        let paths = vec![PathBuf::from("/music/b.mp3"), PathBuf::from("/music/a.flac")];
+        let mut tracks = HashMap::new();
+        tracks.insert(
+            paths[0].clone(),
+            TrackData {
+                gain_db: -3.5,
+                play_count: 7,
+                bookmarks: vec![Bookmark { name: "Chorus".to_string(), position: 42.0 }],
+                rating: 4,
+                favorite: true,
+                ..TrackData::default()
+            },
+        );
+        PlaylistFile::save(&file, &paths, &tracks).unwrap();
+        let loaded = PlaylistFile::load(&file).unwrap();
+        assert!(!loaded.legacy);
+        assert_eq!(loaded.paths, paths);
+        let data = &loaded.tracks[&paths[0]];
+        assert_eq!((data.gain_db, data.play_count, data.rating, data.favorite), (-3.5, 7, 4, true));
+        assert_eq!(data.bookmarks[0].name, "Chorus");
+        assert_eq!(loaded.tracks[&paths[1]].play_count, 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_paths_round_trip() {
+        use std::os::unix::ffi::OsStringExt;
+        let dir = std::env::temp_dir().join(format!("kiraboshi-non-utf8-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(".kiraboshi");
+        let odd = PathBuf::from(std::ffi::OsString::from_vec(b"/music/caf\xe9.mp3".to_vec()));
+        assert!(odd.to_str().is_none());
+        let paths = vec![odd.clone(), PathBuf::from("/music/plain.mp3")];
+        let mut tracks = HashMap::new();
+        tracks.insert(odd.clone(), TrackData { play_count: 2, ..TrackData::default() });
+        PlaylistFile::save(&file, &paths, &tracks).unwrap();
+        let loaded = PlaylistFile::load(&file).unwrap();
+        assert_eq!(loaded.paths, paths);
+        assert_eq!(loaded.tracks[&odd].play_count, 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn shuffle_weight_favors_fewer_plays_and_higher_ratings() {
+        let weight = |play_count, rating| TrackData { play_count, rating, ..TrackData::default() }.shuffle_weight();
+        assert!(weight(0, 0) > weight(5, 0));
+        assert!(weight(5, 5) > weight(5, 1));
+        assert_eq!(weight(0, 0), weight(0, 3));
+    }
+
+    #[test]
+    fn legacy_line_format_is_still_read() {
+        let loaded = PlaylistFile::parse("/music/a.mp3\n\n/music/b.mp3\n").unwrap();
+        assert!(loaded.legacy);
+        assert_eq!(loaded.paths, vec![PathBuf::from("/music/a.mp3"), PathBuf::from("/music/b.mp3")]);
+    }
+
+    #[test]
+    fn corrupt_json_is_an_error_and_is_backed_up() {
+        assert!(PlaylistFile::parse("{\"version\": 1, \"tracks\": [").is_err());
+        let dir = std::env::temp_dir().join(format!("kiraboshi-corrupt-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(".kiraboshi");
+        std::fs::write(&file, "{ broken").unwrap();
+        assert!(PlaylistFile::load(&file).is_err());
+        assert_eq!(std::fs::read_to_string(dir.join(".kiraboshi.bak")).unwrap(), "{ broken");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "{ broken");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn restoring_picks_the_newest_readable_backup() {
+        let dir = std::env::temp_dir().join(format!("kiraboshi-restore-bak-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(".kiraboshi");
+        let good = dir.join(".kiraboshi.bak");
+        PlaylistFile::save(&good, &[PathBuf::from("/music/a.mp3")], &HashMap::new()).unwrap();
+        std::fs::write(dir.join(".kiraboshi.2.bak"), "{ broken").unwrap();
+        std::fs::write(&file, "{ broken").unwrap();
+        assert_eq!(PlaylistFile::restore_backup(&file).unwrap(), good);
+        assert_eq!(PlaylistFile::load(&file).unwrap().paths, vec![PathBuf::from("/music/a.mp3")]);
+        std::fs::remove_file(&good).unwrap();
+        std::fs::write(&file, "{ broken").unwrap();
+        assert!(PlaylistFile::restore_backup(&file).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,54 @@
+use super::playlist_file::{deserialize_path, serialize_path};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ResumePoint {
+    #[serde(serialize_with = "serialize_path", deserialize_with = "deserialize_path")]
+    pub folder: PathBuf,
+    #[serde(serialize_with = "serialize_path", deserialize_with = "deserialize_path")]
+    pub track: PathBuf,
+    pub position: f64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct ResumePoints {
+    points: Vec<ResumePoint>,
+}
+
+impl ResumePoints {
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize resume points: {}", e))?;
+        super::atomic_write(path, contents).map_err(|e| format!("Failed to save resume points: {}", e))
+    }
+
+    pub fn get(&self, folder: &Path) -> Option<&ResumePoint> {
+        self.points.iter().find(|p| p.folder == folder)
+    }
+
+    pub fn set(&mut self, folder: &Path, track: &Path, position: f64) {
+        match self.points.iter_mut().find(|p| p.folder == folder) {
+            Some(point) => {
+                point.track = track.to_path_buf();
+                point.position = position;
+            }
+            None => self.points.push(ResumePoint {
+                folder: folder.to_path_buf(),
+                track: track.to_path_buf(),
+                position,
+            }),
+        }
+    }
+
+    pub fn retain_tracks(&mut self, exists: impl Fn(&Path) -> bool) {
+        self.points.retain(|p| exists(&p.track));
+    }
+}
@@ -0,0 +1,103 @@
+use super::playlist_file::{deserialize_path, serialize_path};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize)]
+struct SessionEntry {
+    #[serde(serialize_with = "serialize_path", deserialize_with = "deserialize_path")]
+    path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueSource {
+    #[default]
+    Folder,
+    Files,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionQueue {
+    name: String,
+    #[serde(default)]
+    source: QueueSource,
+    tracks: Vec<SessionEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionFormat {
+    version: u32,
+    playlist: Option<String>,
+    view: String,
+    queue: Option<SessionQueue>,
+    history: Vec<SessionEntry>,
+    track: Option<SessionEntry>,
+    position: f64,
+    playing: bool,
+    shuffle: bool,
+    reverse: bool,
+    loop_mode: String,
+}
+
+pub struct Session {
+    pub playlist: Option<String>,
+    pub view: String,
+    pub queue: Option<(String, QueueSource, Vec<PathBuf>)>,
+    pub history: Vec<PathBuf>,
+    pub track: Option<PathBuf>,
+    pub position: f64,
+    pub playing: bool,
+    pub shuffle: bool,
+    pub reverse: bool,
+    pub loop_mode: String,
+}
+
+fn entries(paths: &[PathBuf]) -> Vec<SessionEntry> {
+    paths.iter().map(|p| SessionEntry { path: p.clone() }).collect()
+}
+
+fn paths(entries: Vec<SessionEntry>) -> Vec<PathBuf> {
+    entries.into_iter().map(|e| e.path).collect()
+}
+
+impl Session {
+    pub fn load(path: &Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        let format: SessionFormat = serde_json::from_str(&contents).ok()?;
+        Some(Self {
+            playlist: format.playlist,
+            view: format.view,
+            queue: format.queue.map(|q| (q.name, q.source, paths(q.tracks))),
+            history: paths(format.history),
+            track: format.track.map(|t| t.path),
+            position: format.position,
+            playing: format.playing,
+            shuffle: format.shuffle,
+            reverse: format.reverse,
+            loop_mode: format.loop_mode,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let format = SessionFormat {
+            version: 1,
+            playlist: self.playlist.clone(),
+            view: self.view.clone(),
+            queue: self.queue.as_ref().map(|(name, source, tracks)| SessionQueue {
+                name: name.clone(),
+                source: *source,
+                tracks: entries(tracks),
+            }),
+            history: entries(&self.history),
+            track: self.track.as_ref().map(|p| SessionEntry { path: p.clone() }),
+            position: self.position,
+            playing: self.playing,
+            shuffle: self.shuffle,
+            reverse: self.reverse,
+            loop_mode: self.loop_mode.clone(),
+        };
+        let contents = serde_json::to_string_pretty(&format)
+            .map_err(|e| format!("Failed to serialize session: {}", e))?;
+        super::atomic_write(path, contents).map_err(|e| format!("Failed to save session: {}", e))
+    }
+}
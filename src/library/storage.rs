@@ -0,0 +1,117 @@
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+static DATA_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+fn location_file() -> Option<PathBuf> {
+    let config = std::env::var_os("APPDATA")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join("Kiraboshi").join("data_location"))
+}
+
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = DATA_DIR.read().ok().and_then(|d| d.clone()) {
+        return dir;
+    }
+    let dir = location_file()
+        .and_then(|file| std::fs::read_to_string(file).ok())
+        .map(|s| PathBuf::from(s.trim()))
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| PathBuf::from("data"));
+    if let Ok(mut cached) = DATA_DIR.write() {
+        *cached = Some(dir.clone());
+    }
+    dir
+}
+
+pub fn set_data_dir(dir: &Path) -> Result<(), String> {
+    let file = location_file().ok_or("Could not find a configuration folder to remember the data location")?;
+    if let Some(parent) = file.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create configuration folder: {}", e))?;
+    }
+    atomic_write(&file, dir.to_string_lossy().as_bytes())
+        .map_err(|e| format!("Failed to save data location: {}", e))?;
+    if let Ok(mut cached) = DATA_DIR.write() {
+        *cached = Some(dir.to_path_buf());
+    }
+    Ok(())
+}
+
+pub fn is_writable(dir: &Path) -> bool {
+    if std::fs::create_dir_all(dir).is_err() {
+        return false;
+    }
+    let probe = dir.join(format!(".kiraboshi_probe.{}.tmp", std::process::id()));
+    let writable = std::fs::write(&probe, b"").is_ok();
+    let _ = std::fs::remove_file(&probe);
+    writable
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(format!(".{}.tmp", std::process::id()));
+    path.with_file_name(name)
+}
+
+fn write_synced(path: &Path, write: impl FnOnce(&mut dyn Write) -> std::io::Result<()>) -> std::io::Result<()> {
+    let mut file = BufWriter::new(std::fs::File::create(path)?);
+    write(&mut file)?;
+    file.into_inner().map_err(|e| e.into_error())?.sync_all()
+}
+
+pub fn atomic_write(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    atomic_write_with(path, |file| file.write_all(contents.as_ref()))
+}
+
+pub fn atomic_write_with(path: &Path, write: impl FnOnce(&mut dyn Write) -> std::io::Result<()>) -> std::io::Result<()> {
+    let temp = temp_path(path);
+    if let Err(e) = write_synced(&temp, write).and_then(|_| std::fs::rename(&temp, path)) {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e);
+    }
+    #[cfg(unix)]
+    {
+        let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        if let Ok(dir) = std::fs::File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kiraboshi-storage-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn atomic_write_replaces_the_file() {
+        let dir = temp_dir("replace");
+        let file = dir.join("settings");
+        atomic_write(&file, "old").unwrap();
+        atomic_write(&file, "new").unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "new");
+        assert!(!temp_path(&file).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn failed_write_keeps_the_good_file() {
+        let dir = temp_dir("failed");
+        let file = dir.join("settings");
+        atomic_write(&file, "good").unwrap();
+        std::fs::create_dir(temp_path(&file)).unwrap();
+        assert!(atomic_write(&file, "partial").is_err());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "good");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,42 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub struct LibraryWatcher {
+    receiver: Receiver<Vec<PathBuf>>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl LibraryWatcher {
+    pub fn start(dir: PathBuf, interval: Duration, collect: fn(&Path) -> Vec<PathBuf>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let thread_shutdown = Arc::clone(&shutdown);
+        std::thread::spawn(move || {
+            let mut last = None;
+            while !thread_shutdown.load(Ordering::Relaxed) {
+                let files = collect(&dir);
+                if last.as_ref() != Some(&files) {
+                    if sender.send(files.clone()).is_err() {
+                        break;
+                    }
+                    last = Some(files);
+                }
+                std::thread::sleep(interval);
+            }
+        });
+        Self { receiver, shutdown }
+    }
+
+    pub fn poll(&self) -> Option<Vec<PathBuf>> {
+        self.receiver.try_iter().last()
+    }
+}
+
+impl Drop for LibraryWatcher {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+    }
+}
@@ -2,10 +2,55 @@
 
 mod player;
 mod audio;
+mod headless;
+mod library;
+mod playback;
+mod platform;
+mod scrobble;
+mod settings;
+mod status;
+mod tasks;
 
 use std::path::PathBuf;
 
+const USAGE: &str = "Usage: kiraboshi [--headless] [--read-only] [--status-port PORT] [FILE...]";
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    std::process::exit(2);
+}
+
 fn main() -> Result<(), eframe::Error> {
-    let file_arg = std::env::args().nth(1).map(PathBuf::from);
-    player::run(file_arg)
+    let mut files = Vec::new();
+    let mut status_port = None;
+    let mut headless = false;
+    let mut read_only = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--status-port" => match args.next().and_then(|p| p.parse::<u16>().ok()) {
+                Some(port) => status_port = Some(port),
+                None => usage_error("--status-port needs a port number"),
+            },
+            "--headless" => headless = true,
+            "--read-only" => read_only = true,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            flag if flag.starts_with("--") => usage_error(&format!("Unknown option: {}", flag)),
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+    if headless {
+        if !files.is_empty() {
+            usage_error("--headless plays the library and does not take files");
+        }
+        if let Err(e) = headless::run(status_port, read_only) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    player::run(files, status_port, read_only)
 }
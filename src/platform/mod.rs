@@ -0,0 +1,3 @@
+mod taskbar;
+
+pub use taskbar::*;
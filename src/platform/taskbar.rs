@@ -0,0 +1,57 @@
+#[cfg(windows)]
+mod imp {
+    use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::System::Com::{CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED, CoCreateInstance, CoInitializeEx};
+    use windows::Win32::UI::Shell::{ITaskbarList3, TBPF_NOPROGRESS, TBPF_NORMAL, TBPF_PAUSED, TaskbarList};
+
+    pub struct TaskbarProgress {
+        list: ITaskbarList3,
+        hwnd: HWND,
+    }
+
+    impl TaskbarProgress {
+        pub fn new(frame: &eframe::Frame) -> Option<Self> {
+            let RawWindowHandle::Win32(handle) = frame.window_handle().ok()?.as_raw() else {
+                return None;
+            };
+            let hwnd = HWND(handle.hwnd.get() as *mut _);
+            unsafe {
+                let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+                let list: ITaskbarList3 = CoCreateInstance(&TaskbarList, None, CLSCTX_INPROC_SERVER).ok()?;
+                list.HrInit().ok()?;
+                Some(Self { list, hwnd })
+            }
+        }
+
+        pub fn set(&mut self, progress: Option<(f64, bool)>) {
+            unsafe {
+                match progress {
+                    None => {
+                        let _ = self.list.SetProgressState(self.hwnd, TBPF_NOPROGRESS);
+                    }
+                    Some((fraction, paused)) => {
+                        let state = if paused { TBPF_PAUSED } else { TBPF_NORMAL };
+                        let _ = self.list.SetProgressState(self.hwnd, state);
+                        let _ = self.list.SetProgressValue(self.hwnd, (fraction.clamp(0.0, 1.0) * 1000.0) as u64, 1000);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod imp {
+    pub struct TaskbarProgress;
+
+    impl TaskbarProgress {
+        pub fn new(_frame: &eframe::Frame) -> Option<Self> {
+            Some(Self)
+        }
+
+        pub fn set(&mut self, _progress: Option<(f64, bool)>) {}
+    }
+}
+
+pub use imp::TaskbarProgress;
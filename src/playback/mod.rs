@@ -0,0 +1,5 @@
+mod player_core;
+mod session;
+
+pub use player_core::*;
+pub use session::*;
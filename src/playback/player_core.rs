@@ -0,0 +1,486 @@
+use crate::audio::AudioEngine;
+use crate::library::TrackData;
+use crate::settings::ShuffleMode;
+use super::SessionStats;
+use rand::seq::IndexedRandom;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum LoopMode {
+    Off,
+    One,
+    Album,
+    All,
+    Radio,
+}
+
+impl LoopMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LoopMode::Off => "off",
+            LoopMode::One => "one",
+            LoopMode::Album => "album",
+            LoopMode::All => "all",
+            LoopMode::Radio => "radio",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [LoopMode::Off, LoopMode::One, LoopMode::Album, LoopMode::All, LoopMode::Radio]
+            .into_iter()
+            .find(|mode| mode.as_str() == name)
+    }
+}
+
+pub fn shuffle_pick<T: PartialEq>(
+    pool: &[T],
+    current: Option<&T>,
+    mode: ShuffleMode,
+    weight: impl Fn(&T) -> f64,
+    rng: &mut impl rand::Rng,
+) -> Option<usize> {
+    let candidates: Vec<usize> = (0..pool.len())
+        .filter(|&i| current != Some(&pool[i]) || pool.len() == 1)
+        .collect();
+    match mode {
+        ShuffleMode::Uniform => candidates.choose(rng).copied(),
+        ShuffleMode::LeastPlayed => candidates
+            .choose_weighted(rng, |&i| weight(&pool[i]))
+            .ok()
+            .copied(),
+    }
+}
+
+pub fn next_track(
+    playlist: &[PathBuf],
+    current: Option<&PathBuf>,
+    shuffle: Option<ShuffleMode>,
+    wrap: bool,
+    reverse: bool,
+    weight: impl Fn(&PathBuf) -> f64,
+    rng: &mut impl rand::Rng,
+) -> Option<usize> {
+    if let Some(mode) = shuffle {
+        return shuffle_pick(playlist, current, mode, weight, rng);
+    }
+    let idx = playlist.iter().position(|p| Some(p) == current)?;
+    if reverse {
+        idx.checked_sub(1).or_else(|| wrap.then_some(playlist.len() - 1))
+    } else if idx + 1 < playlist.len() {
+        Some(idx + 1)
+    } else {
+        wrap.then_some(0)
+    }
+}
+
+pub fn index_after_removal(
+    playlist: &[PathBuf],
+    removed: &Path,
+    idx: usize,
+    loop_mode: LoopMode,
+    reverse: bool,
+) -> Option<usize> {
+    let album: Vec<usize> = (0..playlist.len())
+        .filter(|&i| loop_mode == LoopMode::Album && playlist[i].parent() == removed.parent())
+        .collect();
+    let (candidates, wrap) = if album.is_empty() {
+        ((0..playlist.len()).collect(), loop_mode == LoopMode::All)
+    } else {
+        (album, true)
+    };
+    let next = if reverse {
+        candidates.iter().rev().find(|&&i| i < idx).or(candidates.last().filter(|_| wrap))
+    } else {
+        candidates.iter().find(|&&i| i >= idx).or(candidates.first().filter(|_| wrap))
+    };
+    next.copied()
+}
+
+#[derive(Default)]
+struct TrackEndWatch {
+    playing_serial: u64,
+    ended_serial: Option<u64>,
+}
+
+impl TrackEndWatch {
+    fn ended(&mut self, was_playing: bool, at_end: bool, serial: u64) -> bool {
+        let ended = was_playing && at_end && serial == self.playing_serial && self.ended_serial != Some(serial);
+        if ended {
+            self.ended_serial = Some(serial);
+        }
+        self.playing_serial = serial;
+        ended
+    }
+}
+
+pub fn step_index(
+    playlist: &[PathBuf],
+    current: Option<&PathBuf>,
+    loop_mode: LoopMode,
+    shuffle: Option<ShuffleMode>,
+    reverse: bool,
+    weight: impl Fn(&PathBuf) -> f64,
+    rng: &mut impl rand::Rng,
+) -> Option<usize> {
+    if loop_mode == LoopMode::Album
+        && let Some(folder) = current.and_then(|c| c.parent())
+    {
+        let album: Vec<usize> = (0..playlist.len())
+            .filter(|&i| playlist[i].parent() == Some(folder))
+            .collect();
+        if !album.is_empty() {
+            let tracks: Vec<PathBuf> = album.iter().map(|&i| playlist[i].clone()).collect();
+            return next_track(&tracks, current, shuffle, true, reverse, weight, rng).map(|i| album[i]);
+        }
+    }
+    next_track(playlist, current, shuffle, loop_mode == LoopMode::All, reverse, weight, rng)
+}
+
+pub struct PlayerCore {
+    pub audio: AudioEngine,
+    pub playlist: Vec<PathBuf>,
+    pub track_data: HashMap<PathBuf, TrackData>,
+    pub loop_mode: LoopMode,
+    pub shuffle: bool,
+    pub reverse: bool,
+    pub shuffle_mode: ShuffleMode,
+    pub sequential_run: bool,
+    pub seek_position: f64,
+    pub seeking: bool,
+    pub end_tolerance: f64,
+    pub session: SessionStats,
+    pub history: VecDeque<PathBuf>,
+    seek_cooldown: u8,
+    was_playing: bool,
+    end_watch: TrackEndWatch,
+    last_tick: SystemTime,
+    last_frame: Instant,
+}
+
+impl PlayerCore {
+    const SEEK_COOLDOWN: u8 = 5;
+    const WAKE_GAP: Duration = Duration::from_secs(5);
+    const HISTORY_LEN: usize = 100;
+    const RADIO_AVOID: usize = 20;
+
+    pub fn new(audio: AudioEngine, playlist: Vec<PathBuf>, track_data: HashMap<PathBuf, TrackData>) -> Self {
+        Self {
+            audio,
+            playlist,
+            track_data,
+            loop_mode: LoopMode::Off,
+            shuffle: false,
+            reverse: false,
+            shuffle_mode: ShuffleMode::Uniform,
+            sequential_run: false,
+            seek_position: 0.0,
+            seeking: false,
+            end_tolerance: 0.1,
+            session: SessionStats::default(),
+            history: VecDeque::new(),
+            seek_cooldown: 0,
+            was_playing: false,
+            end_watch: TrackEndWatch::default(),
+            last_tick: SystemTime::now(),
+            last_frame: Instant::now(),
+        }
+    }
+
+    pub fn detect_wake(&mut self) -> Option<Result<(), String>> {
+        let now = SystemTime::now();
+        let gap = now.duration_since(self.last_tick).unwrap_or_default();
+        self.last_tick = now;
+        if gap < Self::WAKE_GAP || !self.was_playing || self.audio.is_finished() {
+            return None;
+        }
+        let position = self.seek_position;
+        if self.audio.is_playing() && self.audio.get_position() - position >= gap.as_secs_f64() / 2.0 {
+            return None;
+        }
+        self.hold_position();
+        Some(self.audio.recover(position))
+    }
+
+    pub fn tick(&mut self) -> bool {
+        self.audio.tick();
+        let now = Instant::now();
+        if self.was_playing {
+            self.session.add_listening((now - self.last_frame).min(Duration::from_secs(1)));
+        }
+        self.last_frame = now;
+        let serial = self.audio.track_serial();
+        let duration = self.audio.get_duration();
+        let near_end = duration > 0.0 && self.audio.get_position() >= duration - self.end_tolerance;
+        let track_ended = self.end_watch.ended(self.was_playing, self.audio.is_finished() || near_end, serial);
+        self.was_playing = self.audio.is_playing();
+        if self.seek_cooldown > 0 {
+            self.seek_cooldown -= 1;
+        } else if !self.seeking && self.was_playing {
+            self.seek_position = self.audio.get_position();
+        }
+        track_ended
+    }
+
+    pub fn hold_position(&mut self) {
+        self.seek_cooldown = Self::SEEK_COOLDOWN;
+    }
+
+    pub fn is_settled(&self) -> bool {
+        !self.seeking && self.seek_cooldown == 0
+    }
+
+    pub fn seek_to(&mut self, position: f64) {
+        self.audio.seek(position);
+        self.seek_position = position;
+        self.hold_position();
+    }
+
+    pub fn track_gain(&self, path: &PathBuf) -> f32 {
+        self.track_data.get(path).map(|d| d.gain_db).unwrap_or(0.0)
+    }
+
+    pub fn start_track(&mut self, path: &PathBuf, fade: Option<Duration>) -> Result<(), String> {
+        match fade {
+            Some(fade) => self.audio.crossfade_to(path, self.track_gain(path), fade)?,
+            None => {
+                self.audio.set_track_gain(self.track_gain(path));
+                self.audio.play_song(path)?;
+            }
+        }
+        self.track_data.entry(path.clone()).or_default().play_count += 1;
+        self.session.record_play(path);
+        if self.history.back() != Some(path) {
+            self.history.push_back(path.clone());
+            if self.history.len() > Self::HISTORY_LEN {
+                self.history.pop_front();
+            }
+        }
+        Ok(())
+    }
+
+    pub fn previous_distinct(&self) -> Option<PathBuf> {
+        let current = self.audio.current_file();
+        self.history.iter().rev().find(|p| Some(*p) != current).cloned()
+    }
+
+    pub fn next_index(&mut self) -> Option<usize> {
+        self.step_from_current(self.loop_mode)
+    }
+
+    fn step_from_current(&mut self, loop_mode: LoopMode) -> Option<usize> {
+        let current = self.audio.current_file().cloned();
+        let shuffle = (self.shuffle && !self.sequential_run).then_some(self.shuffle_mode);
+        let next = step_index(
+            &self.playlist,
+            current.as_ref(),
+            loop_mode,
+            shuffle,
+            self.reverse,
+            |p| self.track_data.get(p).map_or(1.0, |d| d.shuffle_weight()),
+            &mut rand::rng(),
+        );
+        let first = if self.reverse { self.playlist.len().saturating_sub(1) } else { 0 };
+        let folder = current.as_ref().and_then(|c| c.parent());
+        let in_album = loop_mode == LoopMode::Album
+            && folder.is_some_and(|f| self.playlist.iter().any(|p| p.parent() == Some(f)));
+        if shuffle.is_none() && !in_album && next.is_none_or(|i| i == first) {
+            self.sequential_run = false;
+        }
+        next
+    }
+
+    pub fn previous_index(&self) -> Option<usize> {
+        let current = self.audio.current_file();
+        step_index(
+            &self.playlist,
+            current,
+            self.loop_mode,
+            None,
+            !self.reverse,
+            |_| 1.0,
+            &mut rand::rng(),
+        )
+    }
+
+    pub fn radio_pick(&self, pool: &[PathBuf]) -> Option<PathBuf> {
+        let avoid = (pool.len() / 2).min(Self::RADIO_AVOID);
+        let recent: Vec<&PathBuf> = self.history.iter().rev().take(avoid).collect();
+        let mut candidates: Vec<&PathBuf> = pool.iter().filter(|p| !recent.contains(p)).collect();
+        if candidates.is_empty() {
+            candidates = pool.iter().collect();
+        }
+        candidates
+            .choose_weighted(&mut rand::rng(), |p| self.track_data.get(*p).map_or(1.0, |d| d.shuffle_weight()))
+            .ok()
+            .map(|p| (*p).clone())
+    }
+
+    pub fn play_next(&mut self, library: &[PathBuf], fade: Option<Duration>) -> Result<(), String> {
+        if self.loop_mode == LoopMode::One && !self.playlist.is_empty() {
+            return match self.audio.current_file().cloned() {
+                Some(current) => self.start_track(&current, None),
+                None => Ok(()),
+            };
+        }
+        self.advance(library, fade, self.loop_mode)
+    }
+
+    pub fn skip_next(&mut self, library: &[PathBuf], fade: Option<Duration>) -> Result<(), String> {
+        let loop_mode = if self.loop_mode == LoopMode::One { LoopMode::All } else { self.loop_mode };
+        self.advance(library, fade, loop_mode)
+    }
+
+    fn advance(&mut self, library: &[PathBuf], fade: Option<Duration>, loop_mode: LoopMode) -> Result<(), String> {
+        if self.playlist.is_empty() {
+            return Ok(());
+        }
+        let next = self.step_from_current(loop_mode).map(|next| self.playlist[next].clone());
+        let next = match next {
+            None if loop_mode == LoopMode::Radio => {
+                let pool = if library.is_empty() { &self.playlist } else { library };
+                self.radio_pick(pool)
+            }
+            next => next,
+        };
+        match next {
+            Some(path) => self.start_track(&path, fade),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn rapid_switches_do_not_count_as_track_ends() {
+        let mut watch = TrackEndWatch::default();
+        assert!(!watch.ended(false, false, 1));
+        assert!(!watch.ended(true, false, 1));
+        for serial in 2..6 {
+            assert!(!watch.ended(true, true, serial));
+            assert!(!watch.ended(true, false, serial));
+        }
+    }
+
+    #[test]
+    fn a_natural_end_fires_once() {
+        let mut watch = TrackEndWatch::default();
+        assert!(!watch.ended(true, false, 1));
+        assert!(watch.ended(true, true, 1));
+        assert!(!watch.ended(true, true, 1));
+        assert!(!watch.ended(false, true, 1));
+        assert!(!watch.ended(true, false, 2));
+        assert!(watch.ended(true, true, 2));
+    }
+
+    fn tracks(n: usize) -> Vec<PathBuf> {
+        (0..n).map(|i| PathBuf::from(format!("/music/{}.mp3", i))).collect()
+    }
+
+    #[test]
+    fn least_played_shuffle_favors_low_play_counts() {
+        let playlist = tracks(3);
+        let mut track_data = HashMap::new();
+        track_data.insert(playlist[1].clone(), TrackData { play_count: 49, ..TrackData::default() });
+        let weight = |p: &PathBuf| track_data.get(p).map_or(1.0, |d| d.shuffle_weight());
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let mut picks = [0; 3];
+        for _ in 0..3000 {
+            let next = next_track(&playlist, Some(&playlist[0]), Some(ShuffleMode::LeastPlayed), false, false, weight, &mut rng);
+            picks[next.unwrap()] += 1;
+        }
+        assert_eq!(picks[0], 0);
+        assert!(picks[2] > picks[1] * 10, "{:?}", picks);
+    }
+
+    #[test]
+    fn uniform_shuffle_ignores_play_counts() {
+        let playlist = tracks(2);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let mut picks = [0; 2];
+        for _ in 0..2000 {
+            let next = next_track(&playlist, None, Some(ShuffleMode::Uniform), false, false, |_| 0.0, &mut rng);
+            picks[next.unwrap()] += 1;
+        }
+        assert!(picks[0] > 800 && picks[1] > 800, "{:?}", picks);
+    }
+
+    #[test]
+    fn shuffle_pool_can_hold_cue_tracks_of_one_file() {
+        let file = PathBuf::from("/music/album.flac");
+        let pool: Vec<(PathBuf, Option<usize>)> = (0..3).map(|k| (file.clone(), Some(k))).collect();
+        let current = (file.clone(), Some(1));
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let mut picks = [0; 3];
+        for _ in 0..300 {
+            let next = shuffle_pick(&pool, Some(&current), ShuffleMode::Uniform, |_| 1.0, &mut rng);
+            picks[next.unwrap()] += 1;
+        }
+        assert_eq!(picks[1], 0);
+        assert!(picks[0] > 0 && picks[2] > 0, "{:?}", picks);
+    }
+
+    #[test]
+    fn stepping_stops_at_the_end_unless_looping_all() {
+        let playlist = tracks(3);
+        let step = |current: usize, loop_mode, reverse| {
+            step_index(&playlist, Some(&playlist[current]), loop_mode, None, reverse, |_| 1.0, &mut rand::rng())
+        };
+        assert_eq!(step(0, LoopMode::Off, false), Some(1));
+        assert_eq!(step(2, LoopMode::Off, false), None);
+        assert_eq!(step(2, LoopMode::All, false), Some(0));
+        assert_eq!(step(0, LoopMode::Off, true), None);
+        assert_eq!(step(0, LoopMode::All, true), Some(2));
+    }
+
+    #[test]
+    fn album_loop_stays_inside_the_current_folder() {
+        let playlist: Vec<PathBuf> = ["a/1.mp3", "b/1.mp3", "b/2.mp3", "b/3.mp3", "c/1.mp3"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        let step = |current: usize, reverse| {
+            step_index(&playlist, Some(&playlist[current]), LoopMode::Album, None, reverse, |_| 1.0, &mut rand::rng())
+        };
+        assert_eq!(step(2, false), Some(3));
+        assert_eq!(step(3, false), Some(1));
+        assert_eq!(step(1, true), Some(3));
+        assert_eq!(step(0, false), Some(0));
+    }
+
+    #[test]
+    fn previous_in_album_loop_wraps_inside_the_folder() {
+        let playlist: Vec<PathBuf> = ["a/1.mp3", "b/1.mp3", "b/2.mp3", "c/1.mp3"].iter().map(PathBuf::from).collect();
+        let previous = |current: usize| {
+            step_index(&playlist, Some(&playlist[current]), LoopMode::Album, None, true, |_| 1.0, &mut rand::rng())
+        };
+        assert_eq!(previous(2), Some(1));
+        assert_eq!(previous(1), Some(2));
+    }
+
+    #[test]
+    fn removing_the_current_track_continues_inside_the_album() {
+        let playlist: Vec<PathBuf> = ["a/1.mp3", "b/1.mp3", "b/2.mp3", "c/1.mp3"].iter().map(PathBuf::from).collect();
+        let removed = Path::new("b/3.mp3");
+        assert_eq!(index_after_removal(&playlist, removed, 3, LoopMode::Album, false), Some(1));
+        assert_eq!(index_after_removal(&playlist, removed, 3, LoopMode::Off, false), Some(3));
+        assert_eq!(index_after_removal(&playlist, removed, 4, LoopMode::Off, false), None);
+        assert_eq!(index_after_removal(&playlist, removed, 4, LoopMode::All, false), Some(0));
+        assert_eq!(index_after_removal(&playlist, Path::new("b/0.mp3"), 1, LoopMode::Album, true), Some(2));
+        assert_eq!(index_after_removal(&playlist, Path::new("d/1.mp3"), 4, LoopMode::Album, false), None);
+    }
+
+    #[test]
+    fn a_missing_current_track_has_no_sequential_next() {
+        let playlist = tracks(3);
+        let missing = PathBuf::from("/elsewhere.mp3");
+        assert_eq!(step_index(&playlist, Some(&missing), LoopMode::All, None, false, |_| 1.0, &mut rand::rng()), None);
+        let shuffled = step_index(&playlist, None, LoopMode::Off, Some(ShuffleMode::Uniform), false, |_| 1.0, &mut rand::rng());
+        assert!(shuffled.is_some_and(|i| i < 3));
+    }
+}
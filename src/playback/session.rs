@@ -0,0 +1,28 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Default)]
+pub struct SessionStats {
+    pub listened: Duration,
+    pub tracks_played: u32,
+    plays: HashMap<PathBuf, u32>,
+}
+
+impl SessionStats {
+    pub fn record_play(&mut self, path: &Path) {
+        self.tracks_played += 1;
+        *self.plays.entry(path.to_path_buf()).or_default() += 1;
+    }
+
+    pub fn add_listening(&mut self, elapsed: Duration) {
+        self.listened += elapsed;
+    }
+
+    pub fn most_played(&self) -> Option<(&PathBuf, u32)> {
+        self.plays
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(path, count)| (path, *count))
+    }
+}
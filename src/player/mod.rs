@@ -1,3 +1,4 @@
 mod player;
+mod theme;
 
 pub use player::run;
@@ -1,13 +1,49 @@
-use crate::audio::AudioEngine;
+use crate::audio::{AudioEngine, Chapter, DeviceWatcher, PlaybackStatus, EQ_BANDS, EQ_MAX_FREQ, EQ_MAX_GAIN, EQ_MIN_FREQ, EqPoint, EqPreset, LoopClip, Lyrics, TrackMetadata, WAVEFORM_POINTS, band_gains, curve_gain, detect_transitions, estimate_bpm, export_clip, export_loop, output_device_name, render_loop, sort_points, waveform_peaks};
+use crate::platform::TaskbarProgress;
+use crate::playback::{LoopMode, PlayerCore, index_after_removal, shuffle_pick, step_index};
+use crate::scrobble::{LastFmCredentials, Scrobble, Scrobbler, authenticate};
+use crate::library::{Backup, Bookmark, CollisionMode, CueSheet, MetadataCache, LibraryWatcher, NamedPlaylist, RestoreMode, ResumePoint, ResumePoints, PlaylistFile, QueueSource, Session, SmartFilter, TrackData, atomic_write, content_hash, data_dir, export_track, find_duplicates, is_writable, set_data_dir};
+use crate::settings::{FadeCurve, FileOpenMode, OsdPosition, OutputLatency, RowDensity, Settings, ShuffleMode, StandaloneEnd, TitleDoubleClick, TitleDrag};
+use crate::status::{StatusServer, StatusSnapshot};
+use crate::tasks::{TaskHandle, TaskRegistry, TaskState};
+use super::theme::{AccentTheme, Palette, dominant_color};
 use eframe::egui;
-use rand::seq::IndexedRandom;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[derive(PartialEq, Clone, Copy)]
-enum LoopMode {
-    Off,
-    One,
-    All,
+enum PlaylistView {
+    Library,
+    RecentlyAdded,
+    TopRated,
+    Favorites,
+    Bpm,
+    Smart(usize),
+}
+
+impl PlaylistView {
+    fn key(self) -> String {
+        match self {
+            PlaylistView::Library => "library".to_string(),
+            PlaylistView::RecentlyAdded => "recently_added".to_string(),
+            PlaylistView::TopRated => "top_rated".to_string(),
+            PlaylistView::Favorites => "favorites".to_string(),
+            PlaylistView::Bpm => "bpm".to_string(),
+            PlaylistView::Smart(i) => format!("smart:{}", i),
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "library" => Some(PlaylistView::Library),
+            "recently_added" => Some(PlaylistView::RecentlyAdded),
+            "top_rated" => Some(PlaylistView::TopRated),
+            "favorites" => Some(PlaylistView::Favorites),
+            "bpm" => Some(PlaylistView::Bpm),
+            _ => key.strip_prefix("smart:")?.parse().ok().map(PlaylistView::Smart),
+        }
+    }
 }
 
 fn exe_dir() -> PathBuf {
@@ -17,10 +53,74 @@ fn exe_dir() -> PathBuf {
         .unwrap_or_else(|| PathBuf::from("."))
 }
 
+fn delete_named_playlist(playlists_dir: &Path, library_file: &Path, name: &str) -> Result<Vec<PathBuf>, String> {
+    NamedPlaylist::delete(playlists_dir, name)?;
+    Ok(PlaylistFile::load(library_file)?.paths.into_iter().filter(|p| p.exists()).collect())
+}
+
+fn write_library(
+    library_file: &Path,
+    load_failed: bool,
+    paths: &[PathBuf],
+    tracks: &HashMap<PathBuf, TrackData>,
+) -> Result<bool, String> {
+    if load_failed {
+        return Ok(false);
+    }
+    PlaylistFile::save(library_file, paths, tracks)?;
+    Ok(true)
+}
+
+fn previous_path(playlist: &[PathBuf], current: Option<&PathBuf>, loop_mode: LoopMode, reverse: bool) -> Option<PathBuf> {
+    step_index(playlist, current, loop_mode, None, !reverse, |_| 1.0, &mut rand::rng()).map(|i| playlist[i].clone())
+}
+
+fn parse_dropped_paths(text: &str) -> Vec<PathBuf> {
+    text.lines()
+        .map(|line| line.trim().trim_matches('"'))
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| match line.strip_prefix("file://") {
+            Some(rest) => {
+                let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+                let decoded = percent_decode(rest)?;
+                let decoded = match decoded.strip_prefix('/') {
+                    Some(stripped) if cfg!(windows) && stripped.chars().nth(1) == Some(':') => stripped.to_string(),
+                    _ => decoded,
+                };
+                Some(PathBuf::from(decoded))
+            }
+            None => Some(PathBuf::from(line)).filter(|path| path.is_absolute()),
+        })
+        .collect()
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            decoded.push(u8::from_str_radix(text.get(i + 1..i + 3)?, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+const EMBEDDED_ICON: &[u8] = include_bytes!("../../assets/icon.ico");
+
+fn icon_image() -> Option<image::RgbaImage> {
+    let img = image::open(exe_dir().join("assets/icon.ico"))
+        .or_else(|_| image::load_from_memory(EMBEDDED_ICON))
+        .ok()?;
+    Some(img.to_rgba8())
+}
+
 fn load_icon() -> Option<egui::IconData> {
-    let icon_path = exe_dir().join("assets/icon.ico");
-    let img = image::open(&icon_path).ok()?;
-    let rgba = img.to_rgba8();
+    let rgba = icon_image()?;
     let (w, h) = rgba.dimensions();
     Some(egui::IconData {
         rgba: rgba.into_raw(),
@@ -29,15 +129,44 @@ fn load_icon() -> Option<egui::IconData> {
     })
 }
 
-pub fn run(file_arg: Option<PathBuf>) -> Result<(), eframe::Error> {
-    let standalone = file_arg.is_some();
-    let window_size = if standalone { [600.0, 320.0] } else { [900.0, 620.0] };
+fn load_legacy_path_map<T: std::str::FromStr>(file: &Path) -> HashMap<PathBuf, T> {
+    std::fs::read_to_string(file)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|l| l.split_once('\t'))
+        .filter_map(|(value, path)| Some((PathBuf::from(path), value.parse().ok()?)))
+        .collect()
+}
+
+const MINI_WINDOW_SIZE: egui::Vec2 = egui::vec2(720.0, 72.0);
+
+fn min_window_size(standalone: bool, settings: &Settings) -> egui::Vec2 {
+    if standalone {
+        egui::vec2(600.0, 320.0)
+    } else if settings.mini_player {
+        MINI_WINDOW_SIZE
+    } else {
+        egui::vec2(settings.min_window_width, settings.min_window_height)
+    }
+}
+
+pub fn run(files: Vec<PathBuf>, status_port: Option<u16>, read_only: bool) -> Result<(), eframe::Error> {
+    let settings = Settings::load(&KiraboshiApp::settings_file());
+    let (files, skipped): (Vec<PathBuf>, Vec<PathBuf>) =
+        files.into_iter().partition(|p| p.is_file() && KiraboshiApp::is_audio_file(p));
+    let standalone = !files.is_empty() && (read_only || settings.file_open_mode == FileOpenMode::Standalone);
+    let window_size = min_window_size(standalone, &settings);
 
     let mut viewport = egui::ViewportBuilder::default()
         .with_inner_size(window_size)
+        .with_min_inner_size(window_size)
         .with_title("Kiraboshi")
         .with_decorations(false)
-        .with_resizable(false);
+        .with_resizable(true);
+
+    if !standalone && settings.mini_player && settings.mini_player_on_top {
+        viewport = viewport.with_window_level(egui::WindowLevel::AlwaysOnTop);
+    }
 
     if let Some(icon) = load_icon() {
         viewport = viewport.with_icon(std::sync::Arc::new(icon));
@@ -52,71 +181,433 @@ pub fn run(file_arg: Option<PathBuf>) -> Result<(), eframe::Error> {
     eframe::run_native(
         "Kiraboshi",
         options,
-        Box::new(move |cc| Ok(Box::new(KiraboshiApp::new(cc, file_arg)))),
+        Box::new(move |cc| Ok(Box::new(KiraboshiApp::new(cc, files, skipped, standalone, status_port, read_only)))),
     )
 }
 
+type DuplicateScan = (Vec<Vec<PathBuf>>, HashMap<PathBuf, u64>);
+type LibraryScan = (MetadataCache, Vec<PathBuf>, HashMap<PathBuf, Vec<Chapter>>);
+type MetadataScan = Vec<(PathBuf, TrackMetadata, Option<Option<f32>>)>;
+type ScrobblePlay = (Scrobble, f64, bool);
+type TrackIdentity = (Option<String>, Option<String>, Option<String>, Option<String>, u64);
+
 pub struct KiraboshiApp {
-    audio: AudioEngine,
+    core: PlayerCore,
     volume: f32,
     error_message: Option<String>,
-    seeking: bool,
-    seek_position: f64,
-    seek_cooldown: u8,
-    playlist: Vec<PathBuf>,
-    was_playing: bool,
+    library: Vec<PathBuf>,
+    active_playlist: Option<String>,
+    transient_queue: Option<(String, QueueSource)>,
+    playlist_names: Vec<String>,
+    playlist_name_draft: Option<(Option<String>, String)>,
     drag_index: Option<usize>,
-    loop_mode: LoopMode,
-    shuffle: bool,
     title_icon: Option<egui::TextureHandle>,
-    expected_size: Option<egui::Vec2>,
     standalone: bool,
+    settings: Settings,
+    show_settings: bool,
+    metadata_cache: HashMap<PathBuf, TrackMetadata>,
+    metadata_store: MetadataCache,
+    metadata_prescan: Option<TaskHandle<MetadataCache>>,
+    metadata_scan: Option<TaskHandle<MetadataScan>>,
+    library_watcher: Option<LibraryWatcher>,
+    bpm_order: Option<(Vec<PathBuf>, Vec<usize>)>,
+    startup: Option<(TaskHandle<LibraryScan>, Vec<PathBuf>)>,
+    waveform_thumbs: HashMap<PathBuf, Option<Vec<f32>>>,
+    view: PlaylistView,
+    filter_draft: Option<(Option<usize>, SmartFilter)>,
+    now_playing_text: Option<String>,
+    vu_levels: (f32, f32),
+    show_lyrics: bool,
+    lyrics: Option<(PathBuf, Option<Lyrics>)>,
+    lyrics_line: Option<usize>,
+    show_chapters: bool,
+    chapters: Option<(PathBuf, Vec<Chapter>)>,
+    cue_cache: HashMap<PathBuf, Vec<Chapter>>,
+    last_cue: Option<(PathBuf, usize)>,
+    status_server: Option<StatusServer>,
+    status_port_override: Option<u16>,
+    scrobbler: Option<Scrobbler>,
+    scrobble_serial: u64,
+    scrobble_play: Option<ScrobblePlay>,
+    scrobble_tick: Instant,
+    lastfm_password: String,
+    lastfm_auth: Option<TaskHandle<String>>,
+    hash_cache: HashMap<PathBuf, u64>,
+    duplicate_groups: Option<Vec<Vec<PathBuf>>>,
+    duplicate_scan: Option<TaskHandle<DuplicateScan>>,
+    tasks: TaskRegistry,
+    tasks_panel_height: f32,
+    backup_include_media: bool,
+    pending_restore: Option<(PathBuf, Backup)>,
+    backup_read: Option<TaskHandle<(PathBuf, Backup)>>,
+    backup_restore: Option<TaskHandle<(RestoreMode, Option<PlaylistFile>)>>,
+    pending_export: Option<(Vec<PathBuf>, PathBuf)>,
+    export_collision: CollisionMode,
+    data_dir_unwritable: bool,
+    read_only: bool,
+    last_interaction: Instant,
+    idle_fading: bool,
+    ab_points: Option<(PathBuf, Option<f64>, Option<f64>)>,
+    taskbar: Option<TaskbarProgress>,
+    taskbar_shown: (Option<(u32, bool)>, Instant),
+    show_bookmarks: bool,
+    bookmark_loop: Option<u64>,
+    loop_preview: Option<TaskHandle<LoopClip>>,
+    selected_index: Option<usize>,
+    scroll_to_selection: bool,
+    playlist_focused: bool,
+    playlist_rect: egui::Rect,
+    type_ahead: (String, Instant),
+    time_entry: Option<String>,
+    window_title: (Option<String>, String, Instant),
+    resume_points: ResumePoints,
+    resume_saved: Instant,
+    library_dirty: Option<Instant>,
+    library_load_failed: bool,
+    pending_resume: Option<ResumePoint>,
+    pending_session: Option<Session>,
+    limiter_flash: f32,
+    accent: AccentTheme,
+    artwork_colors: HashMap<PathBuf, Option<egui::Color32>>,
+    artwork_scan: Option<(TaskHandle<Option<egui::Color32>>, PathBuf)>,
+    osd: Option<(Instant, &'static str)>,
+    osd_serial: u64,
+    osd_art: Option<(PathBuf, Option<egui::TextureHandle>)>,
+    advance_queue: Option<(u64, Option<PathBuf>)>,
+    transport_flash: [Option<Instant>; 3],
+    show_equalizer: bool,
+    eq_drag: Option<usize>,
+    eq_preset_name: String,
+    output_device: Option<String>,
+    device_watcher: DeviceWatcher,
+    show_markers: bool,
+    marker_scan: Option<TaskHandle<(PathBuf, Vec<f64>)>>,
 }
 
 impl KiraboshiApp {
-    pub fn new(cc: &eframe::CreationContext<'_>, file_arg: Option<PathBuf>) -> Self {
+    const SIDECAR_EXTENSIONS: [&'static str; 2] = ["cue", "lrc"];
+    const LAUNCH_FADE: Duration = Duration::from_secs(2);
+
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        files: Vec<PathBuf>,
+        skipped: Vec<PathBuf>,
+        standalone: bool,
+        status_port: Option<u16>,
+        read_only: bool,
+    ) -> Self {
         let title_icon = Self::load_title_icon(&cc.egui_ctx);
-        let standalone = file_arg.is_some();
-
-        let mut visuals = egui::Visuals::dark();
-        visuals.selection.bg_fill = egui::Color32::from_rgb(170, 120, 25);
-        visuals.selection.stroke = egui::Stroke::new(1.0, egui::Color32::from_rgb(220, 175, 55));
-        visuals.widgets.inactive.bg_stroke = egui::Stroke::new(1.0, egui::Color32::from_rgb(145, 115, 35));
-        visuals.widgets.inactive.fg_stroke = egui::Stroke::new(1.0, egui::Color32::from_rgb(160, 135, 60));
-        visuals.widgets.hovered.bg_stroke = egui::Stroke::new(1.0, egui::Color32::from_rgb(195, 158, 50));
-        visuals.widgets.hovered.fg_stroke = egui::Stroke::new(1.0, egui::Color32::from_rgb(215, 175, 65));
-        visuals.widgets.active.bg_stroke = egui::Stroke::new(1.0, egui::Color32::from_rgb(220, 178, 60));
-        visuals.widgets.active.fg_stroke = egui::Stroke::new(1.0, egui::Color32::from_rgb(230, 190, 75));
-        cc.egui_ctx.set_visuals(visuals);
+        let settings = Settings::load(&Self::settings_file());
+
+        Palette::for_accent(None).apply(&cc.egui_ctx);
+        cc.egui_ctx.options_mut(|o| o.zoom_with_keyboard = false);
+        cc.egui_ctx.set_zoom_factor(settings.ui_scale);
+        let (library, library_error) = match PlaylistFile::load(&Self::playlist_file()) {
+            Ok(library) => (library, None),
+            Err(e) => (PlaylistFile::default(), Some(e)),
+        };
+        let legacy_library = library.legacy;
+        let output_device = output_device_name();
+        let volume = settings.startup_volume(output_device.as_deref());
         let mut app = Self {
-            audio: AudioEngine::new(),
-            volume: 0.5,
-            error_message: None,
-            seeking: false,
-            seek_position: 0.0,
-            seek_cooldown: 0,
-            playlist: if standalone { Vec::new() } else { Self::load_playlist() },
-            was_playing: false,
+            core: PlayerCore::new(
+                AudioEngine::new(volume, settings.output_sample_rate, settings.output_latency.buffer_frames()),
+                if standalone { files.clone() } else { library.paths.clone() },
+                library.tracks,
+            ),
+            volume,
+            library_load_failed: library_error.is_some(),
+            error_message: library_error,
+            library: library.paths,
+            active_playlist: None,
+            transient_queue: None,
+            playlist_names: NamedPlaylist::list(&Self::playlists_dir()),
+            playlist_name_draft: None,
             drag_index: None,
-            loop_mode: LoopMode::Off,
-            shuffle: false,
             title_icon,
-            expected_size: None,
             standalone,
+            settings,
+            show_settings: false,
+            metadata_cache: HashMap::new(),
+            metadata_store: MetadataCache::load(&Self::metadata_cache_file()),
+            metadata_prescan: None,
+            metadata_scan: None,
+            library_watcher: None,
+            bpm_order: None,
+            startup: None,
+            waveform_thumbs: HashMap::new(),
+            view: PlaylistView::Library,
+            filter_draft: None,
+            now_playing_text: None,
+            vu_levels: (0.0, 0.0),
+            show_lyrics: false,
+            lyrics: None,
+            lyrics_line: None,
+            show_chapters: false,
+            chapters: None,
+            cue_cache: HashMap::new(),
+            last_cue: None,
+            status_server: None,
+            scrobbler: None,
+            scrobble_serial: 0,
+            scrobble_play: None,
+            scrobble_tick: Instant::now(),
+            lastfm_password: String::new(),
+            lastfm_auth: None,
+            status_port_override: status_port,
+            hash_cache: HashMap::new(),
+            duplicate_groups: None,
+            duplicate_scan: None,
+            tasks: TaskRegistry::new(),
+            tasks_panel_height: 0.0,
+            backup_include_media: false,
+            pending_restore: None,
+            backup_read: None,
+            backup_restore: None,
+            pending_export: None,
+            export_collision: CollisionMode::Skip,
+            data_dir_unwritable: !standalone && !is_writable(&Self::data_dir()),
+            read_only,
+            last_interaction: Instant::now(),
+            idle_fading: false,
+            ab_points: None,
+            taskbar: None,
+            taskbar_shown: (None, Instant::now()),
+            show_bookmarks: false,
+            bookmark_loop: None,
+            loop_preview: None,
+            selected_index: None,
+            scroll_to_selection: false,
+            playlist_focused: false,
+            playlist_rect: egui::Rect::NOTHING,
+            type_ahead: (String::new(), Instant::now()),
+            time_entry: None,
+            window_title: (None, String::new(), Instant::now()),
+            resume_points: ResumePoints::load(&Self::resume_file()),
+            resume_saved: Instant::now(),
+            library_dirty: None,
+            pending_resume: None,
+            pending_session: None,
+            limiter_flash: 0.0,
+            accent: AccentTheme::new(),
+            artwork_colors: HashMap::new(),
+            artwork_scan: None,
+            osd: None,
+            osd_serial: 0,
+            osd_art: None,
+            advance_queue: None,
+            transport_flash: [None; 3],
+            show_equalizer: false,
+            eq_drag: None,
+            eq_preset_name: String::new(),
+            device_watcher: DeviceWatcher::start(output_device.clone(), Self::DEVICE_POLL),
+            output_device,
+            show_markers: false,
+            marker_scan: None,
         };
-        app.audio.set_volume(app.volume);
-        if let Some(path) = file_arg {
-            let _ = app.audio.play_song(&path);
+        app.sync_status_server();
+        app.sync_scrobbler();
+        app.check_output_settings();
+        app.core.audio.set_seek_fade(app.settings.seek_fade);
+        app.apply_limiter_settings();
+        app.apply_crossfeed_settings();
+        app.apply_silence_settings();
+        app.apply_eq_settings();
+        app.core.shuffle_mode = app.settings.shuffle_mode;
+        app.core.end_tolerance = app.settings.end_tolerance_secs as f64;
+        if legacy_library && !standalone {
+            app.migrate_legacy_track_data();
+        }
+        if standalone {
+            let fade = app.settings.fade_in_on_launch.then_some(Self::LAUNCH_FADE);
+            if let Some(first) = files.first()
+                && let Err(e) = app.start_track(first, fade)
+            {
+                app.error_message = Some(e);
+            }
         } else {
-            app.scan_songs();
+            app.load_playlist(app.settings.active_playlist.clone());
+            app.begin_startup_scan(files);
+        }
+        if !skipped.is_empty() {
+            let names: Vec<String> = skipped.iter().map(|p| p.display().to_string()).collect();
+            app.error_message = Some(format!("Skipped files that could not be opened: {}", names.join(", ")));
         }
         app
     }
 
+    fn begin_startup_scan(&mut self, files: Vec<PathBuf>) {
+        let mut store = self.metadata_store.clone();
+        let scan = self.tasks.spawn("Loading library", move |reporter| {
+            let paths = Self::library_files(&Self::data_dir());
+            reporter.note(format!("Found {} tracks", paths.len()));
+            let mut cues = HashMap::new();
+            for (i, path) in paths.iter().enumerate() {
+                if store.get(path).is_none() {
+                    store.insert(path, TrackMetadata::read(path));
+                }
+                cues.insert(path.clone(), CueSheet::split_chapters(path));
+                reporter.progress(i + 1, paths.len());
+            }
+            Ok((store, paths, cues))
+        });
+        self.startup = Some((scan, files));
+    }
+
+    fn finish_startup(&mut self, scan: Result<LibraryScan, String>, files: Vec<PathBuf>) {
+        self.watch_library();
+        match scan {
+            Ok((store, on_disk, cues)) => {
+                self.metadata_store = store;
+                self.cue_cache.extend(cues);
+                self.metadata_cache.clear();
+                self.bpm_order = None;
+                self.apply_scan(on_disk);
+                self.save_metadata_cache();
+            }
+            Err(e) => {
+                self.error_message = Some(format!("Failed to load the library: {}", e));
+                self.scan_songs();
+            }
+        }
+        let fade = self.settings.fade_in_on_launch.then_some(Self::LAUNCH_FADE);
+        self.open_in_library(&files, fade);
+        if files.is_empty() && self.settings.restore_session {
+            self.pending_session = Session::load(&Self::session_file())
+                .filter(|session| session.track.as_ref().is_some_and(|t| t.is_file()));
+        }
+        if self.settings.prescan_metadata {
+            self.prescan_metadata();
+        }
+    }
+
+    fn show_loading_view(&mut self, ctx: &egui::Context) {
+        let Some((scan, _)) = &self.startup else {
+            return;
+        };
+        let task = self.tasks.get(scan.id());
+        let fraction = task.and_then(|t| t.fraction());
+        let note = task.and_then(|t| t.note.clone()).unwrap_or_else(|| "Looking for music...".to_string());
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.add_space(ui.available_height() / 2.0 - 40.0);
+            ui.vertical_centered(|ui| {
+                ui.label(
+                    egui::RichText::new("Loading library")
+                        .size(16.0)
+                        .color(egui::Color32::from_rgb(190, 155, 65)),
+                );
+                ui.add_space(8.0);
+                let bar = match fraction {
+                    Some(fraction) => egui::ProgressBar::new(fraction).show_percentage(),
+                    None => egui::ProgressBar::new(0.0).animate(true),
+                };
+                ui.add(bar.desired_width(280.0));
+                ui.add_space(4.0);
+                ui.label(egui::RichText::new(note).size(12.0).color(egui::Color32::from_gray(160)));
+            });
+        });
+    }
+
+    fn set_mini_player(&mut self, ctx: &egui::Context, enabled: bool) {
+        self.settings.mini_player = enabled;
+        if enabled {
+            self.settings.full_window_size = ctx.input(|i| i.viewport().inner_rect).map(|r| (r.width(), r.height()));
+            self.show_settings = false;
+        }
+        let min_size = min_window_size(false, &self.settings);
+        let size = if enabled {
+            min_size
+        } else {
+            self.settings.full_window_size.take().map_or(min_size, |(w, h)| egui::vec2(w, h).max(min_size))
+        };
+        let level = if enabled && self.settings.mini_player_on_top {
+            egui::WindowLevel::AlwaysOnTop
+        } else {
+            egui::WindowLevel::Normal
+        };
+        ctx.send_viewport_cmd(egui::ViewportCommand::Maximized(false));
+        ctx.send_viewport_cmd(egui::ViewportCommand::MinInnerSize(min_size));
+        ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size));
+        ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(level));
+        self.save_settings();
+    }
+
+    fn show_mini_player(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal_centered(|ui| {
+                let btn = egui::vec2(52.0, 24.0);
+                let label = |text: &str| egui::RichText::new(text).color(egui::Color32::from_gray(175));
+                if ui.add_sized(btn, egui::Button::new(label("Prev"))).clicked() {
+                    self.play_previous();
+                }
+                let play_text = if self.core.audio.is_playing() { "Pause" } else { "Play" };
+                if ui.add_sized(btn, egui::Button::new(label(play_text))).clicked() {
+                    self.toggle_playback();
+                }
+                if ui
+                    .add_sized(btn, egui::Button::new(label("Next")))
+                    .on_hover_text("Hold Shift to skip without crossfading")
+                    .clicked()
+                {
+                    self.skip_next(ui.input(|i| i.modifiers.shift));
+                }
+                ui.add_space(8.0);
+                let title_width = (ui.available_width() * 0.4).max(120.0);
+                let (title, color) = match (&self.error_message, self.core.audio.current_file()) {
+                    (Some(error), _) => (error.clone(), egui::Color32::from_rgb(220, 120, 80)),
+                    (None, Some(path)) => (Self::display_name(path), egui::Color32::WHITE),
+                    (None, None) => ("No track loaded".to_string(), egui::Color32::GRAY),
+                };
+                ui.allocate_ui(egui::vec2(title_width, 24.0), |ui| {
+                    ui.set_min_width(title_width);
+                    Self::marquee_label(
+                        ui,
+                        &title,
+                        egui::FontId::new(13.0, egui::FontFamily::Proportional),
+                        color,
+                        title_width,
+                    );
+                });
+                ui.add_space(8.0);
+                let duration = self.core.audio.get_duration();
+                let width = (ui.available_width() - 16.0).max(60.0);
+                self.seek_slider(ui, width, duration);
+            });
+        });
+    }
+
+    fn open_in_library(&mut self, files: &[PathBuf], fade: Option<Duration>) {
+        let mut added = Vec::new();
+        for path in files {
+            match Self::copy_to_data(path, None) {
+                Ok(dest) => added.push(dest),
+                Err(e) => self.error_message = Some(e),
+            }
+        }
+        let Some(first) = added.first().cloned() else {
+            return;
+        };
+        self.scan_songs();
+        if self.active_playlist.is_some() {
+            for dest in &added {
+                if !self.core.playlist.contains(dest) {
+                    self.core.playlist.push(dest.clone());
+                }
+            }
+            self.save_playlist();
+        }
+        if added.len() > 1 {
+            self.start_transient_queue("Opened Files".to_string(), QueueSource::Files, added);
+            return;
+        }
+        match self.start_track(&first, fade) {
+            Ok(()) => self.reveal_current_track(),
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
     fn load_title_icon(ctx: &egui::Context) -> Option<egui::TextureHandle> {
-        let icon_path = exe_dir().join("assets/icon.ico");
-        let img = image::open(&icon_path).ok()?;
-        let rgba = img.to_rgba8();
+        let rgba = icon_image()?;
         let (w, h) = rgba.dimensions();
         let color_image = egui::ColorImage::from_rgba_unmultiplied(
             [w as usize, h as usize],
@@ -131,172 +622,4548 @@ impl KiraboshiApp {
         format!("{:02}:{:02}", mins, secs)
     }
 
+    fn parse_time(text: &str) -> Option<f64> {
+        let parts: Vec<&str> = text.trim().split(':').collect();
+        if parts.len() > 3 {
+            return None;
+        }
+        parts.iter().try_fold(0.0, |acc, part| {
+            let value = part.trim().parse::<f64>().ok().filter(|v| v.is_finite() && *v >= 0.0)?;
+            Some(acc * 60.0 + value)
+        })
+    }
+
     fn display_name(path: &Path) -> String {
         path.file_stem()
-            .and_then(|n| n.to_str())
-            .unwrap_or("Unknown")
-            .to_string()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "Unknown".to_string())
     }
 
     fn data_dir() -> PathBuf {
-        PathBuf::from("data")
+        data_dir()
     }
 
     fn playlist_file() -> PathBuf {
         Self::data_dir().join(".kiraboshi")
     }
 
-    fn load_playlist() -> Vec<PathBuf> {
-        let path = Self::playlist_file();
-        std::fs::read_to_string(&path)
-            .unwrap_or_default()
-            .lines()
-            .filter(|l| !l.is_empty())
-            .map(PathBuf::from)
-            .collect()
+    fn playlists_dir() -> PathBuf {
+        Self::data_dir().join(".kiraboshi_playlists")
     }
 
-    fn save_playlist(&self) {
-        let contents: String = self.playlist
-            .iter()
-            .filter_map(|p| p.to_str())
-            .collect::<Vec<_>>()
-            .join("\n");
-        let _ = std::fs::write(Self::playlist_file(), contents);
+    fn save_playlist(&mut self) {
+        if self.standalone || self.transient_queue.is_some() {
+            return;
+        }
+        if self.active_playlist.is_none() {
+            self.library = self.core.playlist.clone();
+        }
+        if self.check_writable().is_err() {
+            return;
+        }
+        if let Some(name) = &self.active_playlist {
+            let saved = NamedPlaylist {
+                name: name.clone(),
+                paths: self.core.playlist.clone(),
+            }
+            .save(&Self::playlists_dir());
+            if let Err(e) = saved {
+                self.error_message = Some(e);
+            }
+        }
+        self.mark_library_dirty();
     }
 
-    fn scan_songs(&mut self) {
-        let dir = Self::data_dir();
-        let extensions = ["mp3", "wav", "ogg", "flac"];
-        let mut on_disk: Vec<PathBuf> = std::fs::read_dir(&dir)
-            .into_iter()
-            .flatten()
-            .filter_map(|e| e.ok())
-            .map(|e| e.path())
-            .filter(|p| {
-                p.extension()
-                    .and_then(|ext| ext.to_str())
-                    .map(|ext| extensions.contains(&ext.to_lowercase().as_str()))
-                    .unwrap_or(false)
-            })
-            .collect();
-        on_disk.sort();
-        self.playlist.retain(|p| on_disk.contains(p));
-        let mut changed = false;
-        for path in &on_disk {
-            if !self.playlist.contains(path) {
-                self.playlist.push(path.clone());
-                changed = true;
+    fn mark_library_dirty(&mut self) {
+        self.library_dirty.get_or_insert_with(Instant::now);
+    }
+
+    fn flush_library(&mut self) {
+        if self.library_dirty.is_none() || self.check_writable().is_err() {
+            return;
+        }
+        match write_library(&Self::playlist_file(), self.library_load_failed, &self.library, &self.core.track_data) {
+            Ok(false) => {}
+            Ok(true) => self.library_dirty = None,
+            Err(e) => {
+                self.library_dirty = None;
+                self.error_message = Some(e);
             }
         }
-        if changed {
+    }
+
+    fn load_playlist(&mut self, name: Option<String>) {
+        if self.transient_queue.take().is_none() {
             self.save_playlist();
         }
+        self.activate_playlist(name);
     }
 
-    fn copy_to_data(&self, source: &PathBuf) -> Result<PathBuf, String> {
-        let dir = Self::data_dir();
-        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create data dir: {}", e))?;
-        let file_name = source.file_name().ok_or("Invalid file name")?;
-        let dest = dir.join(file_name);
-        if dest != *source {
-            std::fs::copy(source, &dest)
-                .map_err(|e| format!("Failed to copy file: {}", e))?;
+    fn activate_playlist(&mut self, name: Option<String>) {
+        let named = name.and_then(|n| NamedPlaylist::load(&Self::playlists_dir(), &n));
+        match named {
+            Some(playlist) => {
+                self.active_playlist = Some(playlist.name);
+                self.core.playlist = playlist.paths;
+            }
+            None => {
+                self.active_playlist = None;
+                self.core.playlist = self.library.clone();
+            }
+        }
+        self.selected_index = None;
+        self.drag_index = None;
+        if self.settings.active_playlist != self.active_playlist {
+            self.settings.active_playlist = self.active_playlist.clone();
+            self.save_settings();
         }
-        Ok(dest)
     }
 
-    fn play_next(&mut self) {
-        if self.playlist.is_empty() {
+    fn open_transient_queue(&mut self, folder: &Path) {
+        let mut paths = Vec::new();
+        Self::collect_audio_files(folder, &mut paths);
+        if paths.is_empty() {
+            self.error_message = Some(format!("No audio files found in {}", folder.display()));
             return;
         }
-        if self.loop_mode == LoopMode::One {
-            if let Some(current) = self.audio.current_file().cloned() {
-                let _ = self.audio.play_song(&current);
-            }
+        paths.sort();
+        self.start_transient_queue(Self::display_name(folder), QueueSource::Folder, paths);
+    }
+
+    fn start_transient_queue(&mut self, name: String, source: QueueSource, paths: Vec<PathBuf>) {
+        self.save_playlist();
+        self.transient_queue = Some((name, source));
+        self.core.playlist = paths;
+        self.core.sequential_run = false;
+        self.selected_index = None;
+        self.drag_index = None;
+        self.view = PlaylistView::Library;
+        let first = self.core.playlist[0].clone();
+        if let Err(e) = self.play_track(&first) {
+            self.error_message = Some(e);
+        }
+    }
+
+    fn close_transient_queue(&mut self) {
+        if self.transient_queue.is_some() {
+            self.load_playlist(self.active_playlist.clone());
+        }
+    }
+
+    fn check_writable(&self) -> Result<(), String> {
+        if self.read_only {
+            return Err("The library is read-only".to_string());
+        }
+        Ok(())
+    }
+
+    fn create_playlist(&mut self, name: &str) -> Result<(), String> {
+        self.check_writable()?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Playlist name cannot be empty".to_string());
+        }
+        if NamedPlaylist::exists(&Self::playlists_dir(), name) {
+            return Err(format!("A playlist named \"{}\" already exists", name));
+        }
+        NamedPlaylist {
+            name: name.to_string(),
+            paths: Vec::new(),
+        }
+        .save(&Self::playlists_dir())?;
+        self.playlist_names = NamedPlaylist::list(&Self::playlists_dir());
+        self.load_playlist(Some(name.to_string()));
+        Ok(())
+    }
+
+    fn rename_playlist(&mut self, old: &str, new: &str) -> Result<(), String> {
+        self.check_writable()?;
+        let new = new.trim();
+        if new.is_empty() {
+            return Err("Playlist name cannot be empty".to_string());
+        }
+        let dir = Self::playlists_dir();
+        let renamed_file = NamedPlaylist::file_for(&dir, new) != NamedPlaylist::file_for(&dir, old);
+        if renamed_file && NamedPlaylist::exists(&dir, new) {
+            return Err(format!("A playlist named \"{}\" already exists", new));
+        }
+        let Some(mut playlist) = NamedPlaylist::load(&dir, old) else {
+            return Err(format!("Playlist \"{}\" not found", old));
+        };
+        if self.active_playlist.as_deref() == Some(old) {
+            playlist.paths = self.core.playlist.clone();
+        }
+        playlist.name = new.to_string();
+        playlist.save(&dir)?;
+        if renamed_file {
+            NamedPlaylist::delete(&dir, old)?;
+        }
+        self.playlist_names = NamedPlaylist::list(&dir);
+        if self.active_playlist.as_deref() == Some(old) {
+            self.active_playlist = Some(new.to_string());
+            self.settings.active_playlist = self.active_playlist.clone();
+            self.save_settings();
+        }
+        Ok(())
+    }
+
+    fn delete_playlist(&mut self, name: &str) -> Result<(), String> {
+        self.check_writable()?;
+        self.flush_library();
+        let library = delete_named_playlist(&Self::playlists_dir(), &Self::playlist_file(), name)?;
+        self.playlist_names = NamedPlaylist::list(&Self::playlists_dir());
+        if self.active_playlist.as_deref() == Some(name) {
+            self.transient_queue = None;
+            self.library = library;
+            self.activate_playlist(None);
+        }
+        Ok(())
+    }
+
+    fn add_to_playlist(&mut self, name: &str, path: &Path) {
+        if let Err(e) = self.check_writable() {
+            self.error_message = Some(e);
             return;
         }
-        if self.shuffle {
-            let current = self.audio.current_file().cloned();
-            let candidates: Vec<&PathBuf> = self
-                .playlist
-                .iter()
-                .filter(|p| current.as_ref() != Some(*p) || self.playlist.len() == 1)
-                .collect();
-            if let Some(next) = candidates.choose(&mut rand::rng()) {
-                let next = (*next).clone();
-                let _ = self.audio.play_song(&next);
-            }
+        let dir = Self::playlists_dir();
+        let Some(mut playlist) = NamedPlaylist::load(&dir, name) else {
             return;
+        };
+        if !playlist.paths.iter().any(|p| p == path) {
+            playlist.paths.push(path.to_path_buf());
+            if let Err(e) = playlist.save(&dir) {
+                self.error_message = Some(e);
+            }
         }
-        if let Some(current) = self.audio.current_file().cloned() {
-            if let Some(idx) = self.playlist.iter().position(|p| *p == current) {
-                let next_idx = idx + 1;
-                if next_idx < self.playlist.len() {
-                    let next = self.playlist[next_idx].clone();
-                    let _ = self.audio.play_song(&next);
-                } else if self.loop_mode == LoopMode::All {
-                    let next = self.playlist[0].clone();
-                    let _ = self.audio.play_song(&next);
+    }
+
+    fn show_playlist_name_window(&mut self, ctx: &egui::Context) {
+        let Some((renaming, mut name)) = self.playlist_name_draft.take() else {
+            return;
+        };
+        let mut open = true;
+        let mut save = false;
+        let title = if renaming.is_some() { "Rename Playlist" } else { "New Playlist" };
+        egui::Window::new(title)
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let response = ui.text_edit_singleline(&mut name);
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    save = true;
+                }
+                ui.add_space(4.0);
+                if ui.button("Save").clicked() {
+                    save = true;
+                }
+            });
+        if save {
+            let result = match &renaming {
+                Some(old) => self.rename_playlist(old, &name),
+                None => self.create_playlist(&name),
+            };
+            match result {
+                Ok(()) => self.error_message = None,
+                Err(e) => {
+                    self.error_message = Some(e);
+                    self.playlist_name_draft = Some((renaming, name));
                 }
             }
+        } else if open {
+            self.playlist_name_draft = Some((renaming, name));
         }
     }
-}
 
-impl eframe::App for KiraboshiApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let current_size = ctx.input(|i| {
-            i.viewport().inner_rect.map(|r| r.size())
-        });
-        if let Some(size) = current_size {
-            match self.expected_size {
-                None => self.expected_size = Some(size),
-                Some(expected) => {
-                    let diff = (size.x - expected.x).abs() + (size.y - expected.y).abs();
-                    if diff > 1.0 {
-                        ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(expected));
+    fn migrate_legacy_track_data(&mut self) {
+        let gains_file = Self::data_dir().join(".kiraboshi_gain");
+        let plays_file = Self::data_dir().join(".kiraboshi_plays");
+        for (path, gain_db) in load_legacy_path_map::<f32>(&gains_file) {
+            self.core.track_data.entry(path).or_default().gain_db = gain_db;
+        }
+        for (path, play_count) in load_legacy_path_map::<u32>(&plays_file) {
+            self.core.track_data.entry(path).or_default().play_count = play_count;
+        }
+        if self.check_writable().is_ok()
+            && write_library(&Self::playlist_file(), self.library_load_failed, &self.library, &self.core.track_data) == Ok(true)
+        {
+            let _ = std::fs::remove_file(gains_file);
+            let _ = std::fs::remove_file(plays_file);
+        }
+    }
+
+    fn settings_file() -> PathBuf {
+        Self::data_dir().join(".kiraboshi_settings")
+    }
+
+    fn save_all(&mut self) {
+        self.save_session();
+        self.save_playlist();
+        self.flush_library();
+        self.save_settings();
+        self.save_resume_points();
+        self.save_metadata_cache();
+    }
+
+    fn metadata_cache_file() -> PathBuf {
+        Self::data_dir().join(".kiraboshi_cache").join("metadata.json")
+    }
+
+    fn save_metadata_cache(&mut self) {
+        if self.standalone || self.check_writable().is_err() {
+            return;
+        }
+        if let Err(e) = self.metadata_store.save(&Self::metadata_cache_file()) {
+            self.error_message = Some(e);
+        }
+    }
+
+    fn waveform_thumb(&mut self, path: &Path) -> Option<&[f32]> {
+        if !self.waveform_thumbs.contains_key(path) {
+            let peaks = self
+                .metadata_store
+                .get(path)
+                .and_then(|e| e.waveform.as_ref())
+                .filter(|peaks| peaks.len() == WAVEFORM_POINTS)
+                .map(|peaks| peaks.iter().map(|&p| p as f32 / 255.0).collect());
+            self.waveform_thumbs.insert(path.to_path_buf(), peaks);
+        }
+        self.waveform_thumbs.get(path)?.as_deref()
+    }
+
+    fn paint_waveform_thumb(painter: &egui::Painter, rect: egui::Rect, peaks: Option<&[f32]>, color: egui::Color32) {
+        let stroke = egui::Stroke::new(1.0, color);
+        let Some(peaks) = peaks else {
+            painter.line_segment([rect.left_center(), rect.right_center()], stroke);
+            return;
+        };
+        let step = rect.width() / (peaks.len().max(2) - 1) as f32;
+        let half = rect.height() / 2.0;
+        let contour = |sign: f32| -> Vec<egui::Pos2> {
+            peaks
+                .iter()
+                .enumerate()
+                .map(|(i, p)| egui::pos2(rect.left() + i as f32 * step, rect.center().y + sign * p.max(0.04) * half))
+                .collect()
+        };
+        painter.add(egui::Shape::line(contour(-1.0), stroke));
+        painter.add(egui::Shape::line(contour(1.0), stroke));
+    }
+
+    fn prescan_metadata(&mut self) {
+        if self.metadata_prescan.is_some() {
+            return;
+        }
+        let paths = self.library.clone();
+        let mut store = self.metadata_store.clone();
+        let waveforms = self.settings.row_waveforms;
+        let estimate = self.settings.estimate_bpm;
+        self.metadata_prescan = Some(self.tasks.spawn("Scanning library metadata", move |reporter| {
+            let keep: HashSet<&Path> = paths.iter().map(|p| p.as_path()).collect();
+            store.retain(|p| keep.contains(p));
+            let mut scanned = 0;
+            for (i, path) in paths.iter().enumerate() {
+                let cached = store.get(path);
+                let needs_color = cached.is_none_or(|e| e.artwork_color.is_none());
+                let needs_waveform = waveforms && cached.is_none_or(|e| e.waveform.is_none());
+                let needs_bpm = estimate && cached.is_none_or(|e| e.metadata.bpm.is_none() && e.estimated_bpm.is_none());
+                if needs_color || needs_waveform || needs_bpm {
+                    let metadata = cached.map(|e| e.metadata.clone()).unwrap_or_else(|| TrackMetadata::read(path));
+                    let tagged_bpm = metadata.bpm.is_some();
+                    store.insert(path, metadata);
+                    if needs_bpm && !tagged_bpm {
+                        store.set_estimated_bpm(path, estimate_bpm(path).ok().flatten());
+                    }
+                    if needs_color {
+                        let color = TrackMetadata::read_artwork(path).and_then(|bytes| dominant_color(&bytes));
+                        store.set_artwork_color(path, color.map(|c| [c.r(), c.g(), c.b()]));
+                    }
+                    if needs_waveform {
+                        store.set_waveform(path, waveform_peaks(path).unwrap_or_default());
                     }
+                    scanned += 1;
                 }
+                reporter.progress(i + 1, paths.len());
             }
-        }
+            reporter.note(format!("{} of {} tracks updated", scanned, paths.len()));
+            Ok(store)
+        }));
+    }
 
-        ctx.request_repaint();
+    fn resume_file() -> PathBuf {
+        Self::data_dir().join(".kiraboshi_resume")
+    }
 
-        if !self.standalone && self.was_playing && self.audio.is_finished() {
-            self.play_next();
+    fn session_file() -> PathBuf {
+        Self::data_dir().join(".kiraboshi_session")
+    }
+
+    fn save_session(&mut self) {
+        if self.standalone || self.check_writable().is_err() {
+            return;
+        }
+        let Some(track) = self.core.audio.current_file().cloned() else {
+            let _ = std::fs::remove_file(Self::session_file());
+            return;
+        };
+        let session = Session {
+            playlist: self.active_playlist.clone(),
+            view: self.view.key(),
+            queue: self.transient_queue.clone().map(|(name, source)| (name, source, self.core.playlist.clone())),
+            history: self.core.history.iter().cloned().collect(),
+            track: Some(track),
+            position: self.core.audio.get_position(),
+            playing: self.core.audio.is_playing(),
+            shuffle: self.core.shuffle,
+            reverse: self.core.reverse,
+            loop_mode: self.core.loop_mode.as_str().to_string(),
+        };
+        if let Err(e) = session.save(&Self::session_file()) {
+            self.error_message = Some(e);
+        }
+    }
+
+    fn restore_session(&mut self, session: Session) {
+        let Some(track) = session.track else {
+            return;
+        };
+        if self.transient_queue.is_some() || session.playlist != self.active_playlist {
+            self.load_playlist(session.playlist);
+        }
+        if let Some((name, source, paths)) = session.queue {
+            let paths: Vec<PathBuf> = paths.into_iter().filter(|p| p.is_file()).collect();
+            if !paths.is_empty() {
+                self.save_playlist();
+                self.transient_queue = Some((name, source));
+                self.core.playlist = paths;
+            }
         }
-        if self.standalone && self.was_playing && self.audio.is_finished() {
-            if self.loop_mode == LoopMode::One {
-                if let Some(current) = self.audio.current_file().cloned() {
-                    let _ = self.audio.play_song(&current);
+        self.view = PlaylistView::from_key(&session.view)
+            .filter(|view| !matches!(view, PlaylistView::Smart(i) if *i >= self.settings.smart_filters.len()))
+            .unwrap_or(PlaylistView::Library);
+        self.core.shuffle = session.shuffle;
+        self.core.reverse = session.reverse;
+        self.core.loop_mode = LoopMode::from_name(&session.loop_mode).unwrap_or(LoopMode::Off);
+        self.core.sequential_run = false;
+        self.selected_index = None;
+        self.drag_index = None;
+        match self.play_track(&track) {
+            Ok(()) => {
+                self.error_message = None;
+                self.seek_to(session.position);
+                if !session.playing {
+                    self.core.audio.pause();
                 }
+                self.core.history = session.history.into_iter().filter(|p| p.is_file()).collect();
+                self.reveal_current_track();
             }
+            Err(e) => self.error_message = Some(e),
         }
-        self.was_playing = self.audio.is_playing();
+    }
 
-        egui::TopBottomPanel::top("title_bar")
-            .exact_height(30.0)
-            .frame(egui::Frame::NONE.fill(egui::Color32::from_gray(25)))
+    fn show_session_window(&mut self, ctx: &egui::Context) {
+        let Some(session) = self.pending_session.take() else {
+            return;
+        };
+        let mut open = true;
+        let mut restore = false;
+        let mut dismiss = false;
+        let source = match (&session.queue, &session.playlist) {
+            (Some((name, QueueSource::Folder, _)), _) => format!("the folder {}", name),
+            (Some((_, QueueSource::Files, tracks)), _) => format!("the {} opened files", tracks.len()),
+            (None, Some(name)) => format!("the playlist {}", name),
+            (None, None) => "the library".to_string(),
+        };
+        egui::Window::new("Restore Session")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
             .show(ctx, |ui| {
-                ui.set_clip_rect(ui.max_rect());
-                ui.horizontal_centered(|ui| {
-                    ui.add_space(8.0);
-                    if let Some(icon) = &self.title_icon {
-                        let icon_size = egui::vec2(20.0, 20.0);
-                        ui.image(egui::load::SizedTexture::new(icon.id(), icon_size));
-                    }
+                if let Some(track) = &session.track {
+                    ui.label(format!(
+                        "Pick up where you left off in {} with {} at {}?",
+                        source,
+                        Self::display_name(track),
+                        Self::format_time(session.position),
+                    ));
+                }
+                ui.horizontal(|ui| {
+                    restore = ui.button("Restore").clicked();
+                    dismiss = ui.button("Dismiss").clicked();
+                });
+            });
+        if restore {
+            self.restore_session(session);
+        } else if open && !dismiss {
+            self.pending_session = Some(session);
+        }
+    }
 
-                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        ui.spacing_mut().item_spacing.x = 0.0;
-                        let btn_size = egui::vec2(46.0, 30.0);
+    fn scrobble_queue_file() -> PathBuf {
+        Self::data_dir().join(".kiraboshi_scrobbles")
+    }
 
-                        let (close_rect, close_resp) = ui.allocate_exact_size(btn_size, egui::Sense::click());
-                        let close_hovered = ctx.input(|i| i.pointer.hover_pos().is_some_and(|p| close_rect.contains(p)));
-                        if close_hovered {
-                            ui.painter().rect_filled(close_rect, 0.0, egui::Color32::from_rgb(210, 100, 20));
-                        }
-                        let cc = close_rect.center();
+    fn save_resume_points(&mut self) {
+        if self.standalone || self.check_writable().is_err() {
+            return;
+        }
+        if let Err(e) = self.resume_points.save(&Self::resume_file()) {
+            self.error_message = Some(e);
+        }
+        self.resume_saved = Instant::now();
+    }
+
+    fn album_folder(path: &Path) -> Option<PathBuf> {
+        let data_dir = Self::data_dir();
+        path.parent()
+            .filter(|dir| dir.starts_with(&data_dir) && *dir != data_dir)
+            .map(Path::to_path_buf)
+    }
+
+    fn track_resume_point(&mut self) {
+        if self.standalone || !self.core.audio.is_playing() {
+            return;
+        }
+        let Some(path) = self.core.audio.current_file().cloned() else {
+            return;
+        };
+        if let Some(folder) = Self::album_folder(&path) {
+            self.resume_points.set(&folder, &path, self.core.audio.get_position());
+            if self.resume_saved.elapsed() >= Duration::from_secs(10) {
+                self.save_resume_points();
+            }
+        }
+    }
+
+    fn resume_album(&mut self, point: &ResumePoint) {
+        match self.play_track(&point.track) {
+            Ok(_) => {
+                self.error_message = None;
+                self.seek_to(point.position);
+            }
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    fn offer_resume(&mut self, path: &Path, previous: Option<&PathBuf>) {
+        let Some(folder) = Self::album_folder(path) else {
+            return;
+        };
+        if previous.and_then(|p| Self::album_folder(p)).as_ref() == Some(&folder) {
+            return;
+        }
+        self.pending_resume = self
+            .resume_points
+            .get(&folder)
+            .filter(|point| point.track != path || point.position > 5.0)
+            .filter(|point| self.library.contains(&point.track))
+            .cloned();
+    }
+
+    fn show_resume_window(&mut self, ctx: &egui::Context) {
+        let Some(point) = self.pending_resume.take() else {
+            return;
+        };
+        let mut open = true;
+        let mut resume = false;
+        let mut dismiss = false;
+        let album = point.folder.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        egui::Window::new("Resume Album")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Continue {} from {} at {}?",
+                    album,
+                    Self::display_name(&point.track),
+                    Self::format_time(point.position),
+                ));
+                ui.horizontal(|ui| {
+                    resume = ui.button("Resume").clicked();
+                    dismiss = ui.button("Dismiss").clicked();
+                });
+            });
+        if resume {
+            self.resume_album(&point);
+        } else if open && !dismiss {
+            self.pending_resume = Some(point);
+        }
+    }
+
+    fn save_settings(&mut self) {
+        if self.check_writable().is_err() {
+            return;
+        }
+        if let Err(e) = self.settings.save(&Self::settings_file()) {
+            self.error_message = Some(e);
+        }
+    }
+
+    fn play_count(&self, path: &Path) -> u32 {
+        self.core.track_data.get(path).map(|d| d.play_count).unwrap_or(0)
+    }
+
+    fn rating(&self, path: &Path) -> u8 {
+        self.core.track_data.get(path).map(|d| d.rating).unwrap_or(0)
+    }
+
+    fn set_rating(&mut self, path: &Path, rating: u8) {
+        self.core.track_data.entry(path.to_path_buf()).or_default().rating = rating.min(5);
+        self.save_playlist();
+    }
+
+    fn is_favorite(&self, path: &Path) -> bool {
+        self.core.track_data.get(path).is_some_and(|d| d.favorite)
+    }
+
+    fn toggle_favorite(&mut self, path: &Path) {
+        let data = self.core.track_data.entry(path.to_path_buf()).or_default();
+        data.favorite = !data.favorite;
+        self.save_playlist();
+    }
+
+    fn paint_heart(painter: &egui::Painter, center: egui::Pos2, radius: f32, color: egui::Color32) {
+        let lobe = radius * 0.55;
+        let top = center.y - radius * 0.3;
+        painter.circle_filled(egui::pos2(center.x - lobe * 0.9, top), lobe, color);
+        painter.circle_filled(egui::pos2(center.x + lobe * 0.9, top), lobe, color);
+        painter.add(egui::Shape::convex_polygon(
+            vec![
+                egui::pos2(center.x - lobe * 1.85, top + lobe * 0.25),
+                egui::pos2(center.x + lobe * 1.85, top + lobe * 0.25),
+                egui::pos2(center.x, center.y + radius * 0.8),
+            ],
+            color,
+            egui::Stroke::NONE,
+        ));
+    }
+
+    fn star_rating(ui: &mut egui::Ui, rating: u8) -> Option<u8> {
+        let size = 16.0;
+        let (rect, response) = ui.allocate_exact_size(egui::vec2(size * 5.0 + 8.0, size), egui::Sense::click());
+        let hovered = response
+            .hover_pos()
+            .map(|p| (((p.x - rect.left()) / (size + 2.0)) as u8 + 1).min(5));
+        let shown = hovered.unwrap_or(rating);
+        for star in 0..5u8 {
+            let center = egui::pos2(rect.left() + size / 2.0 + star as f32 * (size + 2.0), rect.center().y);
+            let color = if star < shown {
+                egui::Color32::from_rgb(220, 175, 55)
+            } else {
+                egui::Color32::from_gray(70)
+            };
+            Self::paint_star(ui.painter(), center, size / 2.0, color);
+        }
+        let value = hovered.filter(|_| response.clicked())?;
+        Some(if value == rating { 0 } else { value })
+    }
+
+    fn paint_star(painter: &egui::Painter, center: egui::Pos2, radius: f32, color: egui::Color32) {
+        let point = |i: usize, r: f32| {
+            let angle = -std::f32::consts::FRAC_PI_2 + i as f32 * std::f32::consts::PI / 5.0;
+            center + egui::vec2(angle.cos(), angle.sin()) * r
+        };
+        let inner: Vec<egui::Pos2> = (0..5).map(|i| point(i * 2 + 1, radius * 0.4)).collect();
+        painter.add(egui::Shape::convex_polygon(inner.clone(), color, egui::Stroke::NONE));
+        for i in 0..5 {
+            let tip = point(i * 2, radius);
+            let left = inner[(i + 4) % 5];
+            let right = inner[i];
+            painter.add(egui::Shape::convex_polygon(vec![tip, right, left], color, egui::Stroke::NONE));
+        }
+    }
+
+    fn track_gain(&self, path: &Path) -> f32 {
+        self.core.track_data.get(path).map(|d| d.gain_db).unwrap_or(0.0)
+    }
+
+    fn set_track_gain(&mut self, path: &Path, gain_db: f32, save: bool) {
+        self.core.track_data.entry(path.to_path_buf()).or_default().gain_db = gain_db;
+        if self.core.audio.current_file().map(|p| p.as_path()) == Some(path) {
+            self.core.audio.set_track_gain(gain_db);
+        }
+        if save {
+            self.save_playlist();
+        }
+    }
+
+    fn play_track(&mut self, path: &PathBuf) -> Result<(), String> {
+        self.start_track(path, None)
+    }
+
+    fn select_track(&mut self, path: &PathBuf) -> Result<(), String> {
+        let fade = (self.settings.crossfade_on_select && self.core.audio.is_playing())
+            .then(|| Duration::from_secs_f32(self.settings.crossfade_secs));
+        self.start_track(path, fade)
+    }
+
+    fn start_track(&mut self, path: &PathBuf, fade: Option<Duration>) -> Result<(), String> {
+        self.core.start_track(path, fade)?;
+        if !self.standalone {
+            self.mark_library_dirty();
+        }
+        Ok(())
+    }
+
+    fn metadata(&mut self, path: &Path) -> &TrackMetadata {
+        if self.cached_metadata(path).is_none() {
+            let metadata = TrackMetadata::read(path);
+            self.metadata_store.insert(path, metadata.clone());
+            self.bpm_order = None;
+            self.metadata_cache.insert(path.to_path_buf(), metadata);
+        }
+        &self.metadata_cache[path]
+    }
+
+    fn cached_metadata(&mut self, path: &Path) -> Option<&TrackMetadata> {
+        if !self.metadata_cache.contains_key(path) {
+            let entry = self.metadata_store.get(path)?;
+            let mut metadata = entry.metadata.clone();
+            if metadata.bpm.is_none() {
+                metadata.bpm = entry.estimated_bpm.flatten();
+            }
+            self.metadata_cache.insert(path.to_path_buf(), metadata);
+        }
+        self.metadata_cache.get(path)
+    }
+
+    fn bpm_label(&mut self, path: &Path) -> Option<String> {
+        let metadata = self.cached_metadata(path)?;
+        match (metadata.bpm, &metadata.key) {
+            (Some(bpm), Some(key)) => Some(format!("{:.0} BPM  {}", bpm, key)),
+            (Some(bpm), None) => Some(format!("{:.0} BPM", bpm)),
+            (None, Some(key)) => Some(key.clone()),
+            (None, None) => None,
+        }
+    }
+
+    fn row_tooltip(&mut self, path: &Path) -> Option<String> {
+        let bpm = self.bpm_label(path);
+        let metadata = self.cached_metadata(path)?;
+        let lines: Vec<String> = [metadata.title.clone(), metadata.artist.clone(), metadata.album.clone(), bpm]
+            .into_iter()
+            .flatten()
+            .collect();
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    fn current_tags(&mut self) -> Option<&TrackMetadata> {
+        let path = self.core.audio.current_file()?.clone();
+        Some(self.metadata(&path))
+    }
+
+    fn added_time(&mut self, path: &Path) -> u64 {
+        let data = self.core.track_data.entry(path.to_path_buf()).or_default();
+        *data.added.get_or_insert_with(|| {
+            std::fs::metadata(path)
+                .and_then(|meta| meta.created().or_else(|_| meta.modified()))
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        })
+    }
+
+    fn bpm_order(&mut self) -> Vec<usize> {
+        if let Some((playlist, order)) = &self.bpm_order
+            && *playlist == self.core.playlist
+        {
+            return order.clone();
+        }
+        let missing: Vec<PathBuf> = self
+            .core
+            .playlist
+            .iter()
+            .filter(|p| self.metadata_store.get(p).is_none() && p.exists())
+            .cloned()
+            .collect();
+        self.scan_metadata(missing);
+        let bpms: Vec<Option<f32>> = self
+            .core
+            .playlist
+            .iter()
+            .map(|p| self.metadata_store.get(p).and_then(|e| e.metadata.bpm.or(e.estimated_bpm.flatten())))
+            .collect();
+        let mut order: Vec<usize> = (0..bpms.len()).collect();
+        order.sort_by(|&a, &b| match (bpms[a], bpms[b]) {
+            (Some(x), Some(y)) => x.total_cmp(&y),
+            (x, y) => y.is_some().cmp(&x.is_some()),
+        });
+        self.bpm_order = Some((self.core.playlist.clone(), order.clone()));
+        order
+    }
+
+    fn scan_metadata(&mut self, paths: Vec<PathBuf>) {
+        if paths.is_empty() || self.metadata_scan.is_some() {
+            return;
+        }
+        let estimate = self.settings.estimate_bpm;
+        self.metadata_scan = Some(self.tasks.spawn("Reading track tags", move |reporter| {
+            let total = paths.len();
+            let mut scanned = Vec::with_capacity(total);
+            for (i, path) in paths.into_iter().enumerate() {
+                let metadata = TrackMetadata::read(&path);
+                let estimated = (estimate && metadata.bpm.is_none()).then(|| estimate_bpm(&path).ok().flatten());
+                scanned.push((path, metadata, estimated));
+                reporter.progress(i + 1, total);
+            }
+            Ok(scanned)
+        }));
+    }
+
+    fn visible_indices(&mut self) -> Vec<usize> {
+        let filter = match self.view {
+            PlaylistView::Library => return (0..self.core.playlist.len()).collect(),
+            PlaylistView::RecentlyAdded => {
+                let songs = self.core.playlist.clone();
+                let added: Vec<u64> = songs.iter().map(|song| self.added_time(song)).collect();
+                let mut indices: Vec<usize> = (0..songs.len()).collect();
+                indices.sort_by_key(|&i| std::cmp::Reverse(added[i]));
+                return indices;
+            }
+            PlaylistView::TopRated => {
+                return (0..self.core.playlist.len())
+                    .filter(|&i| self.rating(&self.core.playlist[i]) >= 4)
+                    .collect();
+            }
+            PlaylistView::Favorites => {
+                return (0..self.core.playlist.len())
+                    .filter(|&i| self.is_favorite(&self.core.playlist[i]))
+                    .collect();
+            }
+            PlaylistView::Bpm => return self.bpm_order(),
+            PlaylistView::Smart(i) => match self.settings.smart_filters.get(i) {
+                Some(filter) => filter.clone(),
+                None => return (0..self.core.playlist.len()).collect(),
+            },
+        };
+        let songs = self.core.playlist.clone();
+        let needs_metadata = filter.needs_duration() || filter.needs_bpm();
+        let mut missing = Vec::new();
+        let visible = songs
+            .iter()
+            .enumerate()
+            .filter(|(_, song)| {
+                let (duration, bpm) = match needs_metadata.then(|| self.cached_metadata(song)).flatten() {
+                    Some(metadata) => (metadata.duration, metadata.bpm),
+                    None => {
+                        if needs_metadata && song.exists() {
+                            missing.push(song.to_path_buf());
+                        }
+                        (None, None)
+                    }
+                };
+                filter.matches(song, duration, bpm, self.play_count(song), self.rating(song), self.is_favorite(song))
+            })
+            .map(|(i, _)| i)
+            .collect();
+        self.scan_metadata(missing);
+        visible
+    }
+
+    fn show_filter_editor(&mut self, ctx: &egui::Context) {
+        let Some((index, mut filter)) = self.filter_draft.take() else {
+            return;
+        };
+        let mut open = true;
+        let mut save = false;
+        let mut delete = false;
+        egui::Window::new("Smart Playlist")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                egui::Grid::new("smart_filter_grid")
+                    .num_columns(2)
+                    .spacing([8.0, 6.0])
+                    .show(ui, |ui| {
+                        ui.label("Name");
+                        ui.text_edit_singleline(&mut filter.name);
+                        ui.end_row();
+
+                        ui.label("Format");
+                        egui::ComboBox::from_id_salt("smart_filter_format")
+                            .selected_text(filter.format.as_deref().unwrap_or("Any"))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut filter.format, None, "Any");
+                                for ext in ["mp3", "wav", "ogg", "flac"] {
+                                    ui.selectable_value(&mut filter.format, Some(ext.to_string()), ext);
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.label("Name contains");
+                        ui.text_edit_singleline(&mut filter.name_contains);
+                        ui.end_row();
+
+                        ui.label("Min length (min)");
+                        Self::optional_value(ui, &mut filter.min_duration, 60.0, 1.0 / 60.0);
+                        ui.end_row();
+
+                        ui.label("Max length (min)");
+                        Self::optional_value(ui, &mut filter.max_duration, 360.0, 1.0 / 60.0);
+                        ui.end_row();
+
+                        ui.label("Min plays");
+                        Self::optional_value(ui, &mut filter.min_plays, 1, 1.0);
+                        ui.end_row();
+
+                        ui.label("Max plays");
+                        Self::optional_value(ui, &mut filter.max_plays, 0, 1.0);
+                        ui.end_row();
+
+                        ui.label("Min rating");
+                        Self::optional_value(ui, &mut filter.min_rating, 4, 1.0);
+                        ui.end_row();
+
+                        ui.label("Favorites only");
+                        ui.checkbox(&mut filter.favorites_only, "");
+                        ui.end_row();
+
+                        ui.label("Min BPM");
+                        Self::optional_value(ui, &mut filter.min_bpm, 120.0, 1.0);
+                        ui.end_row();
+
+                        ui.label("Max BPM");
+                        Self::optional_value(ui, &mut filter.max_bpm, 128.0, 1.0);
+                        ui.end_row();
+                    });
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        save = true;
+                    }
+                    if index.is_some() && ui.button("Delete").clicked() {
+                        delete = true;
+                    }
+                });
+            });
+
+        if save {
+            if filter.name.trim().is_empty() {
+                filter.name = "Smart Playlist".to_string();
+            }
+            match index {
+                Some(i) => self.settings.smart_filters[i] = filter,
+                None => {
+                    self.settings.smart_filters.push(filter);
+                    self.view = PlaylistView::Smart(self.settings.smart_filters.len() - 1);
+                }
+            }
+            self.save_settings();
+        } else if delete {
+            if let Some(i) = index {
+                self.settings.smart_filters.remove(i);
+                self.view = PlaylistView::Library;
+                self.save_settings();
+            }
+        } else if open {
+            self.filter_draft = Some((index, filter));
+        }
+    }
+
+    fn optional_value<T: egui::emath::Numeric>(
+        ui: &mut egui::Ui,
+        value: &mut Option<T>,
+        default: T,
+        scale: f64,
+    ) {
+        ui.horizontal(|ui| {
+            let mut enabled = value.is_some();
+            if ui.checkbox(&mut enabled, "").changed() {
+                *value = enabled.then_some(default);
+            }
+            if let Some(v) = value {
+                let mut shown = v.to_f64() * scale;
+                if ui
+                    .add(egui::DragValue::new(&mut shown).speed(0.1).range(0.0..=f64::MAX))
+                    .changed()
+                {
+                    *v = T::from_f64(shown / scale);
+                }
+            }
+        });
+    }
+
+    fn current_chapters(&mut self) -> &[Chapter] {
+        let current = self.core.audio.current_file().cloned();
+        if self.chapters.as_ref().map(|(p, _)| p) != current.as_ref() {
+            self.chapters = current.map(|path| {
+                let file_name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+                let chapters = CueSheet::find_for(&path)
+                    .map(|sheet| sheet.chapters_for(&file_name))
+                    .unwrap_or_else(|| self.metadata(&path).chapters.clone());
+                (path, chapters)
+            });
+        }
+        self.chapters.as_ref().map(|(_, c)| c.as_slice()).unwrap_or(&[])
+    }
+
+    fn cue_tracks(&mut self, path: &Path) -> &[Chapter] {
+        self.cue_cache
+            .entry(path.to_path_buf())
+            .or_insert_with(|| CueSheet::split_chapters(path))
+    }
+
+    fn current_cue_index(&mut self) -> Option<usize> {
+        let path = self.core.audio.current_file()?.clone();
+        let position = self.core.audio.get_position();
+        Self::chapter_at(self.cue_tracks(&path), position)
+    }
+
+    fn play_cue_track(&mut self, path: &PathBuf, index: usize) -> Result<(), String> {
+        let start = self.cue_tracks(path).get(index).map(|c| c.start).unwrap_or(0.0);
+        self.play_track(path)?;
+        if start > 0.0 {
+            self.seek_to(start);
+        }
+        self.last_cue = Some((path.clone(), index));
+        Ok(())
+    }
+
+    fn watch_cue_boundaries(&mut self) {
+        let Some(path) = self.core.audio.current_file().cloned() else {
+            self.last_cue = None;
+            return;
+        };
+        let Some(index) = self.current_cue_index() else {
+            self.last_cue = None;
+            return;
+        };
+        let natural = self.core.audio.is_playing() && self.core.is_settled();
+        let previous = self
+            .last_cue
+            .as_ref()
+            .filter(|(p, _)| *p == path)
+            .map(|(_, i)| *i);
+        if natural && previous.is_some_and(|prev| index == prev + 1) {
+            let prev = index - 1;
+            if self.core.loop_mode == LoopMode::One {
+                let start = self.cue_tracks(&path)[prev].start;
+                self.seek_to(start);
+                return;
+            }
+            if self.core.shuffle && !self.core.sequential_run && !self.standalone {
+                self.play_next();
+                return;
+            }
+        }
+        self.last_cue = Some((path, index));
+    }
+
+    fn chapter_at(chapters: &[Chapter], position: f64) -> Option<usize> {
+        chapters.iter().rposition(|c| c.start <= position)
+    }
+
+    fn seek_to(&mut self, position: f64) {
+        self.core.seek_to(position);
+    }
+
+    fn next_chapter(&mut self) {
+        let position = self.core.audio.get_position();
+        let next = self.current_chapters().iter().find(|c| c.start > position + 0.5).map(|c| c.start);
+        if let Some(start) = next {
+            self.seek_to(start);
+        }
+    }
+
+    fn prev_chapter(&mut self) {
+        let position = self.core.audio.get_position();
+        let chapters = self.current_chapters();
+        let target = match Self::chapter_at(chapters, position) {
+            Some(i) if position - chapters[i].start > 3.0 || i == 0 => chapters[i].start,
+            Some(i) => chapters[i - 1].start,
+            None => 0.0,
+        };
+        self.seek_to(target);
+    }
+
+    fn show_chapters_window(&mut self, ctx: &egui::Context) {
+        let position = self.core.audio.get_position();
+        let chapters = self.current_chapters().to_vec();
+        let current = Self::chapter_at(&chapters, position);
+        let mut open = self.show_chapters;
+        let mut seek_target = None;
+        let mut step = 0;
+        egui::Window::new("Chapters")
+            .open(&mut open)
+            .collapsible(false)
+            .default_size([300.0, 320.0])
+            .show(ctx, |ui| {
+                if chapters.is_empty() {
+                    ui.label(egui::RichText::new("No chapters found").color(egui::Color32::GRAY));
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Prev Chapter").clicked() {
+                        step = -1;
+                    }
+                    if ui.button("Next Chapter").clicked() {
+                        step = 1;
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for (i, chapter) in chapters.iter().enumerate() {
+                            let label = format!("{}  {}", Self::format_time(chapter.start), chapter.title);
+                            if ui.selectable_label(Some(i) == current, label).clicked() {
+                                seek_target = Some(chapter.start);
+                            }
+                        }
+                    });
+            });
+        self.show_chapters = open;
+        if let Some(start) = seek_target {
+            self.seek_to(start);
+        }
+        match step {
+            1 => self.next_chapter(),
+            -1 => self.prev_chapter(),
+            _ => {}
+        }
+    }
+
+    fn current_bookmarks(&self) -> Vec<Bookmark> {
+        self.core.audio
+            .current_file()
+            .and_then(|p| self.core.track_data.get(p))
+            .map(|d| d.bookmarks.clone())
+            .unwrap_or_default()
+    }
+
+    fn set_current_bookmarks(&mut self, mut bookmarks: Vec<Bookmark>) {
+        let Some(path) = self.core.audio.current_file().cloned() else {
+            return;
+        };
+        bookmarks.sort_by(|a, b| a.position.total_cmp(&b.position));
+        self.core.track_data.entry(path).or_default().bookmarks = bookmarks;
+        self.save_playlist();
+    }
+
+    fn current_bookmark_loop(&self) -> Option<(f64, f64)> {
+        let data = self.core.track_data.get(self.core.audio.current_file()?)?;
+        let (start, end) = data.bookmark_loop?;
+        let exists = |position: f64| data.bookmarks.iter().any(|b| b.position == position);
+        (start < end && exists(start) && exists(end)).then_some((start, end))
+    }
+
+    fn set_current_bookmark_loop(&mut self, range: Option<(f64, f64)>) {
+        let Some(path) = self.core.audio.current_file().cloned() else {
+            return;
+        };
+        self.core.track_data.entry(path).or_default().bookmark_loop = range;
+        self.save_playlist();
+    }
+
+    fn enforce_bookmark_loop(&mut self) {
+        if self.bookmark_loop != Some(self.core.audio.track_serial()) {
+            self.bookmark_loop = None;
+            return;
+        }
+        let Some((start, end)) = self.current_bookmark_loop() else {
+            self.bookmark_loop = None;
+            return;
+        };
+        if !self.core.seeking && self.core.audio.is_playing() && self.core.audio.get_position() >= end {
+            self.seek_to(start);
+        }
+    }
+
+    fn show_bookmarks_window(&mut self, ctx: &egui::Context) {
+        let mut bookmarks = self.current_bookmarks();
+        let saved_loop = self.current_bookmark_loop();
+        let mut loop_range = saved_loop;
+        let mut looping = self.bookmark_loop.is_some();
+        let has_track = self.core.audio.current_file().is_some();
+        let position = self.core.audio.get_position();
+        let mut open = self.show_bookmarks;
+        let mut changed = false;
+        let mut seek_target = None;
+        let mut remove = None;
+        egui::Window::new("Bookmarks")
+            .open(&mut open)
+            .collapsible(false)
+            .default_size([300.0, 280.0])
+            .show(ctx, |ui| {
+                if !has_track {
+                    ui.label(egui::RichText::new("No track loaded").color(egui::Color32::GRAY));
+                    return;
+                }
+                if ui.button(format!("Add Bookmark at {}", Self::format_time(position))).clicked() {
+                    bookmarks.push(Bookmark {
+                        name: format!("Bookmark {}", bookmarks.len() + 1),
+                        position,
+                    });
+                    changed = true;
+                }
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for (i, bookmark) in bookmarks.iter_mut().enumerate() {
+                            ui.horizontal(|ui| {
+                                if ui.button(Self::format_time(bookmark.position)).clicked() {
+                                    seek_target = Some(bookmark.position);
+                                }
+                                let edit = ui.add(egui::TextEdit::singleline(&mut bookmark.name).desired_width(160.0));
+                                changed |= edit.lost_focus();
+                                if ui.small_button("x").clicked() {
+                                    remove = Some(i);
+                                }
+                            });
+                        }
+                    });
+                if bookmarks.len() < 2 {
+                    return;
+                }
+                ui.separator();
+                let (mut start, mut end) = loop_range.unwrap_or((bookmarks[0].position, bookmarks[1].position));
+                let name_at = |position: f64| {
+                    bookmarks
+                        .iter()
+                        .find(|b| b.position == position)
+                        .map(|b| b.name.clone())
+                        .unwrap_or_default()
+                };
+                ui.horizontal(|ui| {
+                    ui.label("Loop from");
+                    egui::ComboBox::from_id_salt("bookmark_loop_start")
+                        .selected_text(name_at(start))
+                        .show_ui(ui, |ui| {
+                            for bookmark in &bookmarks {
+                                ui.selectable_value(&mut start, bookmark.position, &bookmark.name);
+                            }
+                        });
+                    ui.label("to");
+                    egui::ComboBox::from_id_salt("bookmark_loop_end")
+                        .selected_text(name_at(end))
+                        .show_ui(ui, |ui| {
+                            for bookmark in &bookmarks {
+                                ui.selectable_value(&mut end, bookmark.position, &bookmark.name);
+                            }
+                        });
+                });
+                let valid = start < end;
+                if loop_range.is_some() || (start, end) != (bookmarks[0].position, bookmarks[1].position) {
+                    loop_range = Some((start, end));
+                }
+                ui.horizontal(|ui| {
+                    ui.add_enabled(valid, egui::Checkbox::new(&mut looping, "Repeat between bookmarks"));
+                    if !valid {
+                        ui.label(egui::RichText::new("Start must come before end").color(egui::Color32::GRAY));
+                    }
+                });
+                if looping && valid {
+                    loop_range = Some((start, end));
+                } else if !valid {
+                    looping = false;
+                }
+            });
+        self.show_bookmarks = open;
+        if loop_range != saved_loop {
+            self.set_current_bookmark_loop(loop_range);
+        }
+        if looping != self.bookmark_loop.is_some() {
+            self.bookmark_loop = looping.then(|| self.core.audio.track_serial());
+        }
+        if let Some(i) = remove {
+            bookmarks.remove(i);
+            changed = true;
+        }
+        if changed {
+            self.set_current_bookmarks(bookmarks);
+        } else if let Some(path) = self.core.audio.current_file().cloned()
+            && let Some(data) = self.core.track_data.get_mut(&path)
+        {
+            data.bookmarks = bookmarks;
+        }
+        if let Some(position) = seek_target {
+            self.seek_to(position);
+        }
+    }
+
+    fn draw_seek_preview(&mut self, ui: &mut egui::Ui, rail: egui::Rect, duration: f64) {
+        if duration <= 0.0 {
+            return;
+        }
+        let target = self.core.seek_position.clamp(0.0, duration);
+        let offset = target - self.core.audio.get_position();
+        let sign = if offset < 0.0 { "-" } else { "+" };
+        let chapter = self
+            .current_chapters()
+            .iter()
+            .rev()
+            .find(|c| c.start <= target)
+            .map(|c| c.title.clone());
+        let x = rail.left() + (target / duration) as f32 * rail.width();
+        egui::Area::new(egui::Id::new("seek_preview"))
+            .order(egui::Order::Tooltip)
+            .fixed_pos(egui::pos2(x, rail.top() - 4.0))
+            .pivot(egui::Align2::CENTER_BOTTOM)
+            .interactable(false)
+            .show(ui.ctx(), |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.vertical_centered(|ui| {
+                        ui.label(egui::RichText::new(Self::format_time(target)).monospace().size(13.0));
+                        ui.label(
+                            egui::RichText::new(format!("{}{}", sign, Self::format_time(offset.abs())))
+                                .monospace()
+                                .size(10.0)
+                                .color(egui::Color32::from_gray(140)),
+                        );
+                        if let Some(chapter) = chapter {
+                            ui.label(
+                                egui::RichText::new(chapter)
+                                    .size(11.0)
+                                    .color(egui::Color32::from_rgb(190, 155, 65)),
+                            );
+                        }
+                    });
+                });
+            });
+    }
+
+    fn draw_bookmark_markers(&mut self, ui: &mut egui::Ui, rail: egui::Rect, duration: f64) {
+        if duration <= 0.0 {
+            return;
+        }
+        let mut seek_target = None;
+        for (i, bookmark) in self.current_bookmarks().iter().enumerate() {
+            let x = rail.left() + (bookmark.position / duration).clamp(0.0, 1.0) as f32 * rail.width();
+            let marker = egui::Rect::from_center_size(egui::pos2(x, rail.top() + 1.0), egui::vec2(8.0, 8.0));
+            let response = ui
+                .interact(marker, ui.id().with(("bookmark", i)), egui::Sense::click())
+                .on_hover_text(format!("{} ({})", bookmark.name, Self::format_time(bookmark.position)));
+            let color = if response.hovered() {
+                egui::Color32::from_rgb(255, 210, 80)
+            } else {
+                egui::Color32::from_rgb(190, 155, 65)
+            };
+            ui.painter().add(egui::Shape::convex_polygon(
+                vec![
+                    egui::pos2(x - 4.0, marker.top()),
+                    egui::pos2(x + 4.0, marker.top()),
+                    egui::pos2(x, marker.bottom()),
+                ],
+                color,
+                egui::Stroke::NONE,
+            ));
+            if response.clicked() {
+                seek_target = Some(bookmark.position);
+            }
+        }
+        if let Some(position) = seek_target {
+            self.seek_to(position);
+        }
+    }
+
+    fn current_markers(&self) -> Vec<f64> {
+        self.core.audio
+            .current_file()
+            .and_then(|p| self.core.track_data.get(p))
+            .map(|d| d.markers.clone())
+            .unwrap_or_default()
+    }
+
+    fn set_current_markers(&mut self, mut markers: Vec<f64>) {
+        let Some(path) = self.core.audio.current_file().cloned() else {
+            return;
+        };
+        markers.sort_by(|a, b| a.total_cmp(b));
+        self.core.track_data.entry(path).or_default().markers = markers;
+        self.save_playlist();
+    }
+
+    fn next_marker(&mut self) {
+        let position = self.core.audio.get_position();
+        if let Some(&next) = self.current_markers().iter().find(|&&m| m > position + 0.5) {
+            self.seek_to(next);
+        }
+    }
+
+    fn prev_marker(&mut self) {
+        let position = self.core.audio.get_position();
+        let markers = self.current_markers();
+        let target = match markers.iter().rposition(|&m| m <= position) {
+            Some(i) if position - markers[i] > 3.0 || i == 0 => markers[i],
+            Some(i) => markers[i - 1],
+            None => 0.0,
+        };
+        self.seek_to(target);
+    }
+
+    fn detect_markers(&mut self) {
+        if self.marker_scan.is_some() {
+            return;
+        }
+        let Some(path) = self.core.audio.current_file().cloned() else {
+            return;
+        };
+        let name = format!("Detecting transitions in {}", Self::display_name(&path));
+        self.marker_scan = Some(self.tasks.spawn(name, move |reporter| {
+            let markers = detect_transitions(&path, |done, total| reporter.progress(done, total))?;
+            reporter.note(format!("{} transitions found", markers.len()));
+            Ok((path, markers))
+        }));
+    }
+
+    fn current_ab_points(&self) -> (Option<f64>, Option<f64>) {
+        match &self.ab_points {
+            Some((path, a, b)) if self.core.audio.current_file() == Some(path) => (*a, *b),
+            _ => (None, None),
+        }
+    }
+
+    fn export_current_clip(&mut self) {
+        let Some(source) = self.core.audio.current_file().cloned() else {
+            return;
+        };
+        let (a, b) = self.current_ab_points();
+        let (start, end) = match (a, b) {
+            (Some(a), Some(b)) if b < a => (Some(b), Some(a)),
+            points => points,
+        };
+        let Some(dest) = rfd::FileDialog::new()
+            .add_filter("WAV Audio", &["wav"])
+            .set_file_name(format!("{} (clip).wav", Self::display_name(&source)))
+            .save_file()
+        else {
+            return;
+        };
+        let dest = dest.with_extension("wav");
+        let name = format!("Exporting clip of {}", Self::display_name(&source));
+        self.tasks.spawn(name, move |reporter| {
+            let length = export_clip(&source, &dest, start, end, |done, total| reporter.progress(done, total))?;
+            reporter.note(format!("Saved {} to {}", Self::format_time(length), dest.display()));
+            Ok(())
+        });
+    }
+
+    fn loop_selection(&self) -> Option<(f64, f64)> {
+        match self.current_ab_points() {
+            (Some(a), Some(b)) if a != b => Some((a.min(b), a.max(b))),
+            _ => self.current_bookmark_loop(),
+        }
+    }
+
+    fn toggle_loop_preview(&mut self) {
+        if self.core.audio.is_previewing() {
+            self.core.audio.stop_preview();
+            return;
+        }
+        let (Some(source), Some((start, end))) = (self.core.audio.current_file().cloned(), self.loop_selection()) else {
+            return;
+        };
+        if self.loop_preview.is_some() {
+            return;
+        }
+        self.loop_preview = Some(self.tasks.spawn("Rendering loop preview", move |_| render_loop(&source, start, end)));
+    }
+
+    fn export_current_loop(&mut self) {
+        let (Some(source), Some((start, end))) = (self.core.audio.current_file().cloned(), self.loop_selection()) else {
+            return;
+        };
+        let Some(dest) = rfd::FileDialog::new()
+            .add_filter("WAV Audio", &["wav"])
+            .set_file_name(format!("{} (loop).wav", Self::display_name(&source)))
+            .save_file()
+        else {
+            return;
+        };
+        let dest = dest.with_extension("wav");
+        let name = format!("Exporting loop of {}", Self::display_name(&source));
+        self.tasks.spawn(name, move |reporter| {
+            let clip = render_loop(&source, start, end)?;
+            export_loop(&clip, &dest, |done, total| reporter.progress(done, total))?;
+            let length = clip.frames.len() as f64 / clip.sample_rate as f64;
+            reporter.note(format!("Saved {} loop to {}", Self::format_time(length), dest.display()));
+            Ok(())
+        });
+    }
+
+    fn show_markers_window(&mut self, ctx: &egui::Context) {
+        let mut markers = self.current_markers();
+        let has_track = self.core.audio.current_file().is_some();
+        let position = self.core.audio.get_position();
+        let scanning = self.marker_scan.is_some();
+        let (a, b) = self.current_ab_points();
+        let mut ab = (a, b);
+        let mut export = false;
+        let has_loop = self.loop_selection().is_some();
+        let previewing = self.core.audio.is_previewing();
+        let rendering = self.loop_preview.is_some();
+        let mut preview = false;
+        let mut export_loop = false;
+        let mut open = self.show_markers;
+        let mut changed = false;
+        let mut detect = false;
+        let mut seek_target = None;
+        let mut step = 0;
+        egui::Window::new("Markers")
+            .open(&mut open)
+            .collapsible(false)
+            .default_size([300.0, 280.0])
+            .show(ctx, |ui| {
+                if !has_track {
+                    ui.label(egui::RichText::new("No track loaded").color(egui::Color32::GRAY));
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!scanning, egui::Button::new("Detect Transitions")).clicked() {
+                        detect = true;
+                    }
+                    if ui.button(format!("Add at {}", Self::format_time(position))).clicked() {
+                        markers.push(position);
+                        changed = true;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Prev Marker").clicked() {
+                        step = -1;
+                    }
+                    if ui.button("Next Marker").clicked() {
+                        step = 1;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let point_label = |name: &str, point: Option<f64>| match point {
+                        Some(p) => format!("{} {}", name, Self::format_time(p)),
+                        None => format!("Set {}", name),
+                    };
+                    if ui.button(point_label("A", a)).clicked() {
+                        ab.0 = Some(position);
+                    }
+                    if ui.button(point_label("B", b)).clicked() {
+                        ab.1 = Some(position);
+                    }
+                    if (a.is_some() || b.is_some()) && ui.button("Clear").clicked() {
+                        ab = (None, None);
+                    }
+                    if ui.button("Export Clip...").clicked() {
+                        export = true;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let preview_label = if previewing { "Stop Preview" } else { "Preview Loop" };
+                    preview = ui
+                        .add_enabled((has_loop && !rendering) || previewing, egui::Button::new(preview_label))
+                        .on_disabled_hover_text("Set A and B or pick a bookmark loop first")
+                        .clicked();
+                    export_loop = ui
+                        .add_enabled(has_loop, egui::Button::new("Export Loop..."))
+                        .on_hover_text("Exports the selection as a seamlessly looping WAV")
+                        .clicked();
+                });
+                ui.separator();
+                let mut remove = None;
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for (i, marker) in markers.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                if ui.button(Self::format_time(*marker)).clicked() {
+                                    seek_target = Some(*marker);
+                                }
+                                if ui.small_button("x").clicked() {
+                                    remove = Some(i);
+                                }
+                            });
+                        }
+                    });
+                if let Some(i) = remove {
+                    markers.remove(i);
+                    changed = true;
+                }
+            });
+        self.show_markers = open;
+        if changed {
+            self.set_current_markers(markers);
+        }
+        if ab != (a, b)
+            && let Some(path) = self.core.audio.current_file().cloned()
+        {
+            self.ab_points = Some((path, ab.0, ab.1));
+        }
+        if export {
+            self.export_current_clip();
+        }
+        if preview {
+            self.toggle_loop_preview();
+        }
+        if export_loop {
+            self.export_current_loop();
+        }
+        if detect {
+            self.detect_markers();
+        }
+        if let Some(position) = seek_target {
+            self.seek_to(position);
+        }
+        match step {
+            1 => self.next_marker(),
+            -1 => self.prev_marker(),
+            _ => {}
+        }
+    }
+
+    fn draw_transition_markers(&mut self, ui: &mut egui::Ui, rail: egui::Rect, duration: f64) {
+        if duration <= 0.0 {
+            return;
+        }
+        let mut seek_target = None;
+        for (i, marker) in self.current_markers().into_iter().enumerate() {
+            let x = rail.left() + (marker / duration).clamp(0.0, 1.0) as f32 * rail.width();
+            let hit = egui::Rect::from_center_size(egui::pos2(x, rail.bottom() - 2.0), egui::vec2(6.0, 8.0));
+            let response = ui
+                .interact(hit, ui.id().with(("marker", i)), egui::Sense::click())
+                .on_hover_text(Self::format_time(marker));
+            let color = if response.hovered() {
+                egui::Color32::from_gray(220)
+            } else {
+                egui::Color32::from_gray(140)
+            };
+            ui.painter().vline(x, hit.y_range(), egui::Stroke::new(1.5, color));
+            if response.clicked() {
+                seek_target = Some(marker);
+            }
+        }
+        if let Some(position) = seek_target {
+            self.seek_to(position);
+        }
+    }
+
+    fn show_lyrics_window(&mut self, ctx: &egui::Context) {
+        let current = self.core.audio.current_file().cloned();
+        if self.lyrics.as_ref().map(|(p, _)| p) != current.as_ref() {
+            self.lyrics = current.map(|path| {
+                let embedded = self.metadata(&path).lyrics.clone();
+                let lyrics = Lyrics::load(&path, embedded.as_deref());
+                (path, lyrics)
+            });
+            self.lyrics_line = None;
+        }
+
+        let position = self.core.audio.get_position();
+        let mut open = self.show_lyrics;
+        let mut line_changed = false;
+        egui::Window::new("Lyrics")
+            .open(&mut open)
+            .collapsible(false)
+            .default_size([300.0, 360.0])
+            .show(ctx, |ui| {
+                let lyrics = self.lyrics.as_ref().and_then(|(_, l)| l.as_ref());
+                let Some(lyrics) = lyrics else {
+                    ui.label(egui::RichText::new("No lyrics found").color(egui::Color32::GRAY));
+                    return;
+                };
+                let current_line = lyrics.current_line(position);
+                line_changed = current_line != self.lyrics_line;
+                self.lyrics_line = current_line;
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| match lyrics {
+                        Lyrics::Synced(lines) => {
+                            for (i, line) in lines.iter().enumerate() {
+                                let text = if Some(i) == current_line {
+                                    egui::RichText::new(&line.text)
+                                        .size(15.0)
+                                        .color(egui::Color32::from_rgb(255, 210, 80))
+                                } else {
+                                    egui::RichText::new(&line.text)
+                                        .size(13.0)
+                                        .color(egui::Color32::from_gray(150))
+                                };
+                                let response = ui.label(text);
+                                if line_changed && Some(i) == current_line {
+                                    response.scroll_to_me(Some(egui::Align::Center));
+                                }
+                            }
+                        }
+                        Lyrics::Plain(text) => {
+                            ui.label(egui::RichText::new(text).size(13.0));
+                        }
+                    });
+            });
+        self.show_lyrics = open;
+    }
+
+    fn set_ui_scale(&mut self, ctx: &egui::Context, scale: f32) {
+        let scale = (scale * 20.0).round() / 20.0;
+        self.settings.ui_scale = scale.clamp(0.75, 2.0);
+        ctx.set_zoom_factor(self.settings.ui_scale);
+        self.save_settings();
+    }
+
+    fn handle_keyboard(&mut self, ctx: &egui::Context) {
+        let scale_step = ctx.input_mut(|i| {
+            if i.consume_key(egui::Modifiers::COMMAND, egui::Key::Plus)
+                || i.consume_key(egui::Modifiers::COMMAND, egui::Key::Equals)
+            {
+                Some(self.settings.ui_scale + 0.1)
+            } else if i.consume_key(egui::Modifiers::COMMAND, egui::Key::Minus) {
+                Some(self.settings.ui_scale - 0.1)
+            } else if i.consume_key(egui::Modifiers::COMMAND, egui::Key::Num0) {
+                Some(1.0)
+            } else {
+                None
+            }
+        });
+        if let Some(scale) = scale_step {
+            self.set_ui_scale(ctx, scale);
+        }
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let volume_step = ctx.input_mut(|i| {
+            if i.consume_key(egui::Modifiers::SHIFT, egui::Key::ArrowUp) {
+                0.01
+            } else if i.consume_key(egui::Modifiers::SHIFT, egui::Key::ArrowDown) {
+                -0.01
+            } else if i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp) {
+                0.05
+            } else if i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown) {
+                -0.05
+            } else {
+                0.0
+            }
+        });
+        if volume_step != 0.0 {
+            let volume = ((self.volume + volume_step) * 100.0).round() / 100.0;
+            self.volume = volume.clamp(0.0, self.settings.max_volume);
+            self.core.audio.set_volume(self.volume);
+            self.remember_device_volume();
+        }
+        if !self.standalone {
+            if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::O)) {
+                self.pick_import_folder();
+            } else if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::O)) {
+                self.pick_song();
+            }
+        }
+        if self.playlist_focused {
+            return;
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::R)) {
+            self.cycle_loop_mode();
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::S)) {
+            self.toggle_shuffle();
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Backtick)) {
+            self.play_last_played();
+        }
+        if self.standalone {
+            return;
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::SHIFT, egui::Key::N)) {
+            self.skip_next(true);
+        } else if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::N)) {
+            self.skip_next(false);
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::L)) {
+            self.play_random();
+        }
+        let rating_keys = [
+            egui::Key::Num0,
+            egui::Key::Num1,
+            egui::Key::Num2,
+            egui::Key::Num3,
+            egui::Key::Num4,
+            egui::Key::Num5,
+        ];
+        let rating = ctx.input_mut(|i| {
+            rating_keys
+                .iter()
+                .position(|&key| i.consume_key(egui::Modifiers::NONE, key))
+        });
+        if let Some(rating) = rating
+            && let Some(path) = self.core.audio.current_file().cloned()
+        {
+            self.set_rating(&path, rating as u8);
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::F))
+            && let Some(path) = self.core.audio.current_file().cloned()
+        {
+            self.toggle_favorite(&path);
+        }
+    }
+
+    fn toggle_playback(&mut self) {
+        if self.core.audio.is_playing() {
+            self.core.audio.pause();
+            self.show_osd("Paused");
+        } else {
+            self.core.audio.play();
+            self.core.hold_position();
+            self.show_osd("Playing");
+        }
+    }
+
+    fn play_last_played(&mut self) {
+        if let Some(previous) = self.core.previous_distinct()
+            && let Err(e) = self.play_track(&previous)
+        {
+            self.error_message = Some(e);
+        }
+    }
+
+    fn play_previous(&mut self) {
+        let current = self.core.audio.current_file();
+        if let Some(previous) = previous_path(&self.core.playlist, current, self.core.loop_mode, self.core.reverse)
+            && let Err(e) = self.play_track(&previous)
+        {
+            self.error_message = Some(e);
+        }
+    }
+
+    fn seek_slider(&mut self, ui: &mut egui::Ui, width: f32, duration: f64) {
+        ui.spacing_mut().slider_width = width;
+        let slider = ui.add(
+            egui::Slider::new(&mut self.core.seek_position, 0.0..=duration.max(0.001)).show_value(false),
+        );
+        if slider.drag_started() {
+            self.core.seeking = true;
+        }
+        if slider.drag_stopped() {
+            self.core.audio.seek(self.core.seek_position);
+            self.core.seeking = false;
+            self.core.hold_position();
+        }
+        if slider.changed() && !self.core.seeking {
+            self.core.audio.seek(self.core.seek_position);
+            self.core.hold_position();
+        }
+        let rail = slider.rect.shrink2(egui::vec2(8.0, 0.0));
+        if self.core.seeking {
+            self.draw_seek_preview(ui, rail, duration);
+        }
+        self.draw_bookmark_markers(ui, rail, duration);
+        self.draw_transition_markers(ui, rail, duration);
+    }
+
+    const FLASH: Duration = Duration::from_millis(250);
+
+    fn draw_transport_flash(&mut self, ui: &egui::Ui, index: usize, response: &egui::Response) {
+        if response.clicked() && !self.settings.reduced_motion {
+            self.transport_flash[index] = Some(Instant::now());
+        }
+        let Some(started) = self.transport_flash[index] else {
+            return;
+        };
+        let t = started.elapsed().as_secs_f32() / Self::FLASH.as_secs_f32();
+        if t >= 1.0 {
+            self.transport_flash[index] = None;
+            return;
+        }
+        let accent = ui.visuals().selection.stroke.color;
+        let rect = response.rect.expand(2.0 * (1.0 - t));
+        ui.painter().rect_stroke(
+            rect,
+            ui.visuals().widgets.inactive.corner_radius,
+            egui::Stroke::new(1.5, accent.gamma_multiply(1.0 - t)),
+            egui::StrokeKind::Outside,
+        );
+    }
+
+    fn eject(&mut self) {
+        self.core.audio.unload();
+        self.core.seek_position = 0.0;
+        self.advance_queue = None;
+        self.last_cue = None;
+    }
+
+    const IDLE_NOTE: &'static str = "Paused due to inactivity";
+
+    fn check_idle_pause(&mut self, ctx: &egui::Context) {
+        if ctx.input(|i| !i.events.is_empty() || i.pointer.is_moving()) {
+            self.last_interaction = Instant::now();
+        }
+        if !self.core.audio.is_playing() {
+            self.end_idle_fade();
+            return;
+        }
+        if self.error_message.as_deref() == Some(Self::IDLE_NOTE) {
+            self.error_message = None;
+        }
+        let timeout = self.settings.idle_pause_minutes * 60.0;
+        let idle = self.last_interaction.elapsed().as_secs_f32();
+        if !self.settings.idle_pause || idle < timeout {
+            let fade_secs = self.settings.idle_fade_secs.min(timeout);
+            let fade_start = timeout - fade_secs;
+            if self.settings.idle_pause && fade_secs > 0.0 && idle >= fade_start {
+                let gain = self.settings.idle_fade_curve.gain((idle - fade_start) / fade_secs);
+                self.core.audio.set_volume(self.volume * gain);
+                self.idle_fading = true;
+            } else {
+                self.end_idle_fade();
+            }
+            return;
+        }
+        self.core.audio.pause();
+        self.end_idle_fade();
+        self.last_interaction = Instant::now();
+        self.error_message = Some(Self::IDLE_NOTE.to_string());
+    }
+
+    fn end_idle_fade(&mut self) {
+        if self.idle_fading {
+            self.core.audio.set_volume(self.volume);
+            self.idle_fading = false;
+        }
+    }
+
+    fn update_taskbar_progress(&mut self, frame: &eframe::Frame) {
+        if self.taskbar.is_none() {
+            self.taskbar = TaskbarProgress::new(frame);
+        }
+        let duration = self.core.audio.get_duration();
+        let progress = match self.core.audio.playback_state() {
+            state @ (PlaybackStatus::Playing | PlaybackStatus::Paused) if duration > 0.0 => {
+                let fraction = (self.core.audio.get_position() / duration).clamp(0.0, 1.0);
+                Some(((fraction * 1000.0) as u32, state == PlaybackStatus::Paused))
+            }
+            _ => None,
+        };
+        let Some(taskbar) = &mut self.taskbar else {
+            return;
+        };
+        let (shown, updated) = self.taskbar_shown;
+        if shown == progress || (progress.is_some() && updated.elapsed() < Duration::from_millis(500)) {
+            return;
+        }
+        taskbar.set(progress.map(|(value, paused)| (value as f64 / 1000.0, paused)));
+        self.taskbar_shown = (progress, Instant::now());
+    }
+
+    fn cycle_loop_mode(&mut self) {
+        self.core.loop_mode = match (self.standalone, self.core.loop_mode) {
+            (true, LoopMode::One) => LoopMode::Off,
+            (true, _) => LoopMode::One,
+            (false, LoopMode::Off) => LoopMode::One,
+            (false, LoopMode::One) => LoopMode::Album,
+            (false, LoopMode::Album) => LoopMode::All,
+            (false, LoopMode::All) => LoopMode::Radio,
+            (false, LoopMode::Radio) => LoopMode::Off,
+        };
+    }
+
+    fn toggle_shuffle(&mut self) {
+        if self.standalone {
+            return;
+        }
+        self.core.shuffle = !self.core.shuffle;
+        self.core.sequential_run = false;
+    }
+
+    fn handle_type_ahead(&mut self, ctx: &egui::Context) {
+        if ctx.input(|i| i.pointer.any_pressed())
+            && let Some(pos) = ctx.input(|i| i.pointer.interact_pos())
+        {
+            self.playlist_focused = self.playlist_rect.contains(pos);
+        }
+        if !self.playlist_focused || self.standalone || ctx.wants_keyboard_input() {
+            return;
+        }
+        if let Some(i) = self.selected_index
+            && ctx.input_mut(|input| input.consume_key(egui::Modifiers::NONE, egui::Key::Enter))
+            && let Some(path) = self.core.playlist.get(i).cloned()
+        {
+            self.core.sequential_run = false;
+            match self.select_track(&path) {
+                Ok(_) => self.error_message = None,
+                Err(e) => self.error_message = Some(e),
+            }
+        }
+        let typed: String = ctx.input(|i| {
+            i.events
+                .iter()
+                .filter_map(|e| match e {
+                    egui::Event::Text(text) => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect()
+        });
+        if typed.is_empty() {
+            return;
+        }
+        let (buffer, last_key) = &mut self.type_ahead;
+        if last_key.elapsed() > Duration::from_millis(1000) {
+            buffer.clear();
+        }
+        buffer.push_str(&typed.to_lowercase());
+        *last_key = Instant::now();
+        let prefix = buffer.clone();
+        if let Some(i) = self
+            .visible_indices()
+            .into_iter()
+            .find(|&i| Self::display_name(&self.core.playlist[i]).to_lowercase().starts_with(&prefix))
+        {
+            self.selected_index = Some(i);
+            self.scroll_to_selection = true;
+        }
+    }
+
+    fn marquee_label(
+        ui: &mut egui::Ui,
+        text: &str,
+        font: egui::FontId,
+        color: egui::Color32,
+        max_width: f32,
+    ) -> egui::Response {
+        let galley = ui.painter().layout_no_wrap(text.to_string(), font, color);
+        let overflow = galley.size().x - max_width;
+        if overflow <= 0.0 {
+            return ui.add(egui::Label::new(egui::WidgetText::from(galley)).sense(egui::Sense::click()));
+        }
+        let pause = 1.5;
+        let scroll = overflow as f64 / 30.0;
+        let t = ui.input(|i| i.time) % (2.0 * (pause + scroll));
+        let progress = if t < pause {
+            0.0
+        } else if t < pause + scroll {
+            (t - pause) / scroll
+        } else if t < 2.0 * pause + scroll {
+            1.0
+        } else {
+            1.0 - (t - 2.0 * pause - scroll) / scroll
+        };
+        let (rect, response) = ui.allocate_exact_size(egui::vec2(max_width, galley.size().y), egui::Sense::click());
+        let pos = egui::pos2(rect.left() - overflow * progress as f32, rect.top());
+        ui.painter().with_clip_rect(rect).galley(pos, galley, color);
+        response
+    }
+
+    fn reveal_current_track(&mut self) {
+        let Some(current) = self.core.audio.current_file().cloned() else {
+            return;
+        };
+        let Some(index) = self.core.playlist.iter().position(|p| *p == current) else {
+            return;
+        };
+        if !self.visible_indices().contains(&index) {
+            self.view = PlaylistView::Library;
+        }
+        self.selected_index = Some(index);
+        self.scroll_to_selection = true;
+        self.playlist_focused = true;
+    }
+
+    fn draw_mode_indicators(&self, ui: &mut egui::Ui, area: egui::Rect) {
+        let accent = egui::Color32::from_rgb(220, 175, 55);
+        let dim = egui::Color32::from_gray(60);
+        let loop_glyph = if self.core.loop_mode == LoopMode::One { "🔂" } else { "🔁" };
+        let indicators = [
+            (loop_glyph, self.core.loop_mode != LoopMode::Off),
+            ("🔀", self.core.shuffle),
+        ];
+        let mut x = area.right() - 28.0;
+        for (glyph, active) in indicators.into_iter().rev() {
+            let rect = ui.painter().text(
+                egui::pos2(x, area.bottom() - 4.0),
+                egui::Align2::RIGHT_BOTTOM,
+                glyph,
+                egui::FontId::new(12.0, egui::FontFamily::Proportional),
+                if active { accent } else { dim },
+            );
+            x = rect.left() - 4.0;
+        }
+    }
+
+    fn draw_vu_meters(&mut self, ui: &mut egui::Ui, area: egui::Rect) {
+        let (left, right) = self.core.audio.levels();
+        let decay = ui.input(|i| i.stable_dt) * 1.5;
+        self.vu_levels.0 = left.max(self.vu_levels.0 - decay);
+        self.vu_levels.1 = right.max(self.vu_levels.1 - decay);
+
+        if self.core.audio.limiter_reduction() > 0.5 {
+            self.limiter_flash = 1.0;
+        } else {
+            self.limiter_flash = (self.limiter_flash - decay).max(0.0);
+        }
+        if self.settings.limiter && self.limiter_flash > 0.0 {
+            ui.painter().text(
+                egui::pos2(area.right() - 28.0, area.top() + 6.0),
+                egui::Align2::RIGHT_TOP,
+                "LIM",
+                egui::FontId::new(9.0, egui::FontFamily::Monospace),
+                egui::Color32::from_rgb(230, 90, 40).gamma_multiply(self.limiter_flash),
+            );
+        }
+
+        let bar_width = 6.0;
+        let height = (area.height() - 12.0).max(0.0);
+        let bottom = area.top() + 6.0 + height;
+        for (k, level) in [self.vu_levels.0, self.vu_levels.1].into_iter().enumerate() {
+            let x = area.right() - 22.0 + k as f32 * (bar_width + 3.0);
+            let track = egui::Rect::from_min_max(
+                egui::pos2(x, bottom - height),
+                egui::pos2(x + bar_width, bottom),
+            );
+            ui.painter().rect_filled(track, 2.0, egui::Color32::from_gray(35));
+            let db = if level > 0.0 { 20.0 * level.log10() } else { -80.0 };
+            let fill = ((db + 48.0) / 48.0).clamp(0.0, 1.0);
+            if fill > 0.0 {
+                let color = if db > -3.0 {
+                    egui::Color32::from_rgb(230, 90, 40)
+                } else {
+                    egui::Color32::from_rgb(220, 175, 55)
+                };
+                let bar = egui::Rect::from_min_max(
+                    egui::pos2(x, bottom - height * fill),
+                    egui::pos2(x + bar_width, bottom),
+                );
+                ui.painter().rect_filled(bar, 2.0, color);
+            }
+        }
+    }
+
+    fn render_now_playing(template: &str, tags: &TrackMetadata, path: &Path) -> String {
+        let name = Self::display_name(path);
+        template
+            .replace("{artist}", tags.artist.as_deref().unwrap_or("Unknown Artist"))
+            .replace("{title}", tags.title.as_deref().unwrap_or(&name))
+            .replace("{album}", tags.album.as_deref().unwrap_or(""))
+            .replace("{file}", &name)
+    }
+
+    fn update_now_playing_export(&mut self) {
+        if !self.settings.now_playing_export {
+            return;
+        }
+        let Some(target) = self.settings.now_playing_path.clone() else {
+            return;
+        };
+        let text = match self.core.audio.current_file().cloned() {
+            Some(path) if self.core.audio.is_playing() => {
+                let template = self.settings.now_playing_template.clone();
+                let tags = self.current_tags().cloned().unwrap_or_default();
+                Self::render_now_playing(&template, &tags, &path)
+            }
+            _ => self.settings.now_playing_paused_text.clone(),
+        };
+        if self.now_playing_text.as_ref() != Some(&text) {
+            if let Err(e) = atomic_write(&target, &text) {
+                self.error_message = Some(format!("Failed to write now playing file: {}", e));
+            }
+            self.now_playing_text = Some(text);
+        }
+    }
+
+    fn update_window_title(&mut self, ctx: &egui::Context) {
+        let name = match self.core.audio.current_file().cloned() {
+            Some(path) if self.core.audio.is_playing() => Some(match self.current_tags() {
+                Some(TrackMetadata { artist: Some(artist), title: Some(title), .. }) => {
+                    format!("{} - {}", artist, title)
+                }
+                _ => Self::display_name(&path),
+            }),
+            _ => None,
+        };
+        let position = Self::format_time(self.core.audio.get_position());
+        let (last_name, last_position, last_sent) = &self.window_title;
+        let position_due = name.is_some()
+            && position != *last_position
+            && last_sent.elapsed() >= Duration::from_secs(1);
+        if name == *last_name && !position_due {
+            return;
+        }
+        let title = match &name {
+            Some(name) => format!("Kiraboshi — {} [{}]", name, position),
+            None => "Kiraboshi".to_string(),
+        };
+        ctx.send_viewport_cmd(egui::ViewportCommand::Title(title));
+        self.window_title = (name, position, Instant::now());
+    }
+
+    fn sync_status_server(&mut self) {
+        let port = self.status_port_override.or(
+            self.settings.status_server.then_some(self.settings.status_port),
+        );
+        if let Some(server) = &self.status_server
+            && Some(server.port()) == port
+        {
+            server.set_allow_cors(self.settings.status_cors);
+            return;
+        }
+        self.status_server = None;
+        if let Some(port) = port {
+            match StatusServer::start(port, self.settings.status_cors) {
+                Ok(server) => self.status_server = Some(server),
+                Err(e) => self.error_message = Some(e),
+            }
+        }
+    }
+
+    fn update_status_snapshot(&mut self) {
+        if self.status_server.is_none() {
+            return;
+        }
+        let path = self.core.audio.current_file().cloned();
+        let tags = self.current_tags().cloned().unwrap_or_default();
+        let snapshot = StatusSnapshot {
+            track: path.as_deref().map(Self::display_name),
+            artist: tags.artist,
+            title: tags.title,
+            path: path.as_ref().filter(|_| self.settings.status_paths).map(|p| p.display().to_string()),
+            playing: self.core.audio.is_playing(),
+            state: self.core.audio.playback_state().as_str().to_string(),
+            position: self.core.audio.get_position(),
+            duration: self.core.audio.get_duration(),
+            volume: self.volume,
+            loop_mode: self.core.loop_mode.as_str().to_string(),
+            shuffle: self.core.shuffle,
+            reverse: self.core.reverse,
+        };
+        if let Some(server) = &self.status_server {
+            server.update(snapshot);
+        }
+    }
+
+    const SCROBBLE_MIN_DURATION: f64 = 30.0;
+    const SCROBBLE_MAX_WAIT: f64 = 240.0;
+
+    fn sync_scrobbler(&mut self) {
+        let credentials = self.settings.lastfm_session_key.clone()
+            .filter(|_| self.settings.scrobble)
+            .map(|session_key| LastFmCredentials {
+                api_key: self.settings.lastfm_api_key.clone(),
+                api_secret: self.settings.lastfm_api_secret.clone(),
+                session_key,
+            });
+        if self.scrobbler.as_ref().map(|s| s.credentials()) == credentials.as_ref() {
+            return;
+        }
+        let queue_file = self.check_writable().ok().map(|_| Self::scrobble_queue_file());
+        let previous = self.scrobbler.take();
+        self.scrobbler = credentials.map(|c| Scrobbler::start(c, queue_file, previous));
+        self.scrobble_serial = 0;
+        self.scrobble_play = None;
+    }
+
+    fn connect_lastfm(&mut self) {
+        let api_key = self.settings.lastfm_api_key.trim().to_string();
+        let api_secret = self.settings.lastfm_api_secret.trim().to_string();
+        let username = self.settings.lastfm_username.trim().to_string();
+        let password = std::mem::take(&mut self.lastfm_password);
+        self.lastfm_auth = Some(self.tasks.spawn("Connecting to Last.fm", move |_| {
+            authenticate(&api_key, &api_secret, &username, &password)
+        }));
+    }
+
+    fn current_scrobble(&mut self) -> Option<Scrobble> {
+        let duration = self.core.audio.get_duration();
+        if duration < Self::SCROBBLE_MIN_DURATION {
+            return None;
+        }
+        let tags = self.current_tags()?.clone();
+        Some(Scrobble {
+            artist: tags.artist?,
+            title: tags.title?,
+            album: tags.album,
+            duration: duration as u32,
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        })
+    }
+
+    fn update_scrobbling(&mut self) {
+        let error = self.scrobbler.as_ref().and_then(|s| s.take_error());
+        if self.scrobbler.as_ref().is_some_and(|s| s.auth_failed()) {
+            self.settings.lastfm_session_key = None;
+            self.save_settings();
+            self.sync_scrobbler();
+            self.error_message = Some(format!(
+                "{} Connect to Last.fm again in Settings to resume scrobbling.",
+                error.unwrap_or_else(|| "Last.fm rejected the saved session.".to_string()),
+            ));
+            return;
+        }
+        if let Some(e) = error {
+            self.error_message = Some(e);
+        }
+        if self.scrobbler.is_none() {
+            return;
+        }
+        let serial = self.core.audio.track_serial();
+        if serial != self.scrobble_serial {
+            self.scrobble_serial = serial;
+            self.scrobble_play = self.current_scrobble().map(|track| (track, 0.0, false));
+        }
+        let elapsed = self.scrobble_tick.elapsed().as_secs_f64().min(1.0);
+        self.scrobble_tick = Instant::now();
+        let playing = self.core.audio.is_playing();
+        let (Some(scrobbler), Some((track, listened, announced))) = (&self.scrobbler, &mut self.scrobble_play) else {
+            return;
+        };
+        if !playing {
+            return;
+        }
+        if !*announced {
+            scrobbler.now_playing(track.clone());
+            *announced = true;
+        }
+        *listened += elapsed;
+        if *listened >= (track.duration as f64 / 2.0).min(Self::SCROBBLE_MAX_WAIT) {
+            scrobbler.scrobble(track.clone());
+            self.scrobble_play = None;
+        }
+    }
+
+    fn show_settings_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_settings;
+        let mut changed = false;
+        let mut new_data_dir = None;
+        let mut connect_lastfm = false;
+        let mut scan_metadata = false;
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                ui.label(
+                    egui::RichText::new("Now Playing Export")
+                        .size(13.0)
+                        .color(egui::Color32::from_rgb(190, 155, 65)),
+                );
+                changed |= ui
+                    .checkbox(&mut self.settings.now_playing_export, "Write now playing to a file")
+                    .changed();
+                ui.horizontal(|ui| {
+                    let label = self.settings.now_playing_path
+                        .as_ref()
+                        .map(|p| p.display().to_string())
+                        .unwrap_or_else(|| "No file chosen".to_string());
+                    if ui.button("Choose File").clicked()
+                        && let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Text Files", &["txt"])
+                            .set_file_name("now_playing.txt")
+                            .save_file()
+                    {
+                        self.settings.now_playing_path = Some(path);
+                        changed = true;
+                    }
+                    ui.label(egui::RichText::new(label).size(12.0));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Template");
+                    changed |= ui
+                        .text_edit_singleline(&mut self.settings.now_playing_template)
+                        .changed();
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Paused text");
+                    changed |= ui
+                        .text_edit_singleline(&mut self.settings.now_playing_paused_text)
+                        .changed();
+                });
+                ui.label(
+                    egui::RichText::new("Placeholders: {artist} {title} {album} {file}")
+                        .size(11.0)
+                        .color(egui::Color32::GRAY),
+                );
+
+                ui.add_space(8.0);
+                ui.label(
+                    egui::RichText::new("Volume")
+                        .size(13.0)
+                        .color(egui::Color32::from_rgb(190, 155, 65)),
+                );
+                egui::Grid::new("volume_settings_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Default volume");
+                        changed |= ui
+                            .add(
+                                egui::Slider::new(&mut self.settings.default_volume, 0.0..=self.settings.max_volume)
+                                    .step_by(0.01)
+                                    .custom_formatter(|v, _| format!("{}%", (v * 100.0).round() as i32)),
+                            )
+                            .changed();
+                        ui.end_row();
+                        ui.label("Maximum volume");
+                        if ui
+                            .add(
+                                egui::Slider::new(&mut self.settings.max_volume, 0.1..=2.0)
+                                    .step_by(0.05)
+                                    .custom_formatter(|v, _| format!("{}%", (v * 100.0).round() as i32)),
+                            )
+                            .changed()
+                        {
+                            self.settings.clamp_volumes();
+                            if self.volume > self.settings.max_volume {
+                                self.volume = self.settings.max_volume;
+                                self.core.audio.set_volume(self.volume);
+                            }
+                            changed = true;
+                        }
+                        ui.end_row();
+                    });
+                if ui.checkbox(&mut self.settings.seek_fade, "Fade briefly when seeking").changed() {
+                    self.core.audio.set_seek_fade(self.settings.seek_fade);
+                    changed = true;
+                }
+                changed |= ui
+                    .checkbox(&mut self.settings.fade_in_on_launch, "Fade in the first track on launch")
+                    .changed();
+                changed |= ui
+                    .checkbox(&mut self.settings.advance_on_remove, "Keep playing when the current track is removed")
+                    .changed();
+                let mut silence_changed = ui
+                    .checkbox(&mut self.settings.skip_silence, "Skip silence at the start and end of tracks")
+                    .changed();
+                ui.add_enabled_ui(self.settings.skip_silence, |ui| {
+                    egui::Grid::new("silence_settings_grid")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("Silence below");
+                            silence_changed |= ui
+                                .add(
+                                    egui::Slider::new(&mut self.settings.silence_threshold_db, -80.0..=-20.0)
+                                        .step_by(1.0)
+                                        .suffix(" dB"),
+                                )
+                                .changed();
+                            ui.end_row();
+                            ui.label("Minimum length");
+                            silence_changed |= ui
+                                .add(
+                                    egui::Slider::new(&mut self.settings.silence_min_secs, 0.1..=5.0)
+                                        .step_by(0.1)
+                                        .suffix(" s"),
+                                )
+                                .changed();
+                            ui.end_row();
+                        });
+                });
+                if silence_changed {
+                    self.apply_silence_settings();
+                    changed = true;
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Advance within");
+                    if ui
+                        .add(
+                            egui::Slider::new(&mut self.settings.end_tolerance_secs, 0.0..=1.0)
+                                .step_by(0.05)
+                                .suffix(" s of the end"),
+                        )
+                        .changed()
+                    {
+                        self.core.end_tolerance = self.settings.end_tolerance_secs as f64;
+                        changed = true;
+                    }
+                });
+                changed |= ui
+                    .checkbox(&mut self.settings.crossfade_on_select, "Crossfade when selecting a track")
+                    .changed();
+                changed |= ui
+                    .checkbox(&mut self.settings.crossfade_on_advance, "Crossfade into the next track")
+                    .changed();
+                changed |= ui
+                    .checkbox(&mut self.settings.crossfade_on_next, "Crossfade when skipping to the next track")
+                    .on_hover_text("Press N to skip with a crossfade, or Shift+N to skip instantly")
+                    .changed();
+                ui.add_enabled_ui(self.settings.crossfade_on_advance, |ui| {
+                    changed |= ui
+                        .checkbox(&mut self.settings.gapless_albums, "Play tracks from the same album without crossfade")
+                        .changed();
+                });
+                let crossfade = self.settings.crossfade_on_select
+                    || self.settings.crossfade_on_advance
+                    || self.settings.crossfade_on_next;
+                ui.add_enabled_ui(crossfade, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Crossfade length");
+                        changed |= ui
+                            .add(
+                                egui::Slider::new(&mut self.settings.crossfade_secs, 0.5..=10.0)
+                                    .step_by(0.5)
+                                    .suffix(" s"),
+                            )
+                            .changed();
+                    });
+                });
+                changed |= ui
+                    .checkbox(&mut self.settings.idle_pause, "Pause after a period of inactivity")
+                    .changed();
+                ui.add_enabled_ui(self.settings.idle_pause, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Idle for");
+                        changed |= ui
+                            .add(
+                                egui::Slider::new(&mut self.settings.idle_pause_minutes, 1.0..=240.0)
+                                    .step_by(1.0)
+                                    .suffix(" min"),
+                            )
+                            .changed();
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Fade out over");
+                        changed |= ui
+                            .add(
+                                egui::Slider::new(&mut self.settings.idle_fade_secs, 0.0..=300.0)
+                                    .step_by(5.0)
+                                    .suffix(" s"),
+                            )
+                            .changed();
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Fade curve");
+                        egui::ComboBox::from_id_salt("idle_fade_curve")
+                            .selected_text(self.settings.idle_fade_curve.label())
+                            .show_ui(ui, |ui| {
+                                for curve in FadeCurve::ALL {
+                                    changed |= ui
+                                        .selectable_value(&mut self.settings.idle_fade_curve, curve, curve.label())
+                                        .changed();
+                                }
+                            });
+                    });
+                });
+
+                ui.add_space(8.0);
+                ui.label(
+                    egui::RichText::new("Output")
+                        .size(13.0)
+                        .color(egui::Color32::from_rgb(190, 155, 65)),
+                );
+                let mut limiter_changed = ui
+                    .checkbox(&mut self.settings.limiter, "Limit output peaks to prevent clipping")
+                    .changed();
+                ui.add_enabled_ui(self.settings.limiter, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Ceiling");
+                        limiter_changed |= ui
+                            .add(
+                                egui::Slider::new(&mut self.settings.limiter_threshold_db, -12.0..=0.0)
+                                    .step_by(0.5)
+                                    .suffix(" dB"),
+                            )
+                            .changed();
+                    });
+                    limiter_changed |= ui
+                        .checkbox(&mut self.settings.compressor, "Soft compression below the ceiling")
+                        .changed();
+                });
+                if limiter_changed {
+                    self.apply_limiter_settings();
+                    changed = true;
+                }
+                let mut crossfeed_changed = ui
+                    .checkbox(&mut self.settings.crossfeed, "Headphone crossfeed")
+                    .on_hover_text("Blends a little of each channel into the other to soften hard-panned stereo")
+                    .changed();
+                ui.add_enabled_ui(self.settings.crossfeed, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Cutoff");
+                        crossfeed_changed |= ui
+                            .add(
+                                egui::Slider::new(&mut self.settings.crossfeed_cutoff_hz, 300.0..=1000.0)
+                                    .step_by(10.0)
+                                    .suffix(" Hz"),
+                            )
+                            .changed();
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Separation");
+                        crossfeed_changed |= ui
+                            .add(
+                                egui::Slider::new(&mut self.settings.crossfeed_level_db, 1.0..=15.0)
+                                    .step_by(0.5)
+                                    .suffix(" dB"),
+                            )
+                            .on_hover_text("Lower values feed more of the opposite channel")
+                            .changed();
+                    });
+                });
+                if crossfeed_changed {
+                    self.apply_crossfeed_settings();
+                    changed = true;
+                }
+                let rate_label = |rate: Option<u32>| match rate {
+                    Some(rate) => format!("{} Hz", rate),
+                    None => "Device default".to_string(),
+                };
+                ui.horizontal(|ui| {
+                    ui.label("Sample rate");
+                    let mut rate_changed = false;
+                    egui::ComboBox::from_id_salt("output_sample_rate")
+                        .selected_text(rate_label(self.settings.output_sample_rate))
+                        .show_ui(ui, |ui| {
+                            for rate in [None, Some(44100), Some(48000), Some(88200), Some(96000)] {
+                                rate_changed |= ui
+                                    .selectable_value(&mut self.settings.output_sample_rate, rate, rate_label(rate))
+                                    .changed();
+                            }
+                        });
+                    if rate_changed {
+                        self.apply_output_settings();
+                        changed = true;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Latency");
+                    let mut latency_changed = false;
+                    egui::ComboBox::from_id_salt("output_latency")
+                        .selected_text(self.settings.output_latency.label())
+                        .show_ui(ui, |ui| {
+                            for latency in OutputLatency::ALL {
+                                latency_changed |= ui
+                                    .selectable_value(&mut self.settings.output_latency, latency, latency.label())
+                                    .changed();
+                            }
+                        });
+                    if latency_changed {
+                        self.apply_output_settings();
+                        changed = true;
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Higher latency uses a larger output buffer, which prevents crackling on slow or busy \
+                     machines but makes seeking and pausing respond more slowly.",
+                );
+                if ui.button("Equalizer...").clicked() {
+                    self.show_equalizer = true;
+                }
+
+                ui.add_space(8.0);
+                ui.label(
+                    egui::RichText::new("Interface")
+                        .size(13.0)
+                        .color(egui::Color32::from_rgb(190, 155, 65)),
+                );
+                let mut ui_scale = self.settings.ui_scale;
+                ui.horizontal(|ui| {
+                    ui.label("UI scale");
+                    let slider = ui.add(
+                        egui::Slider::new(&mut ui_scale, 0.75..=2.0)
+                            .step_by(0.05)
+                            .custom_formatter(|v, _| format!("{}%", (v * 100.0).round() as i32)),
+                    );
+                    if slider.drag_stopped() || (slider.changed() && !slider.dragged()) {
+                        self.set_ui_scale(ctx, ui_scale);
+                    } else if slider.changed() {
+                        self.settings.ui_scale = ui_scale;
+                    }
+                });
+                ui.label(
+                    egui::RichText::new("Ctrl + Plus / Minus to zoom, Ctrl + 0 to reset")
+                        .size(11.0)
+                        .color(egui::Color32::GRAY),
+                );
+                changed |= ui
+                    .checkbox(&mut self.settings.artwork_accent, "Tint the interface with the album art color")
+                    .changed();
+                changed |= ui
+                    .checkbox(&mut self.settings.reduced_motion, "Reduce motion")
+                    .changed();
+                changed |= ui
+                    .checkbox(&mut self.settings.osd, "Show an overlay when the track changes")
+                    .changed();
+                ui.add_enabled_ui(self.settings.osd, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Overlay position");
+                        egui::ComboBox::from_id_salt("osd_position")
+                            .selected_text(self.settings.osd_position.label())
+                            .show_ui(ui, |ui| {
+                                for position in OsdPosition::ALL {
+                                    changed |= ui
+                                        .selectable_value(&mut self.settings.osd_position, position, position.label())
+                                        .changed();
+                                }
+                            });
+                    });
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Minimum window size");
+                    let width = ui.add(
+                        egui::DragValue::new(&mut self.settings.min_window_width)
+                            .range(600.0..=3840.0)
+                            .suffix(" w"),
+                    );
+                    let height = ui.add(
+                        egui::DragValue::new(&mut self.settings.min_window_height)
+                            .range(400.0..=2160.0)
+                            .suffix(" h"),
+                    );
+                    if (width.changed() || height.changed()) && !self.standalone {
+                        ctx.send_viewport_cmd(egui::ViewportCommand::MinInnerSize(min_window_size(
+                            false,
+                            &self.settings,
+                        )));
+                        changed = true;
+                    }
+                });
+                if !self.standalone {
+                    if ui
+                        .checkbox(&mut self.settings.mini_player, "Mini player")
+                        .on_hover_text("Shrink the window to a single row of controls")
+                        .changed()
+                    {
+                        self.set_mini_player(ctx, self.settings.mini_player);
+                    }
+                    changed |= ui
+                        .checkbox(&mut self.settings.mini_player_on_top, "Keep the mini player above other windows")
+                        .changed();
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Drag window by");
+                    egui::ComboBox::from_id_salt("title_drag")
+                        .selected_text(self.settings.title_drag.label())
+                        .show_ui(ui, |ui| {
+                            for drag in TitleDrag::ALL {
+                                changed |= ui
+                                    .selectable_value(&mut self.settings.title_drag, drag, drag.label())
+                                    .changed();
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Double-click title bar to");
+                    egui::ComboBox::from_id_salt("title_double_click")
+                        .selected_text(self.settings.title_double_click.label())
+                        .show_ui(ui, |ui| {
+                            for action in TitleDoubleClick::ALL {
+                                changed |= ui
+                                    .selectable_value(&mut self.settings.title_double_click, action, action.label())
+                                    .changed();
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Opening a single file");
+                    egui::ComboBox::from_id_salt("file_open_mode")
+                        .selected_text(self.settings.file_open_mode.label())
+                        .show_ui(ui, |ui| {
+                            for mode in FileOpenMode::ALL {
+                                changed |= ui
+                                    .selectable_value(&mut self.settings.file_open_mode, mode, mode.label())
+                                    .changed();
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.label("When a single file ends");
+                    egui::ComboBox::from_id_salt("standalone_end")
+                        .selected_text(self.settings.standalone_end.label())
+                        .show_ui(ui, |ui| {
+                            for end in StandaloneEnd::ALL {
+                                changed |= ui
+                                    .selectable_value(&mut self.settings.standalone_end, end, end.label())
+                                    .changed();
+                            }
+                        });
+                });
+
+                ui.add_space(8.0);
+                ui.label(
+                    egui::RichText::new("Playlist")
+                        .size(13.0)
+                        .color(egui::Color32::from_rgb(190, 155, 65)),
+                );
+                egui::Grid::new("playlist_settings_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Row density");
+                        egui::ComboBox::from_id_salt("row_density")
+                            .selected_text(self.settings.row_density.label())
+                            .show_ui(ui, |ui| {
+                                for density in RowDensity::ALL {
+                                    changed |= ui
+                                        .selectable_value(&mut self.settings.row_density, density, density.label())
+                                        .changed();
+                                }
+                            });
+                        ui.end_row();
+                        ui.label("Shuffle");
+                        egui::ComboBox::from_id_salt("shuffle_mode")
+                            .selected_text(self.settings.shuffle_mode.label())
+                            .show_ui(ui, |ui| {
+                                for mode in ShuffleMode::ALL {
+                                    if ui
+                                        .selectable_value(&mut self.settings.shuffle_mode, mode, mode.label())
+                                        .changed()
+                                    {
+                                        self.core.shuffle_mode = mode;
+                                        changed = true;
+                                    }
+                                }
+                            });
+                        ui.end_row();
+                        ui.label("Font size");
+                        changed |= ui
+                            .add(
+                                egui::Slider::new(&mut self.settings.playlist_font_scale, 0.8..=1.5)
+                                    .step_by(0.05)
+                                    .custom_formatter(|v, _| format!("{}%", (v * 100.0).round() as i32)),
+                            )
+                            .changed();
+                        ui.end_row();
+                    });
+                ui.horizontal(|ui| {
+                    changed |= ui
+                        .checkbox(&mut self.settings.prescan_metadata, "Scan library metadata on startup")
+                        .changed();
+                    if ui
+                        .add_enabled(self.metadata_prescan.is_none(), egui::Button::new("Scan Now"))
+                        .clicked()
+                    {
+                        scan_metadata = true;
+                    }
+                });
+                if ui
+                    .checkbox(&mut self.settings.row_waveforms, "Show waveform thumbnails in rows")
+                    .on_hover_text("Waveforms are generated by the library metadata scan")
+                    .changed()
+                {
+                    scan_metadata |= self.settings.row_waveforms;
+                    changed = true;
+                }
+                if ui
+                    .checkbox(&mut self.settings.row_bpm, "Show BPM and key in rows")
+                    .on_hover_text("Tags are read by the library metadata scan")
+                    .changed()
+                {
+                    scan_metadata |= self.settings.row_bpm;
+                    changed = true;
+                }
+                changed |= ui
+                    .checkbox(&mut self.settings.row_numbers, "Show track numbers in rows")
+                    .on_hover_text("Numbers follow the order of the current view")
+                    .changed();
+                if ui
+                    .checkbox(&mut self.settings.estimate_bpm, "Estimate BPM for untagged tracks")
+                    .on_hover_text("Tempo is estimated by the library metadata scan")
+                    .changed()
+                {
+                    scan_metadata |= self.settings.estimate_bpm;
+                    changed = true;
+                }
+                changed |= ui
+                    .checkbox(&mut self.settings.restore_session, "Offer to restore the last session on launch")
+                    .changed();
+
+                ui.add_space(8.0);
+                ui.label(
+                    egui::RichText::new("Status Endpoint")
+                        .size(13.0)
+                        .color(egui::Color32::from_rgb(190, 155, 65)),
+                );
+                changed |= ui
+                    .checkbox(&mut self.settings.status_server, "Serve playback status as JSON on localhost")
+                    .changed();
+                ui.horizontal(|ui| {
+                    ui.label("Port");
+                    let response = ui.add(egui::DragValue::new(&mut self.settings.status_port).range(1024..=65535));
+                    changed |= response.drag_stopped() || response.lost_focus();
+                });
+                changed |= ui
+                    .checkbox(&mut self.settings.status_paths, "Include file paths")
+                    .changed();
+                changed |= ui
+                    .checkbox(&mut self.settings.status_cors, "Allow web pages to read the status")
+                    .on_hover_text("Sends Access-Control-Allow-Origin: *, so any site open in a browser can see what is playing")
+                    .changed();
+                if let Some(server) = &self.status_server {
+                    ui.label(
+                        egui::RichText::new(format!("Serving at http://127.0.0.1:{}/", server.port()))
+                            .size(11.0)
+                            .color(egui::Color32::GRAY),
+                    );
+                }
+
+                ui.add_space(8.0);
+                ui.label(
+                    egui::RichText::new("Last.fm")
+                        .size(13.0)
+                        .color(egui::Color32::from_rgb(190, 155, 65)),
+                );
+                changed |= ui
+                    .checkbox(&mut self.settings.scrobble, "Scrobble played tracks to Last.fm")
+                    .changed();
+                let connected = self.settings.lastfm_session_key.is_some();
+                egui::Grid::new("lastfm_settings_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("API key");
+                        changed |= ui
+                            .add_enabled(!connected, egui::TextEdit::singleline(&mut self.settings.lastfm_api_key))
+                            .changed();
+                        ui.end_row();
+                        ui.label("API secret");
+                        changed |= ui
+                            .add_enabled(
+                                !connected,
+                                egui::TextEdit::singleline(&mut self.settings.lastfm_api_secret).password(true),
+                            )
+                            .changed();
+                        ui.end_row();
+                        ui.label("Username");
+                        changed |= ui
+                            .add_enabled(!connected, egui::TextEdit::singleline(&mut self.settings.lastfm_username))
+                            .changed();
+                        ui.end_row();
+                        if !connected {
+                            ui.label("Password");
+                            ui.add(egui::TextEdit::singleline(&mut self.lastfm_password).password(true));
+                            ui.end_row();
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    if connected {
+                        if ui.button("Disconnect").clicked() {
+                            self.settings.lastfm_session_key = None;
+                            changed = true;
+                        }
+                        ui.label(
+                            egui::RichText::new(format!("Connected as {}", self.settings.lastfm_username))
+                                .size(12.0),
+                        );
+                    } else {
+                        let ready = [
+                            &self.settings.lastfm_api_key,
+                            &self.settings.lastfm_api_secret,
+                            &self.settings.lastfm_username,
+                            &self.lastfm_password,
+                        ]
+                        .iter()
+                        .all(|field| !field.trim().is_empty());
+                        connect_lastfm = ui.add_enabled(ready, egui::Button::new("Connect")).clicked();
+                    }
+                });
+                if let Some(scrobbler) = &self.scrobbler {
+                    let queued = scrobbler.queued();
+                    let status = match (scrobbler.is_offline(), queued) {
+                        (true, _) => Some(format!("Last.fm unreachable, {} scrobbles queued", queued)),
+                        (false, 0) => None,
+                        (false, _) => Some(format!("{} scrobbles queued", queued)),
+                    };
+                    if let Some(status) = status {
+                        ui.label(egui::RichText::new(status).size(11.0).color(egui::Color32::GRAY));
+                    }
+                }
+
+                ui.add_space(8.0);
+                ui.label(
+                    egui::RichText::new("Data Folder")
+                        .size(13.0)
+                        .color(egui::Color32::from_rgb(190, 155, 65)),
+                );
+                ui.horizontal(|ui| {
+                    if ui.button("Change...").clicked()
+                        && let Some(folder) = rfd::FileDialog::new().pick_folder()
+                    {
+                        new_data_dir = Some(folder);
+                    }
+                    ui.label(egui::RichText::new(Self::data_dir().display().to_string()).size(12.0));
+                });
+
+                ui.add_space(8.0);
+                ui.label(
+                    egui::RichText::new("Backup")
+                        .size(13.0)
+                        .color(egui::Color32::from_rgb(190, 155, 65)),
+                );
+                ui.checkbox(&mut self.backup_include_media, "Include audio files");
+                ui.horizontal(|ui| {
+                    if ui.button("Export Backup...").clicked()
+                        && let Some(dest) = rfd::FileDialog::new()
+                            .add_filter("Kiraboshi Backup", &["json"])
+                            .set_file_name("kiraboshi-backup.json")
+                            .save_file()
+                    {
+                        self.export_backup(dest);
+                    }
+                    if !self.read_only
+                        && ui.button("Import Backup...").clicked()
+                        && let Some(source) = rfd::FileDialog::new()
+                            .add_filter("Kiraboshi Backup", &["json"])
+                            .pick_file()
+                    {
+                        self.backup_read = Some(self.tasks.spawn("Reading backup", move |_| {
+                            Backup::read(&source).map(|backup| (source, backup))
+                        }));
+                    }
+                });
+            });
+        self.show_settings = open;
+        if changed {
+            self.now_playing_text = None;
+            self.save_settings();
+            self.sync_status_server();
+            self.sync_scrobbler();
+        }
+        if connect_lastfm {
+            self.connect_lastfm();
+        }
+        if scan_metadata {
+            self.prescan_metadata();
+        }
+        if let Some(folder) = new_data_dir {
+            self.change_data_dir(&folder);
+        }
+    }
+
+    fn change_data_dir(&mut self, folder: &Path) {
+        if !is_writable(folder) {
+            self.error_message = Some(format!("Kiraboshi cannot write to {}", folder.display()));
+            return;
+        }
+        self.save_metadata_cache();
+        self.flush_library();
+        if let Err(e) = set_data_dir(folder) {
+            self.error_message = Some(e);
+            return;
+        }
+        if !Self::settings_file().exists() {
+            self.save_settings();
+        }
+        self.data_dir_unwritable = false;
+        self.error_message = None;
+        self.reload_library();
+        self.scan_songs();
+        self.watch_library();
+    }
+
+    fn show_library_recovery_window(&mut self, ctx: &egui::Context) {
+        if !self.library_load_failed {
+            return;
+        }
+        let mut restore = false;
+        let mut start_fresh = false;
+        egui::Window::new("Library File Damaged")
+            .collapsible(false)
+            .resizable(false)
+            .default_width(340.0)
+            .show(ctx, |ui| {
+                ui.label(
+                    "Kiraboshi could not read its library file. Ratings, play counts and other track data \
+                     will not be saved until you restore a backup or start a fresh library.",
+                );
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    restore = ui.button("Restore Backup").clicked();
+                    start_fresh = ui.button("Start Fresh").clicked();
+                });
+            });
+        if restore {
+            match PlaylistFile::restore_backup(&Self::playlist_file()) {
+                Ok(_) => {
+                    self.error_message = None;
+                    self.reload_library();
+                    self.scan_songs();
+                }
+                Err(e) => self.error_message = Some(e),
+            }
+        } else if start_fresh {
+            self.library_load_failed = false;
+            self.error_message = None;
+            self.mark_library_dirty();
+            self.flush_library();
+        }
+    }
+
+    fn show_data_dir_window(&mut self, ctx: &egui::Context) {
+        if !self.data_dir_unwritable {
+            return;
+        }
+        let mut open = true;
+        let mut choose = false;
+        egui::Window::new("Data Folder Not Writable")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Kiraboshi cannot save to {}. Your library, playlists and settings will not be kept.",
+                    Self::data_dir().display()
+                ));
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Choose Folder...").clicked() {
+                        choose = true;
+                    }
+                    if ui.button("Continue Anyway").clicked() {
+                        self.data_dir_unwritable = false;
+                    }
+                });
+            });
+        if !open {
+            self.data_dir_unwritable = false;
+        }
+        if choose && let Some(folder) = rfd::FileDialog::new().pick_folder() {
+            self.change_data_dir(&folder);
+        }
+    }
+
+    fn export_backup(&mut self, dest: PathBuf) {
+        self.save_all();
+        let include_media = self.backup_include_media;
+        self.tasks.spawn("Exporting backup", move |reporter| {
+            let saved = Backup::export(&Self::data_dir(), include_media, &dest, |done, total| reporter.progress(done, total))?;
+            reporter.note(format!("Saved {} files", saved));
+            Ok(())
+        });
+    }
+
+    fn apply_limiter_settings(&mut self) {
+        self.core.audio.set_limiter(
+            self.settings.limiter,
+            self.settings.limiter_threshold_db,
+            self.settings.compressor,
+        );
+    }
+
+    fn apply_crossfeed_settings(&mut self) {
+        self.core.audio.set_crossfeed(
+            self.settings.crossfeed,
+            self.settings.crossfeed_cutoff_hz,
+            self.settings.crossfeed_level_db,
+        );
+    }
+
+    const DEVICE_POLL: Duration = Duration::from_secs(2);
+
+    fn remember_device_volume(&mut self) {
+        if let Some(device) = &self.output_device {
+            self.settings.device_volumes.insert(device.clone(), self.volume);
+        }
+    }
+
+    fn watch_output_device(&mut self) {
+        let Some(device) = self.device_watcher.poll() else {
+            return;
+        };
+        self.volume = self.settings.volume_for(Some(&device));
+        self.core.audio.set_volume(self.volume);
+        self.output_device = Some(device);
+    }
+
+    fn apply_output_settings(&mut self) {
+        let buffer_frames = self.settings.output_latency.buffer_frames();
+        if let Err(e) = self.core.audio.set_output(self.settings.output_sample_rate, buffer_frames) {
+            let current = self.core.audio.buffer_frames();
+            self.settings.output_sample_rate = self.core.audio.sample_rate();
+            self.settings.output_latency = OutputLatency::ALL
+                .into_iter()
+                .find(|l| l.buffer_frames() == current)
+                .unwrap_or(OutputLatency::Default);
+            self.error_message = Some(format!("The output device rejected this setting: {}", e));
+        }
+    }
+
+    fn check_output_settings(&mut self) {
+        let requested = (self.settings.output_sample_rate, self.settings.output_latency.buffer_frames());
+        if requested != (self.core.audio.sample_rate(), self.core.audio.buffer_frames()) {
+            self.error_message = Some(
+                "The output device rejected the configured sample rate or latency, so device defaults are in use"
+                    .to_string(),
+            );
+        }
+    }
+
+    fn apply_eq_settings(&mut self) {
+        let gains = self.settings.eq_enabled.then(|| band_gains(&self.settings.eq_points));
+        self.core.audio.set_eq(gains);
+    }
+
+    fn eq_curve_editor(ui: &mut egui::Ui, points: &mut Vec<EqPoint>, dragging: &mut Option<usize>) -> (bool, bool) {
+        let (rect, response) = ui.allocate_exact_size(egui::vec2(360.0, 160.0), egui::Sense::click_and_drag());
+        let log_min = EQ_MIN_FREQ.log10();
+        let log_span = EQ_MAX_FREQ.log10() - log_min;
+        let to_pos = |frequency: f32, gain_db: f32| {
+            egui::pos2(
+                rect.left() + (frequency.log10() - log_min) / log_span * rect.width(),
+                rect.center().y - gain_db / EQ_MAX_GAIN * rect.height() / 2.0,
+            )
+        };
+        let from_pos = |pos: egui::Pos2| {
+            EqPoint {
+                frequency: 10f32.powf(log_min + (pos.x - rect.left()) / rect.width() * log_span),
+                gain_db: (rect.center().y - pos.y) / (rect.height() / 2.0) * EQ_MAX_GAIN,
+            }
+            .clamped()
+        };
+        let hit = |points: &[EqPoint], pos: egui::Pos2| {
+            points
+                .iter()
+                .position(|p| to_pos(p.frequency, p.gain_db).distance(pos) < 8.0)
+        };
+
+        let pointer = response.interact_pointer_pos();
+        let mut edited = false;
+        let mut finished = false;
+        if response.drag_started() {
+            *dragging = pointer.and_then(|pos| hit(points, pos));
+        }
+        if let Some(index) = *dragging
+            && let Some(pos) = pointer
+            && response.dragged()
+        {
+            let moved = from_pos(pos);
+            points[index] = moved;
+            sort_points(points);
+            *dragging = points.iter().position(|p| *p == moved);
+            edited = true;
+        }
+        if response.drag_stopped() && dragging.take().is_some() {
+            finished = true;
+        }
+        if response.double_clicked()
+            && let Some(pos) = pointer
+            && hit(points, pos).is_none()
+        {
+            points.push(from_pos(pos));
+            sort_points(points);
+            edited = true;
+            finished = true;
+        }
+        if response.secondary_clicked()
+            && let Some(index) = pointer.and_then(|pos| hit(points, pos))
+        {
+            points.remove(index);
+            edited = true;
+            finished = true;
+        }
+
+        let painter = ui.painter_at(rect);
+        let accent = ui.visuals().selection.stroke.color;
+        painter.rect_filled(rect, 4.0, egui::Color32::from_gray(30));
+        for frequency in EQ_BANDS {
+            let x = to_pos(frequency, 0.0).x;
+            painter.vline(x, rect.y_range(), egui::Stroke::new(1.0, egui::Color32::from_gray(42)));
+        }
+        for gain in [-6.0, 6.0] {
+            painter.hline(rect.x_range(), to_pos(EQ_MIN_FREQ, gain).y, egui::Stroke::new(1.0, egui::Color32::from_gray(42)));
+        }
+        painter.hline(rect.x_range(), rect.center().y, egui::Stroke::new(1.0, egui::Color32::from_gray(70)));
+        let curve: Vec<egui::Pos2> = (0..=120)
+            .map(|i| {
+                let frequency = 10f32.powf(log_min + log_span * i as f32 / 120.0);
+                to_pos(frequency, curve_gain(points, frequency))
+            })
+            .collect();
+        painter.add(egui::Shape::line(curve, egui::Stroke::new(1.5, accent)));
+        for (frequency, gain) in EQ_BANDS.iter().zip(band_gains(points)) {
+            painter.circle_filled(to_pos(*frequency, gain), 2.0, egui::Color32::from_gray(150));
+        }
+        let hovered = response.hover_pos().and_then(|pos| hit(points, pos));
+        for (i, point) in points.iter().enumerate() {
+            let radius = if Some(i) == hovered || Some(i) == *dragging { 6.0 } else { 4.5 };
+            painter.circle_filled(to_pos(point.frequency, point.gain_db), radius, accent);
+        }
+        response.on_hover_text("Drag points to shape the curve. Double-click to add a point, right-click to remove one.");
+        (edited, finished)
+    }
+
+    fn show_equalizer_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_equalizer;
+        let mut apply = false;
+        let mut changed = false;
+        egui::Window::new("Equalizer")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                if ui.checkbox(&mut self.settings.eq_enabled, "Enable equalizer").changed() {
+                    apply = true;
+                    changed = true;
+                }
+                ui.add_space(4.0);
+                let (edited, finished) =
+                    Self::eq_curve_editor(ui, &mut self.settings.eq_points, &mut self.eq_drag);
+                apply |= edited;
+                changed |= finished;
+                ui.horizontal(|ui| {
+                    for (frequency, gain) in EQ_BANDS.iter().zip(band_gains(&self.settings.eq_points)) {
+                        let label = if *frequency >= 1000.0 {
+                            format!("{}k", frequency / 1000.0)
+                        } else {
+                            format!("{}", frequency)
+                        };
+                        ui.label(
+                            egui::RichText::new(format!("{}\n{:+.1}", label, gain))
+                                .size(10.0)
+                                .color(egui::Color32::GRAY),
+                        );
+                    }
+                });
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    let mut chosen = None;
+                    egui::ComboBox::from_id_salt("eq_preset")
+                        .selected_text("Presets")
+                        .show_ui(ui, |ui| {
+                            if ui.selectable_label(false, "Flat").clicked() {
+                                chosen = Some(("Flat".to_string(), Vec::new()));
+                            }
+                            for preset in &self.settings.eq_presets {
+                                if ui.selectable_label(false, &preset.name).clicked() {
+                                    chosen = Some((preset.name.clone(), preset.points.clone()));
+                                }
+                            }
+                        });
+                    if let Some((name, points)) = chosen {
+                        self.settings.eq_points = points;
+                        self.eq_preset_name = name;
+                        apply = true;
+                        changed = true;
+                    }
+                    ui.add(egui::TextEdit::singleline(&mut self.eq_preset_name).desired_width(120.0));
+                    let name = self.eq_preset_name.trim().to_string();
+                    let existing = self.settings.eq_presets.iter().position(|p| p.name == name);
+                    if ui.add_enabled(!name.is_empty(), egui::Button::new("Save")).clicked() {
+                        let preset = EqPreset {
+                            name,
+                            points: self.settings.eq_points.clone(),
+                        };
+                        match existing {
+                            Some(i) => self.settings.eq_presets[i] = preset,
+                            None => self.settings.eq_presets.push(preset),
+                        }
+                        changed = true;
+                    }
+                    if let Some(i) = existing
+                        && ui.button("Delete").clicked()
+                    {
+                        self.settings.eq_presets.remove(i);
+                        changed = true;
+                    }
+                });
+            });
+        if apply {
+            self.apply_eq_settings();
+        }
+        if changed {
+            self.save_settings();
+        }
+        self.show_equalizer = open;
+    }
+
+    const OSD_FADE_IN: f32 = 0.2;
+    const OSD_HOLD: f32 = 2.0;
+    const OSD_FADE_OUT: f32 = 0.5;
+
+    fn show_osd(&mut self, label: &'static str) {
+        if self.settings.osd {
+            self.osd = Some((Instant::now(), label));
+        }
+    }
+
+    fn osd_artwork(&mut self, ctx: &egui::Context, path: &Path) -> Option<egui::TextureHandle> {
+        if self.osd_art.as_ref().is_none_or(|(p, _)| p != path) {
+            let texture = TrackMetadata::read_artwork(path)
+                .and_then(|bytes| image::load_from_memory(&bytes).ok())
+                .map(|img| {
+                    let rgba = img.thumbnail(96, 96).to_rgba8();
+                    let size = [rgba.width() as usize, rgba.height() as usize];
+                    let image = egui::ColorImage::from_rgba_unmultiplied(size, &rgba.into_raw());
+                    ctx.load_texture("osd_art", image, egui::TextureOptions::LINEAR)
+                });
+            self.osd_art = Some((path.to_path_buf(), texture));
+        }
+        self.osd_art.as_ref().and_then(|(_, texture)| texture.clone())
+    }
+
+    fn draw_osd(&mut self, ctx: &egui::Context) {
+        let serial = self.core.audio.track_serial();
+        if serial != self.osd_serial {
+            self.osd_serial = serial;
+            if self.core.audio.current_file().is_some() {
+                self.show_osd("Now Playing");
+            }
+        }
+        let Some((started, label)) = self.osd else {
+            return;
+        };
+        let elapsed = started.elapsed().as_secs_f32();
+        let alpha = if elapsed < Self::OSD_FADE_IN {
+            elapsed / Self::OSD_FADE_IN
+        } else {
+            1.0 - ((elapsed - Self::OSD_FADE_IN - Self::OSD_HOLD) / Self::OSD_FADE_OUT).clamp(0.0, 1.0)
+        };
+        if alpha <= 0.0 || !self.settings.osd {
+            self.osd = None;
+            return;
+        }
+        let Some(path) = self.core.audio.current_file().cloned() else {
+            self.osd = None;
+            return;
+        };
+        let art = self.osd_artwork(ctx, &path);
+        let (align, offset) = match self.settings.osd_position {
+            OsdPosition::TopLeft => (egui::Align2::LEFT_TOP, egui::vec2(16.0, 46.0)),
+            OsdPosition::TopRight => (egui::Align2::RIGHT_TOP, egui::vec2(-16.0, 46.0)),
+            OsdPosition::BottomLeft => (egui::Align2::LEFT_BOTTOM, egui::vec2(16.0, -16.0)),
+            OsdPosition::BottomRight => (egui::Align2::RIGHT_BOTTOM, egui::vec2(-16.0, -16.0)),
+        };
+        egui::Area::new(egui::Id::new("now_playing_osd"))
+            .order(egui::Order::Foreground)
+            .anchor(align, offset)
+            .interactable(false)
+            .show(ctx, |ui| {
+                ui.multiply_opacity(alpha);
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        if let Some(art) = &art {
+                            ui.image(egui::load::SizedTexture::new(art.id(), egui::vec2(48.0, 48.0)));
+                        }
+                        ui.vertical(|ui| {
+                            ui.label(
+                                egui::RichText::new(label)
+                                    .size(11.0)
+                                    .color(egui::Color32::from_rgb(190, 155, 65)),
+                            );
+                            ui.label(
+                                egui::RichText::new(Self::display_name(&path))
+                                    .size(15.0)
+                                    .color(egui::Color32::WHITE),
+                            );
+                        });
+                    });
+                });
+            });
+    }
+
+    fn show_session_stats(&self, ui: &mut egui::Ui, width: f32) {
+        let session = &self.core.session;
+        ui.allocate_ui(egui::vec2(width, 0.0), |ui| {
+            egui::CollapsingHeader::new(egui::RichText::new("This Session").size(12.0))
+                .id_salt("session_stats")
+                .show(ui, |ui| {
+                    let secs = session.listened.as_secs();
+                    let listened = format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+                    egui::Grid::new("session_stats_grid")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label(egui::RichText::new("Listening time").size(12.0));
+                            ui.label(egui::RichText::new(listened).size(12.0).monospace());
+                            ui.end_row();
+                            ui.label(egui::RichText::new("Tracks played").size(12.0));
+                            ui.label(egui::RichText::new(session.tracks_played.to_string()).size(12.0));
+                            ui.end_row();
+                            if let Some((path, count)) = session.most_played() {
+                                ui.label(egui::RichText::new("Most played").size(12.0));
+                                ui.label(
+                                    egui::RichText::new(format!("{} ({})", Self::display_name(path), count)).size(12.0),
+                                );
+                                ui.end_row();
+                            }
+                        });
+                });
+        });
+    }
+
+    fn update_accent(&mut self, ctx: &egui::Context) {
+        let target = match self.core.audio.current_file().cloned() {
+            Some(path) if self.settings.artwork_accent => {
+                if !self.artwork_colors.contains_key(&path) {
+                    match self.metadata_store.get(&path).and_then(|e| e.artwork_color) {
+                        Some(color) => {
+                            self.artwork_colors.insert(path.clone(), color.map(|[r, g, b]| egui::Color32::from_rgb(r, g, b)));
+                        }
+                        None => {
+                            if self.artwork_scan.is_none() {
+                                let source = path.clone();
+                                let scan = self.tasks.spawn("Reading album art", move |_| {
+                                    Ok(TrackMetadata::read_artwork(&source).and_then(|bytes| dominant_color(&bytes)))
+                                });
+                                self.artwork_scan = Some((scan, path));
+                            }
+                            return;
+                        }
+                    }
+                }
+                self.artwork_colors[&path]
+            }
+            _ => None,
+        };
+        self.accent.update(ctx, target);
+    }
+
+    fn apply_silence_settings(&mut self) {
+        let skip = self.settings.skip_silence.then_some((
+            self.settings.silence_threshold_db,
+            self.settings.silence_min_secs as f64,
+        ));
+        self.core.audio.set_skip_silence(skip);
+    }
+
+    fn reload_library(&mut self) {
+        self.library_dirty = None;
+        self.library_load_failed = false;
+        let library = PlaylistFile::load(&Self::playlist_file()).unwrap_or_else(|e| {
+            self.error_message = Some(e);
+            self.library_load_failed = true;
+            PlaylistFile::default()
+        });
+        self.library = library.paths;
+        self.core.playlist = self.library.clone();
+        self.active_playlist = None;
+        self.core.track_data = library.tracks;
+        self.settings = Settings::load(&Self::settings_file());
+        self.playlist_names = NamedPlaylist::list(&Self::playlists_dir());
+        self.load_playlist(self.settings.active_playlist.clone());
+        self.resume_points = ResumePoints::load(&Self::resume_file());
+        self.core.audio.set_seek_fade(self.settings.seek_fade);
+        self.apply_limiter_settings();
+        self.apply_crossfeed_settings();
+        self.apply_silence_settings();
+        self.apply_eq_settings();
+        self.apply_output_settings();
+        self.core.shuffle_mode = self.settings.shuffle_mode;
+        self.core.end_tolerance = self.settings.end_tolerance_secs as f64;
+        self.metadata_cache.clear();
+        self.metadata_store = MetadataCache::load(&Self::metadata_cache_file());
+        self.bpm_order = None;
+        self.metadata_prescan = None;
+        self.waveform_thumbs.clear();
+        self.artwork_colors.clear();
+        self.cue_cache.clear();
+        self.hash_cache.clear();
+        self.view = PlaylistView::Library;
+        self.now_playing_text = None;
+        self.sync_status_server();
+        if self.settings.prescan_metadata {
+            self.prescan_metadata();
+        }
+    }
+
+    fn restore_backup(&mut self, backup: Backup, mode: RestoreMode) -> Result<(), String> {
+        self.check_writable()?;
+        if self.backup_restore.is_some() {
+            return Err("A backup is already being restored".to_string());
+        }
+        self.save_all();
+        let data_dir = Self::data_dir();
+        self.backup_restore = Some(self.tasks.spawn("Restoring backup", move |reporter| {
+            let playlist_name = Path::new(".kiraboshi");
+            let skip: &[&Path] = if mode == RestoreMode::Merge { &[playlist_name] } else { &[] };
+            backup.restore(&data_dir, mode, skip, |done, total| reporter.progress(done, total))?;
+            let imported = match mode {
+                RestoreMode::Replace => None,
+                RestoreMode::Merge => backup
+                    .file(playlist_name)
+                    .map(|bytes| PlaylistFile::parse(&String::from_utf8_lossy(&bytes)))
+                    .transpose()?,
+            };
+            reporter.note(format!("Restored {} files", backup.len()));
+            Ok((mode, imported))
+        }));
+        Ok(())
+    }
+
+    fn finish_restore(&mut self, mode: RestoreMode, imported: Option<PlaylistFile>) {
+        if mode == RestoreMode::Replace {
+            self.reload_library();
+            return;
+        }
+        let Some(imported) = imported else {
+            return;
+        };
+        for path in imported.paths {
+            if !self.library.contains(&path) {
+                if self.active_playlist.is_none() {
+                    self.core.playlist.push(path.clone());
+                }
+                self.library.push(path);
+            }
+        }
+        for (path, data) in imported.tracks {
+            self.core.track_data.entry(path).or_insert(data);
+        }
+        self.save_playlist();
+    }
+
+    fn show_restore_window(&mut self, ctx: &egui::Context) {
+        let Some((source, backup)) = self.pending_restore.take() else {
+            return;
+        };
+        let mut open = true;
+        let mut choice = None;
+        egui::Window::new("Import Backup")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} contains {} files ({} audio).",
+                    source.file_name().map(|n| n.to_string_lossy()).unwrap_or_default(),
+                    backup.len(),
+                    backup.media_count(),
+                ));
+                ui.label(
+                    egui::RichText::new("Merge keeps existing files and adds missing tracks. Replace overwrites the playlist and settings.")
+                        .size(11.0)
+                        .color(egui::Color32::GRAY),
+                );
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Merge").clicked() {
+                        choice = Some(Some(RestoreMode::Merge));
+                    }
+                    if ui.button("Replace").clicked() {
+                        choice = Some(Some(RestoreMode::Replace));
+                    }
+                    if ui.button("Cancel").clicked() {
+                        choice = Some(None);
+                    }
+                });
+            });
+        match choice {
+            Some(Some(mode)) => match self.restore_backup(backup, mode) {
+                Ok(()) => self.error_message = None,
+                Err(e) => self.error_message = Some(e),
+            },
+            Some(None) => {}
+            None if open => self.pending_restore = Some((source, backup)),
+            None => {}
+        }
+    }
+
+    fn pick_song(&mut self) {
+        if self.read_only {
+            return;
+        }
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Audio Files", &Self::AUDIO_EXTENSIONS)
+            .pick_file()
+        else {
+            return;
+        };
+        match Self::copy_to_data(&path, None) {
+            Ok(dest) => {
+                self.error_message = None;
+                self.scan_songs();
+                if self.active_playlist.is_some() && self.transient_queue.is_none() && !self.core.playlist.contains(&dest) {
+                    self.core.playlist.push(dest);
+                    self.save_playlist();
+                }
+            }
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    fn pick_import_folder(&mut self) {
+        if self.read_only {
+            return;
+        }
+        if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+            self.import_folder(folder);
+        }
+    }
+
+    fn begin_export(&mut self, tracks: Vec<PathBuf>) {
+        if tracks.is_empty() {
+            return;
+        }
+        if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+            self.pending_export = Some((tracks, folder));
+        }
+    }
+
+    fn show_export_window(&mut self, ctx: &egui::Context) {
+        let Some((tracks, folder)) = self.pending_export.take() else {
+            return;
+        };
+        let mut open = true;
+        let mut choice = None;
+        egui::Window::new("Export Tracks")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let count = if tracks.len() == 1 { "1 track".to_string() } else { format!("{} tracks", tracks.len()) };
+                ui.label(format!("Copy {} to {}", count, folder.display()));
+                ui.horizontal(|ui| {
+                    ui.label("If a file already exists");
+                    egui::ComboBox::from_id_salt("export_collision")
+                        .selected_text(self.export_collision.label())
+                        .show_ui(ui, |ui| {
+                            for mode in CollisionMode::ALL {
+                                ui.selectable_value(&mut self.export_collision, mode, mode.label());
+                            }
+                        });
+                });
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Export").clicked() {
+                        choice = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        choice = Some(false);
+                    }
+                });
+            });
+        match choice {
+            Some(true) => self.export_tracks(tracks, folder),
+            Some(false) => {}
+            None if open => self.pending_export = Some((tracks, folder)),
+            None => {}
+        }
+    }
+
+    fn export_tracks(&mut self, tracks: Vec<PathBuf>, folder: PathBuf) {
+        let mode = self.export_collision;
+        let name = format!("Exporting to {}", Self::display_name(&folder));
+        self.tasks.spawn(name, move |reporter| {
+            let mut copied = 0;
+            for (done, track) in tracks.iter().enumerate() {
+                reporter.progress(done, tracks.len());
+                if export_track(track, &folder, mode)? {
+                    copied += 1;
+                }
+            }
+            reporter.progress(tracks.len(), tracks.len());
+            reporter.note(match tracks.len() - copied {
+                0 => format!("Exported {} files", copied),
+                skipped => format!("Exported {} files, skipped {} existing", copied, skipped),
+            });
+            Ok(())
+        });
+    }
+
+    const LIBRARY_RESCAN: Duration = Duration::from_secs(5);
+    const AUDIO_EXTENSIONS: [&'static str; 4] = ["mp3", "wav", "ogg", "flac"];
+
+    fn is_audio_file(path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| Self::AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+            .unwrap_or(false)
+    }
+
+    fn collect_audio_files(dir: &Path, out: &mut Vec<PathBuf>) {
+        Self::collect_audio_files_once(dir, out, &mut HashSet::new());
+    }
+
+    fn collect_audio_files_once(dir: &Path, out: &mut Vec<PathBuf>, visited: &mut HashSet<PathBuf>) {
+        let Ok(canonical) = dir.canonicalize() else {
+            return;
+        };
+        if !visited.insert(canonical) {
+            return;
+        }
+        for path in std::fs::read_dir(dir).into_iter().flatten().filter_map(|e| e.ok()).map(|e| e.path()) {
+            if path.is_dir() {
+                Self::collect_audio_files_once(&path, out, visited);
+            } else if Self::is_audio_file(&path) {
+                out.push(path);
+            }
+        }
+    }
+
+    fn library_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        Self::collect_audio_files(dir, &mut files);
+        files.sort();
+        files
+    }
+
+    fn watch_library(&mut self) {
+        self.library_watcher = Some(LibraryWatcher::start(Self::data_dir(), Self::LIBRARY_RESCAN, Self::library_files));
+    }
+
+    fn poll_library_watcher(&mut self) {
+        if let Some(files) = self.library_watcher.as_ref().and_then(|w| w.poll()) {
+            self.apply_scan(files);
+        }
+    }
+
+    fn track_identity(metadata: &TrackMetadata, path: &Path) -> Option<TrackIdentity> {
+        let duration = metadata.duration?.round() as u64;
+        let file_name = match (&metadata.artist, &metadata.title) {
+            (Some(_), Some(_)) => None,
+            _ => Some(path.file_name()?.to_string_lossy().into_owned()),
+        };
+        Some((metadata.artist.clone(), metadata.title.clone(), metadata.album.clone(), file_name, duration))
+    }
+
+    fn find_moved_files(&mut self, missing: &[PathBuf], added: &[PathBuf]) -> Vec<(PathBuf, PathBuf)> {
+        let mut before: HashMap<TrackIdentity, Vec<&PathBuf>> = HashMap::new();
+        for path in missing {
+            let metadata = self.metadata_cache.get(path).or_else(|| self.metadata_store.stored(path));
+            if let Some(identity) = metadata.and_then(|m| Self::track_identity(m, path)) {
+                before.entry(identity).or_default().push(path);
+            }
+        }
+        if before.is_empty() {
+            return Vec::new();
+        }
+        let mut after: HashMap<TrackIdentity, Vec<&PathBuf>> = HashMap::new();
+        for path in added {
+            if let Some(identity) = Self::track_identity(self.metadata(path), path) {
+                after.entry(identity).or_default().push(path);
+            }
+        }
+        before
+            .into_iter()
+            .filter_map(|(identity, from)| match (from.as_slice(), after.get(&identity)?.as_slice()) {
+                ([from], [to]) => Some(((*from).clone(), (*to).clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn relink_moved_files(&mut self, moves: &[(PathBuf, PathBuf)]) {
+        for (from, to) in moves {
+            for path in self.library.iter_mut().chain(self.core.playlist.iter_mut()) {
+                if path == from {
+                    *path = to.clone();
+                }
+            }
+            if let Some(data) = self.core.track_data.remove(from) {
+                self.core.track_data.insert(to.clone(), data);
+            }
+            if let Some(hash) = self.hash_cache.remove(from) {
+                self.hash_cache.insert(to.clone(), hash);
+            }
+            self.metadata_cache.remove(from);
+        }
+        for name in self.playlist_names.clone() {
+            if self.active_playlist.as_ref() == Some(&name) {
+                continue;
+            }
+            let Some(mut playlist) = NamedPlaylist::load(&Self::playlists_dir(), &name) else {
+                continue;
+            };
+            let mut changed = false;
+            for path in &mut playlist.paths {
+                if let Some((_, to)) = moves.iter().find(|(from, _)| from == path) {
+                    *path = to.clone();
+                    changed = true;
+                }
+            }
+            if changed
+                && !self.read_only
+                && let Err(e) = playlist.save(&Self::playlists_dir())
+            {
+                self.error_message = Some(e);
+            }
+        }
+        let dir = Self::data_dir();
+        let relative = |path: &Path| path.strip_prefix(&dir).unwrap_or(path).display().to_string();
+        let summary: Vec<String> = moves
+            .iter()
+            .map(|(from, to)| format!("{} → {}", relative(from), relative(to)))
+            .collect();
+        let note = format!("Relinked {} moved files: {}", moves.len(), summary.join(", "));
+        self.tasks.spawn("Relinking moved files", move |reporter| {
+            reporter.note(note);
+            Ok(())
+        });
+    }
+
+    fn scan_songs(&mut self) {
+        self.apply_scan(Self::library_files(&Self::data_dir()));
+    }
+
+    fn apply_scan(&mut self, on_disk: Vec<PathBuf>) {
+        let missing: Vec<PathBuf> = self.library.iter().filter(|p| !on_disk.contains(p)).cloned().collect();
+        let mut relinked = false;
+        if !missing.is_empty() {
+            let added: Vec<PathBuf> = on_disk.iter().filter(|p| !self.library.contains(p)).cloned().collect();
+            let moves = if added.is_empty() { Vec::new() } else { self.find_moved_files(&missing, &added) };
+            if !moves.is_empty() {
+                self.relink_moved_files(&moves);
+                relinked = true;
+            }
+        }
+        self.library.retain(|p| on_disk.contains(p));
+        if self.transient_queue.is_none() {
+            self.core.playlist.retain(|p| on_disk.contains(p));
+        }
+        let mut changed = relinked;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        for path in &on_disk {
+            if !self.library.contains(path) {
+                self.library.push(path.clone());
+                if self.active_playlist.is_none() && self.transient_queue.is_none() {
+                    self.core.playlist.push(path.clone());
+                }
+                self.core.track_data.entry(path.clone()).or_default().added.get_or_insert(now);
+                changed = true;
+            }
+        }
+        if changed {
+            self.save_playlist();
+        }
+    }
+
+    fn advance_after_removal(&mut self, removed: &Path, idx: usize) {
+        if self.core.playlist.is_empty() {
+            return;
+        }
+        if self.core.shuffle && !self.core.sequential_run {
+            self.play_next();
+            return;
+        }
+        let next = index_after_removal(&self.core.playlist, removed, idx, self.core.loop_mode, self.core.reverse);
+        if !self.core.reverse && self.core.loop_mode != LoopMode::Album && next.is_none_or(|n| n < idx) {
+            self.core.sequential_run = false;
+        }
+        if let Some(next) = next {
+            let path = self.core.playlist[next].clone();
+            if let Err(e) = self.play_track(&path) {
+                self.error_message = Some(e);
+            }
+        }
+    }
+
+    fn remove_track(&mut self, idx: usize) {
+        if self.active_playlist.is_some() || self.transient_queue.is_some() {
+            self.core.playlist.remove(idx);
+            self.shift_selection(idx);
+            self.save_playlist();
+        } else {
+            let path = self.core.playlist[idx].clone();
+            self.delete_track(&path);
+        }
+    }
+
+    fn shift_selection(&mut self, removed: usize) {
+        self.selected_index = match self.selected_index {
+            Some(i) if i == removed => None,
+            Some(i) if i > removed => Some(i - 1),
+            other => other,
+        };
+    }
+
+    fn delete_track(&mut self, path: &Path) {
+        if self.read_only {
+            return;
+        }
+        let idx = self.core.playlist.iter().position(|p| p == path);
+        if let Some(idx) = idx {
+            self.core.playlist.remove(idx);
+            self.shift_selection(idx);
+        }
+        self.library.retain(|p| p != path);
+        let path = path.to_path_buf();
+        self.core.track_data.remove(&path);
+        let is_current = self.core.audio.current_file() == Some(&path);
+        if is_current {
+            let was_playing = self.core.audio.is_playing();
+            self.core.audio.unload();
+            self.core.seek_position = 0.0;
+            if was_playing
+                && self.settings.advance_on_remove
+                && let Some(idx) = idx
+            {
+                self.advance_after_removal(&path, idx);
+            }
+        }
+        let _ = std::fs::remove_file(&path);
+        for ext in Self::SIDECAR_EXTENSIONS {
+            let _ = std::fs::remove_file(path.with_extension(ext));
+        }
+        let data_dir = Self::data_dir();
+        let mut parent = path.parent();
+        while let Some(dir) = parent
+            && dir.starts_with(&data_dir)
+            && dir != data_dir
+            && std::fs::remove_dir(dir).is_ok()
+        {
+            parent = dir.parent();
+        }
+        self.cue_cache.remove(&path);
+        self.hash_cache.remove(&path);
+        self.resume_points.retain_tracks(|track| track != path);
+        self.save_playlist();
+    }
+
+    fn show_duplicates_window(&mut self, ctx: &egui::Context) {
+        let Some(groups) = self.duplicate_groups.take() else {
+            return;
+        };
+        let mut open = true;
+        let mut remove = None;
+        egui::Window::new("Duplicates")
+            .open(&mut open)
+            .collapsible(false)
+            .default_size([340.0, 320.0])
+            .show(ctx, |ui| {
+                if groups.is_empty() {
+                    ui.label(egui::RichText::new("No duplicates found").color(egui::Color32::GRAY));
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for (g, group) in groups.iter().enumerate() {
+                            if g > 0 {
+                                ui.separator();
+                            }
+                            for path in group {
+                                ui.horizontal(|ui| {
+                                    ui.label(path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default());
+                                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                        if !self.read_only && ui.button("Remove").clicked() {
+                                            remove = Some(path.clone());
+                                        }
+                                    });
+                                });
+                            }
+                        }
+                    });
+            });
+        if !open {
+            return;
+        }
+        let mut groups = groups;
+        if let Some(path) = remove {
+            self.delete_track(&path);
+            for group in &mut groups {
+                group.retain(|p| *p != path);
+            }
+            groups.retain(|g| g.len() > 1);
+        }
+        self.duplicate_groups = Some(groups);
+    }
+
+    fn unique_destination(dir: &Path, source: &Path, relative: &Path) -> PathBuf {
+        let dest = dir.join(relative);
+        let stem = relative.file_stem().map(|s| s.to_os_string()).unwrap_or_default();
+        let ext = relative.extension().map(|e| e.to_os_string());
+        let source_hash = content_hash(source);
+        let mut candidate = dest.clone();
+        let mut n = 2;
+        while candidate.exists() && candidate != source {
+            if source_hash.is_some() && content_hash(&candidate) == source_hash {
+                return candidate;
+            }
+            let mut name = stem.clone();
+            name.push(format!(" ({})", n));
+            if let Some(ext) = &ext {
+                name.push(".");
+                name.push(ext);
+            }
+            candidate = dest.with_file_name(name);
+            n += 1;
+        }
+        candidate
+    }
+
+    fn import_folder(&mut self, folder: PathBuf) {
+        let name = format!("Importing {}", Self::display_name(&folder));
+        self.tasks.spawn(name, move |reporter| {
+            let base = folder.parent().unwrap_or(&folder);
+            let mut files = Vec::new();
+            Self::collect_audio_files(&folder, &mut files);
+            files.sort();
+            for (done, file) in files.iter().enumerate() {
+                reporter.progress(done, files.len());
+                Self::copy_to_data(file, Some(base))?;
+            }
+            reporter.progress(files.len(), files.len());
+            reporter.note(format!("Imported {} files", files.len()));
+            Ok(())
+        });
+    }
+
+    fn find_duplicates(&mut self) {
+        if self.duplicate_scan.is_some() {
+            return;
+        }
+        let playlist = self.library.clone();
+        let mut hash_cache = self.hash_cache.clone();
+        self.duplicate_scan = Some(self.tasks.spawn("Finding duplicates", move |reporter| {
+            let groups = find_duplicates(&playlist, &mut hash_cache, |done, total| reporter.progress(done, total));
+            reporter.note(format!("{} duplicate groups", groups.len()));
+            Ok((groups, hash_cache))
+        }));
+    }
+
+    fn poll_tasks(&mut self) {
+        self.tasks.drain();
+        if let Some(scan) = &self.duplicate_scan
+            && let Some(result) = scan.poll()
+        {
+            self.duplicate_scan = None;
+            match result {
+                Ok((groups, hash_cache)) => {
+                    self.hash_cache.extend(hash_cache);
+                    self.duplicate_groups = Some(groups);
+                }
+                Err(e) => self.error_message = Some(format!("Finding duplicates failed: {}", e)),
+            }
+        }
+        if let Some(scan) = &self.marker_scan
+            && let Some(result) = scan.poll()
+        {
+            self.marker_scan = None;
+            if let Ok((path, markers)) = result {
+                self.core.track_data.entry(path).or_default().markers = markers;
+                self.save_playlist();
+            }
+        }
+        if let Some((scan, _)) = &self.startup
+            && let Some(result) = scan.poll()
+            && let Some((_, files)) = self.startup.take()
+        {
+            self.finish_startup(result, files);
+        }
+        if let Some(render) = &self.loop_preview
+            && let Some(result) = render.poll()
+        {
+            self.loop_preview = None;
+            if let Ok(clip) = result {
+                self.core.audio.pause();
+                if let Err(e) = self.core.audio.play_preview(clip) {
+                    self.error_message = Some(e);
+                }
+            }
+        }
+        if self.core.audio.is_previewing() && self.core.audio.is_playing() {
+            self.core.audio.stop_preview();
+        }
+        if let Some(scan) = &self.metadata_prescan
+            && let Some(result) = scan.poll()
+        {
+            self.metadata_prescan = None;
+            if let Ok(store) = result {
+                self.metadata_store.merge(store);
+                let keep: HashSet<&Path> = self.library.iter().map(|p| p.as_path()).collect();
+                self.metadata_store.retain(|p| keep.contains(p));
+                self.metadata_cache.clear();
+                self.bpm_order = None;
+                self.waveform_thumbs.clear();
+                self.artwork_colors.clear();
+                self.save_metadata_cache();
+            }
+        }
+        if let Some(scan) = &self.metadata_scan
+            && let Some(result) = scan.poll()
+        {
+            self.metadata_scan = None;
+            if let Ok(scanned) = result {
+                self.bpm_order = None;
+                for (path, metadata, estimated) in scanned {
+                    self.metadata_store.insert(&path, metadata);
+                    if let Some(bpm) = estimated {
+                        self.metadata_store.set_estimated_bpm(&path, bpm);
+                    }
+                    self.metadata_cache.remove(&path);
+                }
+            }
+        }
+        if let Some((scan, _)) = &self.artwork_scan
+            && let Some(result) = scan.poll()
+            && let Some((_, path)) = self.artwork_scan.take()
+        {
+            let color = result.unwrap_or(None);
+            self.metadata_store.set_artwork_color(&path, color.map(|c| [c.r(), c.g(), c.b()]));
+            self.artwork_colors.insert(path, color);
+        }
+        if let Some(read) = &self.backup_read
+            && let Some(result) = read.poll()
+        {
+            self.backup_read = None;
+            match result {
+                Ok(pending) => self.pending_restore = Some(pending),
+                Err(e) => self.error_message = Some(e),
+            }
+        }
+        if let Some(restore) = &self.backup_restore
+            && let Some(result) = restore.poll()
+        {
+            self.backup_restore = None;
+            if let Ok((mode, imported)) = result {
+                self.finish_restore(mode, imported);
+            }
+        }
+        if let Some(auth) = &self.lastfm_auth
+            && let Some(result) = auth.poll()
+        {
+            self.lastfm_auth = None;
+            if let Ok(session_key) = result {
+                self.settings.lastfm_session_key = Some(session_key);
+                self.save_settings();
+                self.sync_scrobbler();
+            }
+        }
+    }
+
+    fn show_tasks_panel(&mut self, ui: &mut egui::Ui) {
+        if self.tasks.is_empty() {
+            self.tasks_panel_height = 0.0;
+            return;
+        }
+        let title = match self.tasks.active_count() {
+            0 => "Tasks".to_string(),
+            n => format!("Tasks ({} running)", n),
+        };
+        let mut clear = false;
+        let top = ui.cursor().top();
+        egui::CollapsingHeader::new(egui::RichText::new(title).size(12.0))
+            .id_salt("tasks_panel")
+            .show(ui, |ui| {
+                for task in self.tasks.tasks() {
+                    ui.horizontal(|ui| {
+                        ui.label(egui::RichText::new(&task.name).size(12.0));
+                        match &task.state {
+                            TaskState::Running => {
+                                let bar = match task.fraction() {
+                                    Some(fraction) => egui::ProgressBar::new(fraction).show_percentage(),
+                                    None => egui::ProgressBar::new(0.0).animate(true),
+                                };
+                                ui.add(bar.desired_width(160.0));
+                            }
+                            TaskState::Done => {
+                                ui.label(
+                                    egui::RichText::new(task.note.as_deref().unwrap_or("Done"))
+                                        .size(12.0)
+                                        .color(egui::Color32::from_gray(160)),
+                                );
+                            }
+                            TaskState::Failed(e) => {
+                                ui.label(
+                                    egui::RichText::new(e)
+                                        .size(12.0)
+                                        .color(egui::Color32::from_rgb(255, 100, 100)),
+                                );
+                            }
+                        }
+                    });
+                }
+                if ui.small_button("Clear finished").clicked() {
+                    clear = true;
+                }
+            });
+        self.tasks_panel_height = ui.cursor().top() - top;
+        if clear {
+            self.tasks.clear_finished();
+        }
+    }
+
+    fn copy_to_data(source: &PathBuf, base: Option<&Path>) -> Result<PathBuf, String> {
+        let dir = Self::data_dir();
+        let file_name = source.file_name().ok_or("Invalid file name")?;
+        let relative = base
+            .and_then(|b| source.strip_prefix(b).ok())
+            .unwrap_or(Path::new(file_name));
+        let dest = Self::unique_destination(&dir, source, relative);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create data dir: {}", e))?;
+        }
+        if dest != *source && !dest.exists() {
+            std::fs::copy(source, &dest)
+                .map_err(|e| format!("Failed to copy file: {}", e))?;
+            for ext in Self::SIDECAR_EXTENSIONS {
+                let sidecar = source.with_extension(ext);
+                if sidecar.is_file() {
+                    let _ = std::fs::copy(&sidecar, dest.with_extension(ext));
+                }
+            }
+        }
+        Ok(dest)
+    }
+
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        if self.standalone || self.read_only {
+            return;
+        }
+        let mut paths: Vec<PathBuf> =
+            ctx.input(|i| i.raw.dropped_files.iter().filter_map(|f| f.path.clone()).collect());
+        if !ctx.wants_keyboard_input() {
+            let pasted: Vec<String> = ctx.input(|i| {
+                i.events
+                    .iter()
+                    .filter_map(|e| match e {
+                        egui::Event::Paste(text) => Some(text.clone()),
+                        _ => None,
+                    })
+                    .collect()
+            });
+            paths.extend(pasted.iter().flat_map(|text| parse_dropped_paths(text)));
+        }
+        if paths.is_empty() {
+            return;
+        }
+        let mut added = Vec::new();
+        let mut errors = Vec::new();
+        for path in paths {
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            let supported = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| Self::AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+            if path.is_dir() {
+                self.import_folder(path);
+            } else if !path.is_file() {
+                errors.push(format!("{} was not found", path.display()));
+            } else if !supported {
+                errors.push(format!("{} is not a supported audio file", name));
+            } else {
+                match Self::copy_to_data(&path, None) {
+                    Ok(dest) => added.push(dest),
+                    Err(e) => errors.push(e),
+                }
+            }
+        }
+        if !added.is_empty() {
+            self.scan_songs();
+            if self.active_playlist.is_some() {
+                for dest in added {
+                    if !self.core.playlist.contains(&dest) {
+                        self.core.playlist.push(dest);
+                    }
+                }
+                self.save_playlist();
+            }
+        }
+        self.error_message = (!errors.is_empty()).then(|| errors.join("\n"));
+    }
+
+    fn same_album(&mut self, a: &Path, b: &Path) -> bool {
+        if a.parent() == b.parent() {
+            return true;
+        }
+        let album_a = self.metadata(a).album.clone();
+        album_a.is_some() && album_a == self.metadata(b).album
+    }
+
+    fn finish_standalone(&mut self, ctx: &egui::Context) {
+        let Some(current) = self.core.audio.current_file().cloned() else {
+            return;
+        };
+        match self.settings.standalone_end {
+            StandaloneEnd::Hold => {}
+            StandaloneEnd::Close => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+            StandaloneEnd::Loop => {
+                if let Err(e) = self.play_track(&current) {
+                    self.error_message = Some(e);
+                }
+            }
+            StandaloneEnd::Rewind => match self.core.audio.play_song(&current) {
+                Ok(()) => {
+                    self.core.audio.stop();
+                    self.core.seek_position = 0.0;
+                }
+                Err(e) => self.error_message = Some(e),
+            },
+        }
+    }
+
+    fn prepare_advance(&mut self) {
+        if !self.settings.crossfade_on_advance
+            || !self.core.audio.is_playing()
+            || self.core.loop_mode == LoopMode::One
+        {
+            return;
+        }
+        let serial = self.core.audio.track_serial();
+        if self.advance_queue.as_ref().is_some_and(|(s, _)| *s == serial) {
+            return;
+        }
+        let duration = self.core.audio.get_duration();
+        let remaining = duration - self.core.audio.get_position();
+        if duration <= 0.0 || remaining > self.settings.crossfade_secs as f64 {
+            return;
+        }
+        let Some(current) = self.core.audio.current_file().cloned() else {
+            return;
+        };
+        let next = self.core.next_index().map(|i| self.core.playlist[i].clone());
+        match next {
+            Some(next) if !(self.settings.gapless_albums && self.same_album(&current, &next)) => {
+                let fade = Duration::from_secs_f64(remaining.max(0.1));
+                if let Err(e) = self.start_track(&next, Some(fade)) {
+                    self.error_message = Some(e);
+                }
+            }
+            next => self.advance_queue = Some((serial, next)),
+        }
+    }
+
+    fn play_next(&mut self) {
+        if let Some((serial, queued)) = self.advance_queue.take()
+            && serial == self.core.audio.track_serial()
+        {
+            match queued {
+                Some(path) => {
+                    let _ = self.play_track(&path);
+                }
+                None => {
+                    let _ = self.core.play_next(&self.library, None);
+                    self.save_playlist();
+                }
+            }
+            return;
+        }
+        if self.core.playlist.is_empty() {
+            return;
+        }
+        if self.core.loop_mode == LoopMode::One
+            && let Some(current) = self.core.audio.current_file().cloned()
+            && let Some((path, index)) = self.last_cue.clone()
+            && path == current
+        {
+            let _ = self.play_cue_track(&current, index);
+            return;
+        }
+        if self.play_shuffled_cue(None).is_some() {
+            return;
+        }
+        let _ = self.core.play_next(&self.library, None);
+        self.save_playlist();
+    }
+
+    fn play_shuffled_cue(&mut self, fade: Option<Duration>) -> Option<Result<(), String>> {
+        if !self.core.shuffle || self.core.sequential_run || self.standalone {
+            return None;
+        }
+        let current = self.core.audio.current_file().cloned();
+        let folder = current.as_ref().and_then(|c| c.parent()).map(Path::to_path_buf);
+        let mut paths: Vec<PathBuf> = self.core.playlist.clone();
+        if self.core.loop_mode == LoopMode::Album && paths.iter().any(|p| p.parent() == folder.as_deref()) {
+            paths.retain(|p| p.parent() == folder.as_deref());
+        }
+        let mut pool: Vec<(PathBuf, Option<usize>)> = Vec::new();
+        for path in paths {
+            match self.cue_tracks(&path).len() {
+                0 => pool.push((path, None)),
+                count => pool.extend((0..count).map(|k| (path.clone(), Some(k)))),
+            }
+        }
+        if pool.iter().all(|(_, cue)| cue.is_none()) {
+            return None;
+        }
+        let current = current.map(|path| (path, self.current_cue_index()));
+        let track_data = &self.core.track_data;
+        let weight = |entry: &(PathBuf, Option<usize>)| track_data.get(&entry.0).map_or(1.0, |d| d.shuffle_weight());
+        let pick = shuffle_pick(&pool, current.as_ref(), self.core.shuffle_mode, weight, &mut rand::rng())?;
+        let (path, cue) = pool.swap_remove(pick);
+        let result = match cue {
+            Some(index) => self.play_cue_track(&path, index),
+            None => self.start_track(&path, fade),
+        };
+        self.save_playlist();
+        Some(result)
+    }
+
+    fn play_random(&mut self) {
+        let Some(path) = self.core.radio_pick(&self.core.playlist) else {
+            return;
+        };
+        match self.select_track(&path) {
+            Ok(()) => self.error_message = None,
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    fn skip_next(&mut self, instant: bool) {
+        if self.core.playlist.is_empty() {
+            return;
+        }
+        self.advance_queue = None;
+        if !self.core.shuffle || self.core.sequential_run {
+            let step = if self.core.reverse { -1 } else { 1 };
+            if let Some(current) = self.core.audio.current_file().cloned()
+                && let Some(index) = self.current_cue_index()
+                && let Some(target) = index.checked_add_signed(step)
+                && let Some(start) = self.cue_tracks(&current).get(target).map(|c| c.start)
+            {
+                self.seek_to(start);
+                self.last_cue = Some((current, target));
+                return;
+            }
+        }
+        let fade = (self.settings.crossfade_on_next && !instant && self.core.audio.is_playing())
+            .then(|| Duration::from_secs_f32(self.settings.crossfade_secs));
+        if let Some(result) = self.play_shuffled_cue(fade) {
+            if let Err(e) = result {
+                self.error_message = Some(e);
+            }
+            return;
+        }
+        if let Err(e) = self.core.skip_next(&self.library, fade) {
+            self.error_message = Some(e);
+        }
+        self.save_playlist();
+    }
+}
+
+impl eframe::App for KiraboshiApp {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        ctx.request_repaint();
+        if let Some(Err(e)) = self.core.detect_wake() {
+            self.error_message = Some(format!("Playback could not be restored after sleep: {}", e));
+        }
+        let track_ended = self.core.tick();
+        self.check_idle_pause(ctx);
+        self.poll_tasks();
+        self.poll_library_watcher();
+        self.handle_keyboard(ctx);
+        self.handle_dropped_files(ctx);
+        self.handle_type_ahead(ctx);
+        self.enforce_bookmark_loop();
+
+        if !self.standalone {
+            self.prepare_advance();
+        }
+        if !self.standalone && track_ended {
+            self.play_next();
+            if self.transient_queue.is_some() && self.core.audio.is_finished() {
+                self.close_transient_queue();
+            }
+        }
+        if self.standalone && track_ended {
+            if self.core.loop_mode == LoopMode::One {
+                if let Some(current) = self.core.audio.current_file().cloned() {
+                    let _ = self.play_track(&current);
+                }
+            } else if let Some(next) = self.core.next_index() {
+                let path = self.core.playlist[next].clone();
+                if let Err(e) = self.play_track(&path) {
+                    self.error_message = Some(e);
+                }
+            } else {
+                self.finish_standalone(ctx);
+            }
+        }
+        self.watch_cue_boundaries();
+        self.update_now_playing_export();
+        self.update_status_snapshot();
+        self.update_scrobbling();
+        self.update_window_title(ctx);
+        self.update_accent(ctx);
+        self.watch_output_device();
+        self.update_taskbar_progress(frame);
+        self.track_resume_point();
+        if self.backup_restore.is_none() && self.library_dirty.is_some_and(|since| since.elapsed() >= Duration::from_secs(2)) {
+            self.flush_library();
+        }
+
+        egui::TopBottomPanel::top("title_bar")
+            .exact_height(30.0)
+            .frame(egui::Frame::NONE.fill(egui::Color32::from_gray(25)))
+            .show(ctx, |ui| {
+                ui.set_clip_rect(ui.max_rect());
+                ui.horizontal_centered(|ui| {
+                    ui.add_space(8.0);
+                    if let Some(icon) = &self.title_icon {
+                        let icon_size = egui::vec2(20.0, 20.0);
+                        ui.image(egui::load::SizedTexture::new(icon.id(), icon_size));
+                    }
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.spacing_mut().item_spacing.x = 0.0;
+                        let btn_size = egui::vec2(46.0, 30.0);
+
+                        let (close_rect, close_resp) = ui.allocate_exact_size(btn_size, egui::Sense::click());
+                        let close_hovered = ctx.input(|i| i.pointer.hover_pos().is_some_and(|p| close_rect.contains(p)));
+                        if close_hovered {
+                            ui.painter().rect_filled(close_rect, 0.0, egui::Color32::from_rgb(210, 100, 20));
+                        }
+                        let cc = close_rect.center();
                         let x_color = if close_hovered { egui::Color32::from_rgb(255, 225, 120) } else { egui::Color32::from_rgb(185, 155, 65) };
                         let s = 5.0;
                         ui.painter().line_segment([egui::pos2(cc.x - s, cc.y - s), egui::pos2(cc.x + s, cc.y + s)], egui::Stroke::new(1.5, x_color));
@@ -312,319 +5179,713 @@ impl eframe::App for KiraboshiApp {
                         if min_hovered {
                             ui.painter().rect_filled(min_rect, 0.0, egui::Color32::from_rgba_premultiplied(50, 35, 5, 30));
                         }
-                        let nc = min_rect.center();
-                        let min_color = if min_hovered { egui::Color32::from_rgb(255, 220, 100) } else { egui::Color32::from_rgb(185, 155, 65) };
-                        ui.painter().line_segment([egui::pos2(nc.x - 5.0, nc.y), egui::pos2(nc.x + 5.0, nc.y)], egui::Stroke::new(1.5, min_color));
-                        if min_resp.is_pointer_button_down_on()
+                        let nc = min_rect.center();
+                        let min_color = if min_hovered { egui::Color32::from_rgb(255, 220, 100) } else { egui::Color32::from_rgb(185, 155, 65) };
+                        ui.painter().line_segment([egui::pos2(nc.x - 5.0, nc.y), egui::pos2(nc.x + 5.0, nc.y)], egui::Stroke::new(1.5, min_color));
+                        if min_resp.is_pointer_button_down_on()
+                            && ctx.input(|i| i.pointer.any_pressed())
+                        {
+                            ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(true));
+                        }
+
+                        let mini = self.settings.mini_player && !self.standalone;
+                        if !self.standalone {
+                            let (mini_rect, mini_resp) = ui.allocate_exact_size(btn_size, egui::Sense::click());
+                            let mini_hovered = ctx.input(|i| i.pointer.hover_pos().is_some_and(|p| mini_rect.contains(p)));
+                            if mini_hovered {
+                                ui.painter().rect_filled(mini_rect, 0.0, egui::Color32::from_rgba_premultiplied(50, 35, 5, 30));
+                            }
+                            let mini_color = if mini_hovered { egui::Color32::from_rgb(255, 220, 100) } else { egui::Color32::from_rgb(185, 155, 65) };
+                            let icon_size = if mini { egui::vec2(12.0, 10.0) } else { egui::vec2(14.0, 5.0) };
+                            ui.painter().rect_stroke(
+                                egui::Rect::from_center_size(mini_rect.center(), icon_size),
+                                1.0,
+                                egui::Stroke::new(1.5, mini_color),
+                                egui::StrokeKind::Middle,
+                            );
+                            let mini_resp = mini_resp.on_hover_text(if mini { "Full player" } else { "Mini player" });
+                            if mini_resp.is_pointer_button_down_on()
+                                && ctx.input(|i| i.pointer.any_pressed())
+                            {
+                                self.set_mini_player(ctx, !mini);
+                            }
+                        }
+                        if mini {
+                            return;
+                        }
+
+                        let (set_rect, set_resp) = ui.allocate_exact_size(btn_size, egui::Sense::click());
+                        let set_hovered = ctx.input(|i| i.pointer.hover_pos().is_some_and(|p| set_rect.contains(p)));
+                        if set_hovered || self.show_settings {
+                            ui.painter().rect_filled(set_rect, 0.0, egui::Color32::from_rgba_premultiplied(50, 35, 5, 30));
+                        }
+                        let gc = set_rect.center();
+                        let set_color = if set_hovered || self.show_settings { egui::Color32::from_rgb(255, 220, 100) } else { egui::Color32::from_rgb(185, 155, 65) };
+                        ui.painter().circle_stroke(gc, 3.5, egui::Stroke::new(1.5, set_color));
+                        for k in 0..8 {
+                            let a = k as f32 * std::f32::consts::FRAC_PI_4;
+                            let dir = egui::vec2(a.cos(), a.sin());
+                            ui.painter().line_segment([gc + dir * 5.0, gc + dir * 7.0], egui::Stroke::new(1.5, set_color));
+                        }
+                        if set_resp.is_pointer_button_down_on()
                             && ctx.input(|i| i.pointer.any_pressed())
                         {
-                            ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(true));
+                            self.show_settings = !self.show_settings;
                         }
                     });
 
                     let bar = ui.max_rect();
-                    let buttons_width = 46.0 * 3.0;
-                    let drag_rect = egui::Rect::from_min_max(
-                        bar.min,
-                        egui::pos2(bar.max.x - buttons_width, bar.max.y),
-                    );
-                    let title_bar_response = ui.interact(
-                        drag_rect,
-                        ui.id().with("title_bar_drag"),
-                        egui::Sense::click_and_drag(),
-                    );
-                    if title_bar_response.is_pointer_button_down_on()
-                        && ctx.input(|i| i.pointer.any_pressed())
-                    {
-                        ctx.send_viewport_cmd(egui::ViewportCommand::StartDrag);
-                    }
-                    if title_bar_response.double_clicked() {
-                        let is_maximized = ctx.input(|i| i.viewport().maximized.unwrap_or(false));
-                        ctx.send_viewport_cmd(egui::ViewportCommand::Maximized(!is_maximized));
+                    let buttons_width = if self.standalone || self.settings.mini_player { 46.0 * 3.0 } else { 46.0 * 4.0 };
+                    let drag_right = match self.settings.title_drag {
+                        TitleDrag::Full => Some(bar.max.x - buttons_width),
+                        TitleDrag::Grip => Some(bar.min.x + 36.0),
+                        TitleDrag::Off => None,
+                    };
+                    if let Some(drag_right) = drag_right {
+                        let drag_rect = egui::Rect::from_min_max(bar.min, egui::pos2(drag_right, bar.max.y));
+                        let title_bar_response = ui.interact(
+                            drag_rect,
+                            ui.id().with("title_bar_drag"),
+                            egui::Sense::click_and_drag(),
+                        );
+                        if title_bar_response.is_pointer_button_down_on()
+                            && ctx.input(|i| i.pointer.any_pressed())
+                        {
+                            ctx.send_viewport_cmd(egui::ViewportCommand::StartDrag);
+                        }
+                        if title_bar_response.double_clicked() {
+                            match self.settings.title_double_click {
+                                TitleDoubleClick::Maximize => {
+                                    let is_maximized = ctx.input(|i| i.viewport().maximized.unwrap_or(false));
+                                    ctx.send_viewport_cmd(egui::ViewportCommand::Maximized(!is_maximized));
+                                }
+                                TitleDoubleClick::Minimize => {
+                                    ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(true));
+                                }
+                                TitleDoubleClick::MiniPlayer if !self.standalone => {
+                                    self.set_mini_player(ctx, !self.settings.mini_player);
+                                }
+                                TitleDoubleClick::MiniPlayer | TitleDoubleClick::Nothing => {}
+                            }
+                        }
                     }
                 });
             });
 
-        let panel_width = 560.0;
+        if self.startup.is_some() {
+            self.show_loading_view(ctx);
+            return;
+        }
+        if self.show_settings {
+            self.show_settings_window(ctx);
+        }
+        self.show_filter_editor(ctx);
+        if self.show_equalizer {
+            self.show_equalizer_window(ctx);
+        }
+        if self.show_lyrics {
+            self.show_lyrics_window(ctx);
+        }
+        if self.show_chapters {
+            self.show_chapters_window(ctx);
+        }
+        if self.show_bookmarks {
+            self.show_bookmarks_window(ctx);
+        }
+        if self.show_markers {
+            self.show_markers_window(ctx);
+        }
+        self.show_duplicates_window(ctx);
+        self.show_restore_window(ctx);
+        self.show_export_window(ctx);
+        self.show_resume_window(ctx);
+        self.show_session_window(ctx);
+        self.show_data_dir_window(ctx);
+        self.show_library_recovery_window(ctx);
+        self.show_playlist_name_window(ctx);
+        self.draw_osd(ctx);
+        if self.settings.mini_player && !self.standalone {
+            self.show_mini_player(ctx);
+            return;
+        }
+
+        let margin = if self.standalone { 40.0 } else { 340.0 };
+        let panel_width = (ctx.content_rect().width() - margin).max(560.0);
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical_centered(|ui| {
-                ui.add_space(24.0);
-                {
-                    let t = ctx.input(|i| i.time);
-                    let text = "Kiraboshi";
-                    let mut job = egui::text::LayoutJob::default();
-                    for (i, ch) in text.chars().enumerate() {
-                        let phase = (t * 3.0 - i as f64 * 0.5) as f32;
-                        let wave = phase.sin() * 0.5 + 0.5;
-                        let g = (150.0 + wave * 105.0) as u8;
-                        let b = (wave * 30.0) as u8;
-                        job.append(
-                            &ch.to_string(),
-                            0.0,
-                            egui::TextFormat {
-                                font_id: egui::FontId::new(28.0, egui::FontFamily::Proportional),
-                                color: egui::Color32::from_rgb(255, g, b),
-                                ..Default::default()
-                            },
-                        );
-                    }
-                    ui.label(job);
-                }
-                ui.add_space(24.0);
+                let panel_height = ui.available_height();
+                let controls_height = if self.standalone {
+                    panel_height
+                } else {
+                    (panel_height * (1.0 - self.settings.playlist_height_ratio)).floor()
+                };
+                egui::ScrollArea::vertical()
+                    .id_salt("controls")
+                    .max_height(controls_height)
+                    .auto_shrink([true, self.standalone])
+                    .show(ui, |ui| {
+                        ui.vertical_centered(|ui| {
+                        ui.add_space(24.0);
+                        {
+                            let t = if self.settings.reduced_motion { 0.0 } else { ctx.input(|i| i.time) };
+                            let text = "Kiraboshi";
+                            let mut job = egui::text::LayoutJob::default();
+                            for (i, ch) in text.chars().enumerate() {
+                                let phase = (t * 3.0 - i as f64 * 0.5) as f32;
+                                let wave = phase.sin() * 0.5 + 0.5;
+                                let g = (150.0 + wave * 105.0) as u8;
+                                let b = (wave * 30.0) as u8;
+                                job.append(
+                                    &ch.to_string(),
+                                    0.0,
+                                    egui::TextFormat {
+                                        font_id: egui::FontId::new(28.0, egui::FontFamily::Proportional),
+                                        color: egui::Color32::from_rgb(255, g, b),
+                                        ..Default::default()
+                                    },
+                                );
+                            }
+                            ui.label(job);
+                        }
+                        ui.add_space(24.0);
 
-                ui.allocate_ui(egui::vec2(panel_width, 56.0), |ui| {
-                    ui.vertical_centered(|ui| {
-                        if let Some(path) = self.audio.current_file() {
-                            ui.label(
-                                egui::RichText::new("Now Playing")
-                                    .size(12.0)
-                                    .color(egui::Color32::from_rgb(190, 155, 65))
-                            );
-                            ui.label(
-                                egui::RichText::new(Self::display_name(path))
-                                    .size(18.0)
-                                    .color(egui::Color32::WHITE),
-                            );
-                        } else {
-                            ui.label(
-                                egui::RichText::new("Now Playing")
-                                    .size(12.0)
-                                    .color(egui::Color32::from_rgb(190, 155, 65))
-                            );
-                            ui.label(
-                                egui::RichText::new("No track loaded")
-                                    .size(18.0)
-                                    .color(egui::Color32::GRAY),
+                        let mut reveal = false;
+                        ui.allocate_ui(egui::vec2(panel_width, 56.0), |ui| {
+                            let area = ui.max_rect();
+                            self.draw_vu_meters(ui, area);
+                            self.draw_mode_indicators(ui, area);
+                            let lyrics_rect = egui::Rect::from_min_size(
+                                egui::pos2(area.left(), area.top() + 4.0),
+                                egui::vec2(62.0, 22.0),
                             );
+                            if ui
+                                .put(
+                                    lyrics_rect,
+                                    egui::Button::new(egui::RichText::new("Lyrics").size(11.0).color(egui::Color32::from_gray(175)))
+                                        .selected(self.show_lyrics),
+                                )
+                                .clicked()
+                            {
+                                self.show_lyrics = !self.show_lyrics;
+                            }
+                            if ui
+                                .put(
+                                    egui::Rect::from_min_size(lyrics_rect.right_top() + egui::vec2(4.0, 0.0), egui::vec2(74.0, 22.0)),
+                                    egui::Button::new(egui::RichText::new("Bookmarks").size(11.0).color(egui::Color32::from_gray(175)))
+                                        .selected(self.show_bookmarks),
+                                )
+                                .clicked()
+                            {
+                                self.show_bookmarks = !self.show_bookmarks;
+                            }
+                            if ui
+                                .put(
+                                    egui::Rect::from_min_size(lyrics_rect.right_top() + egui::vec2(4.0, 26.0), egui::vec2(74.0, 22.0)),
+                                    egui::Button::new(egui::RichText::new("Markers").size(11.0).color(egui::Color32::from_gray(175)))
+                                        .selected(self.show_markers),
+                                )
+                                .clicked()
+                            {
+                                self.show_markers = !self.show_markers;
+                            }
+                            let position = self.core.audio.get_position();
+                            let chapters = self.current_chapters();
+                            let chapter_title = Self::chapter_at(chapters, position).map(|i| chapters[i].title.clone());
+                            let has_chapters = !chapters.is_empty();
+                            if has_chapters {
+                                let chapters_rect = lyrics_rect.translate(egui::vec2(0.0, 26.0));
+                                if ui
+                                    .put(
+                                        chapters_rect,
+                                        egui::Button::new(egui::RichText::new("Chapters").size(11.0).color(egui::Color32::from_gray(175)))
+                                            .selected(self.show_chapters),
+                                    )
+                                    .clicked()
+                                {
+                                    self.show_chapters = !self.show_chapters;
+                                }
+                            }
+                            ui.vertical_centered(|ui| {
+                                if let Some(path) = self.core.audio.current_file() {
+                                    ui.label(
+                                        egui::RichText::new("Now Playing")
+                                            .size(12.0)
+                                            .color(egui::Color32::from_rgb(190, 155, 65))
+                                    );
+                                    let title_width = area.width() - 2.0 * 152.0;
+                                    let title = Self::marquee_label(
+                                        ui,
+                                        &Self::display_name(path),
+                                        egui::FontId::new(18.0, egui::FontFamily::Proportional),
+                                        egui::Color32::WHITE,
+                                        title_width,
+                                    );
+                                    if !self.standalone {
+                                        reveal |= title
+                                            .on_hover_cursor(egui::CursorIcon::PointingHand)
+                                            .on_hover_text("Show in playlist")
+                                            .clicked();
+                                    }
+                                    if let Some(title) = &chapter_title {
+                                        ui.label(
+                                            egui::RichText::new(title)
+                                                .size(11.0)
+                                                .color(egui::Color32::from_gray(160)),
+                                        );
+                                    }
+                                } else {
+                                    ui.label(
+                                        egui::RichText::new("Now Playing")
+                                            .size(12.0)
+                                            .color(egui::Color32::from_rgb(190, 155, 65))
+                                    );
+                                    ui.label(
+                                        egui::RichText::new("No track loaded")
+                                            .size(18.0)
+                                            .color(egui::Color32::GRAY),
+                                    );
+                                }
+                            });
+                        });
+
+                        if reveal {
+                            self.reveal_current_track();
                         }
-                    });
-                });
 
-                ui.add_space(8.0);
+                        ui.add_space(8.0);
 
-                let position = self.audio.get_position();
-                let duration = self.audio.get_duration();
-                if self.seek_cooldown > 0 {
-                    self.seek_cooldown -= 1;
-                } else if !self.seeking && self.audio.is_playing() {
-                    self.seek_position = position;
-                }
+                        let duration = self.core.audio.get_duration();
 
-                ui.allocate_ui(egui::vec2(panel_width, 20.0), |ui| {
-                    ui.horizontal(|ui| {
-                        ui.label(
-                            egui::RichText::new(Self::format_time(self.seek_position))
-                                .monospace()
-                                .size(12.0),
-                        );
-                        ui.spacing_mut().slider_width = panel_width - 110.0;
-                        let slider = ui.add(
-                            egui::Slider::new(
-                                &mut self.seek_position,
-                                0.0..=duration.max(0.001),
-                            )
-                            .show_value(false),
-                        );
-                        if slider.drag_started() {
-                            self.seeking = true;
-                        }
-                        if slider.drag_stopped() {
-                            self.audio.seek(self.seek_position);
-                            self.seeking = false;
-                            self.seek_cooldown = 5;
-                        }
-                        if slider.changed() && !self.seeking {
-                            self.audio.seek(self.seek_position);
-                            self.seek_cooldown = 5;
-                        }
-                        ui.label(
-                            egui::RichText::new(Self::format_time(duration))
-                                .monospace()
-                                .size(12.0),
-                        );
-                    });
-                });
+                        ui.allocate_ui(egui::vec2(panel_width, 20.0), |ui| {
+                            ui.horizontal(|ui| {
+                                let mut jump = None;
+                                match &mut self.time_entry {
+                                    Some(text) => {
+                                        let edit = ui.add(
+                                            egui::TextEdit::singleline(text)
+                                                .font(egui::FontId::monospace(12.0))
+                                                .desired_width(40.0),
+                                        );
+                                        if edit.lost_focus() {
+                                            let submitted = ui.input(|i| i.key_pressed(egui::Key::Enter));
+                                            jump = Some(Self::parse_time(text).filter(|_| submitted));
+                                        } else if !edit.has_focus() {
+                                            edit.request_focus();
+                                        }
+                                    }
+                                    None => {
+                                        let label = ui
+                                            .add(
+                                                egui::Label::new(
+                                                    egui::RichText::new(Self::format_time(self.core.seek_position))
+                                                        .monospace()
+                                                        .size(12.0),
+                                                )
+                                                .sense(egui::Sense::click()),
+                                            )
+                                            .on_hover_text("Jump to time");
+                                        if label.clicked() && duration > 0.0 {
+                                            self.time_entry = Some(Self::format_time(self.core.seek_position));
+                                        }
+                                    }
+                                }
+                                if let Some(target) = jump {
+                                    self.time_entry = None;
+                                    if let Some(target) = target {
+                                        self.seek_to(target.min(duration));
+                                    }
+                                }
+                                self.seek_slider(ui, panel_width - 110.0, duration);
+                                ui.label(
+                                    egui::RichText::new(Self::format_time(duration))
+                                        .monospace()
+                                        .size(12.0),
+                                );
+                            });
+                        });
 
-                ui.add_space(12.0);
+                        ui.add_space(12.0);
 
-                let btn = egui::vec2(80.0, 28.0);
-                let btn_spacing = 4.0;
-                let btn_count = if self.standalone { 3.0 } else { 4.0 };
-                let total_w = btn.x * btn_count + btn_spacing * (btn_count - 1.0);
-                ui.allocate_ui(egui::vec2(panel_width, 32.0), |ui| {
-                    ui.horizontal(|ui| {
-                        ui.add_space((panel_width - total_w) / 2.0);
-                        ui.spacing_mut().item_spacing.x = btn_spacing;
-
-                        let play_text =
-                            if self.audio.is_playing() { "Pause" } else { "Play" };
-                        if ui.add_sized(btn, egui::Button::new(egui::RichText::new(play_text).color(egui::Color32::from_gray(175)))).clicked() {
-                            if self.audio.is_playing() {
-                                self.audio.pause();
-                            } else {
-                                self.audio.play();
-                                self.seek_cooldown = 5;
-                            }
-                        }
+                        let btn = egui::vec2(80.0, 28.0);
+                        let btn_spacing = 4.0;
+                        let btn_count = if self.standalone { 4.0 } else { 5.0 };
+                        let total_w = btn.x * btn_count + btn_spacing * (btn_count - 1.0);
+                        ui.allocate_ui(egui::vec2(panel_width, 32.0), |ui| {
+                            ui.horizontal(|ui| {
+                                ui.add_space((panel_width - total_w) / 2.0);
+                                ui.spacing_mut().item_spacing.x = btn_spacing;
 
-                        if ui.add_sized(btn, egui::Button::new(egui::RichText::new("Stop").color(egui::Color32::from_gray(175)))).clicked() {
-                            self.audio.stop();
-                            self.seek_position = 0.0;
-                        }
+                                let play_text =
+                                    if self.core.audio.is_playing() { "Pause" } else { "Play" };
+                                let play_resp = ui.add_sized(btn, egui::Button::new(egui::RichText::new(play_text).color(egui::Color32::from_gray(175))));
+                                self.draw_transport_flash(ui, 0, &play_resp);
+                                if play_resp.clicked() {
+                                    self.toggle_playback();
+                                }
 
-                        if self.standalone {
-                            let loop_text = if self.loop_mode == LoopMode::One { "Loop On" } else { "Loop" };
-                            if ui.add_sized(btn, egui::Button::new(egui::RichText::new(loop_text).color(egui::Color32::from_gray(175)))).clicked() {
-                                self.loop_mode = if self.loop_mode == LoopMode::One { LoopMode::Off } else { LoopMode::One };
-                            }
-                        } else {
-                            let loop_text = match self.loop_mode {
-                                LoopMode::Off => "Loop",
-                                LoopMode::One => "Loop One",
-                                LoopMode::All => "Loop All",
-                            };
-                            if ui.add_sized(btn, egui::Button::new(egui::RichText::new(loop_text).color(egui::Color32::from_gray(175)))).clicked() {
-                                self.loop_mode = match self.loop_mode {
-                                    LoopMode::Off => LoopMode::One,
-                                    LoopMode::One => LoopMode::All,
-                                    LoopMode::All => LoopMode::Off,
-                                };
-                            }
+                                let stop_resp = ui.add_sized(btn, egui::Button::new(egui::RichText::new("Stop").color(egui::Color32::from_gray(175))));
+                                self.draw_transport_flash(ui, 1, &stop_resp);
+                                if stop_resp.clicked() {
+                                    self.core.audio.stop();
+                                    self.core.seek_position = 0.0;
+                                    self.show_osd("Stopped");
+                                }
 
-                            let shuf_text = if self.shuffle { "Shuffle On" } else { "Shuffle" };
-                            if ui.add_sized(btn, egui::Button::new(egui::RichText::new(shuf_text).color(egui::Color32::from_gray(175)))).clicked() {
-                                self.shuffle = !self.shuffle;
-                            }
-                        }
-                    });
-                });
+                                let eject_resp = ui.add_sized(btn, egui::Button::new(egui::RichText::new("Eject").color(egui::Color32::from_gray(175))));
+                                self.draw_transport_flash(ui, 2, &eject_resp);
+                                if eject_resp.clicked() {
+                                    self.eject();
+                                }
 
-                ui.add_space(12.0);
+                                if self.standalone {
+                                    let loop_text = if self.core.loop_mode == LoopMode::One { "Loop On" } else { "Loop" };
+                                    if ui.add_sized(btn, egui::Button::new(egui::RichText::new(loop_text).color(egui::Color32::from_gray(175)))).clicked() {
+                                        self.cycle_loop_mode();
+                                    }
+                                } else {
+                                    let loop_text = match self.core.loop_mode {
+                                        LoopMode::Off => "Loop",
+                                        LoopMode::One => "Loop One",
+                                        LoopMode::Album => "Loop Album",
+                                        LoopMode::All => "Loop All",
+                                        LoopMode::Radio => "Radio",
+                                    };
+                                    if ui.add_sized(btn, egui::Button::new(egui::RichText::new(loop_text).color(egui::Color32::from_gray(175)))).clicked() {
+                                        self.cycle_loop_mode();
+                                    }
 
-                ui.allocate_ui(egui::vec2(panel_width, 20.0), |ui| {
-                    ui.horizontal(|ui| {
-                        ui.add_space((panel_width - 280.0) / 2.0);
-                        ui.label(egui::RichText::new("Volume").size(12.0));
-                        ui.spacing_mut().slider_width = 180.0;
-                        if ui
-                            .add(
-                                egui::Slider::new(&mut self.volume, 0.0..=2.0)
-                                    .step_by(0.01)
-                                    .show_value(false),
-                            )
-                            .changed()
-                        {
-                            self.audio.set_volume(self.volume);
+                                    let shuf_text = if self.core.shuffle { "Shuffle On" } else { "Shuffle" };
+                                    if ui.add_sized(btn, egui::Button::new(egui::RichText::new(shuf_text).color(egui::Color32::from_gray(175)))).clicked() {
+                                        self.toggle_shuffle();
+                                    }
+                                }
+                            });
+                        });
+
+                        ui.add_space(12.0);
+
+                        ui.allocate_ui(egui::vec2(panel_width, 20.0), |ui| {
+                            ui.horizontal(|ui| {
+                                ui.add_space((panel_width - 280.0) / 2.0);
+                                ui.label(egui::RichText::new("Volume").size(12.0));
+                                ui.spacing_mut().slider_width = 180.0;
+                                if ui
+                                    .add(
+                                        egui::Slider::new(&mut self.volume, 0.0..=self.settings.max_volume)
+                                            .step_by(0.01)
+                                            .show_value(false),
+                                    )
+                                    .changed()
+                                {
+                                    self.core.audio.set_volume(self.volume);
+                                    self.remember_device_volume();
+                                }
+                                ui.label(
+                                    egui::RichText::new(format!("{}%", (self.volume * 100.0) as i32))
+                                        .size(12.0),
+                                );
+                            });
+                        });
+
+                        if !self.standalone {
+                            ui.add_space(8.0);
+                            self.show_session_stats(ui, panel_width);
                         }
-                        ui.label(
-                            egui::RichText::new(format!("{}%", (self.volume * 100.0) as i32))
-                                .size(12.0),
-                        );
+                        });
                     });
-                });
 
                 if !self.standalone {
-                ui.add_space(20.0);
-                ui.separator();
-                ui.add_space(8.0);
+                let (divider_rect, divider) = ui.allocate_exact_size(
+                    egui::vec2(panel_width, 28.0),
+                    egui::Sense::drag(),
+                );
+                let divider_color = if divider.hovered() || divider.dragged() {
+                    egui::Color32::from_rgb(190, 155, 65)
+                } else {
+                    ui.visuals().widgets.noninteractive.bg_stroke.color
+                };
+                ui.painter().hline(
+                    divider_rect.x_range(),
+                    divider_rect.center().y,
+                    egui::Stroke::new(1.0, divider_color),
+                );
+                if divider.hovered() || divider.dragged() {
+                    ctx.set_cursor_icon(egui::CursorIcon::ResizeVertical);
+                }
+                if divider.dragged() && panel_height > 0.0 {
+                    let ratio = self.settings.playlist_height_ratio - divider.drag_delta().y / panel_height;
+                    self.settings.playlist_height_ratio = ratio.clamp(0.2, 0.8);
+                }
+                if divider.drag_stopped() {
+                    self.save_settings();
+                }
 
-                self.scan_songs();
-                let current_file = self.audio.current_file().cloned();
+                let current_file = self.core.audio.current_file().cloned();
 
                 ui.allocate_ui(egui::vec2(panel_width, 20.0), |ui| {
-                    let rect = ui.available_rect_before_wrap();
-                    ui.painter().text(
-                        egui::pos2(rect.center().x, rect.center().y),
-                        egui::Align2::CENTER_CENTER,
-                        "Playlist",
-                        egui::FontId::new(14.0, egui::FontFamily::Proportional),
-                        egui::Color32::from_rgb(190, 155, 65),
-                    );
-                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        if ui.button(egui::RichText::new("+ Add Song").color(egui::Color32::from_gray(175))).clicked() {
-                            if let Some(path) = rfd::FileDialog::new()
-                                .add_filter("Audio Files", &["mp3", "wav", "ogg", "flac"])
-                                .pick_file()
-                            {
-                                match self.copy_to_data(&path) {
-                                    Ok(_) => {
-                                        self.error_message = None;
-                                        self.scan_songs();
+                    ui.horizontal(|ui| {
+                        let mut chosen = None;
+                        let playlist_label = match &self.transient_queue {
+                            Some((name, _)) => name.as_str(),
+                            None => self.active_playlist.as_deref().unwrap_or("Library"),
+                        };
+                        let in_playlist = self.transient_queue.is_none();
+                        egui::ComboBox::from_id_salt("active_playlist")
+                            .width(110.0)
+                            .selected_text(
+                                egui::RichText::new(playlist_label)
+                                    .color(egui::Color32::from_rgb(190, 155, 65)),
+                            )
+                            .show_ui(ui, |ui| {
+                                if ui.selectable_label(in_playlist && self.active_playlist.is_none(), "Library").clicked() {
+                                    chosen = Some(None);
+                                }
+                                for name in &self.playlist_names {
+                                    let selected = in_playlist && self.active_playlist.as_ref() == Some(name);
+                                    if ui.selectable_label(selected, name).clicked() {
+                                        chosen = Some(Some(name.clone()));
+                                    }
+                                }
+                            });
+                        if let Some(name) = chosen
+                            && (name != self.active_playlist || !in_playlist)
+                        {
+                            self.load_playlist(name);
+                        }
+                        ui.menu_button(egui::RichText::new("...").color(egui::Color32::from_gray(175)), |ui| {
+                            if ui.button("New Playlist").clicked() {
+                                ui.close();
+                                self.playlist_name_draft = Some((None, String::new()));
+                            }
+                            if let Some(name) = self.active_playlist.clone() {
+                                if ui.button("Rename Playlist").clicked() {
+                                    ui.close();
+                                    self.playlist_name_draft = Some((Some(name.clone()), name.clone()));
+                                }
+                                if ui.button("Delete Playlist").clicked() {
+                                    ui.close();
+                                    if let Err(e) = self.delete_playlist(&name) {
+                                        self.error_message = Some(e);
                                     }
-                                    Err(e) => self.error_message = Some(e),
                                 }
                             }
+                        });
+                        let view_name = match self.view {
+                            PlaylistView::Library => "Library".to_string(),
+                            PlaylistView::RecentlyAdded => "Recently Added".to_string(),
+                            PlaylistView::TopRated => "4+ Stars".to_string(),
+                            PlaylistView::Favorites => "Favorites".to_string(),
+                            PlaylistView::Bpm => "By BPM".to_string(),
+                            PlaylistView::Smart(i) => self
+                                .settings
+                                .smart_filters
+                                .get(i)
+                                .map(|f| f.name.clone())
+                                .unwrap_or_default(),
+                        };
+                        egui::ComboBox::from_id_salt("playlist_view")
+                            .width(110.0)
+                            .selected_text(view_name)
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.view, PlaylistView::Library, "Library");
+                                ui.selectable_value(&mut self.view, PlaylistView::RecentlyAdded, "Recently Added");
+                                ui.selectable_value(&mut self.view, PlaylistView::TopRated, "4+ Stars");
+                                ui.selectable_value(&mut self.view, PlaylistView::Favorites, "Favorites");
+                                ui.selectable_value(&mut self.view, PlaylistView::Bpm, "By BPM");
+                                for (i, filter) in self.settings.smart_filters.iter().enumerate() {
+                                    ui.selectable_value(&mut self.view, PlaylistView::Smart(i), &filter.name);
+                                }
+                            });
+                        let (label, draft) = match self.view {
+                            PlaylistView::Smart(i) => ("Edit", (Some(i), self.settings.smart_filters[i].clone())),
+                            _ => ("+ Smart", (None, SmartFilter::default())),
+                        };
+                        if ui.button(egui::RichText::new(label).color(egui::Color32::from_gray(175))).clicked() {
+                            self.filter_draft = Some(draft);
                         }
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if !self.read_only
+                                && ui
+                                    .button(egui::RichText::new("+ Add Song").color(egui::Color32::from_gray(175)))
+                                    .on_hover_text("Ctrl+O")
+                                    .clicked()
+                            {
+                                self.pick_song();
+                            }
+                            ui.menu_button(egui::RichText::new("More").color(egui::Color32::from_gray(175)), |ui| {
+                                if !self.read_only
+                                    && ui.add(egui::Button::new("Import Folder...").shortcut_text("Ctrl+Shift+O")).clicked()
+                                {
+                                    ui.close();
+                                    self.pick_import_folder();
+                                }
+                                if ui
+                                    .add_enabled(
+                                        !self.core.playlist.is_empty(),
+                                        egui::Button::new("Play Random Track").shortcut_text("L"),
+                                    )
+                                    .clicked()
+                                {
+                                    ui.close();
+                                    self.play_random();
+                                }
+                                if ui.button("Open Folder (Play Once)...").clicked() {
+                                    ui.close();
+                                    if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+                                        self.open_transient_queue(&folder);
+                                    }
+                                }
+                                let close_label = match &self.transient_queue {
+                                    Some((_, QueueSource::Files)) => Some("Close File Queue"),
+                                    Some((_, QueueSource::Folder)) => Some("Close Folder Queue"),
+                                    None => None,
+                                };
+                                if let Some(label) = close_label
+                                    && ui.button(label).clicked()
+                                {
+                                    ui.close();
+                                    self.close_transient_queue();
+                                }
+                                if ui.button("Find Duplicates").clicked() {
+                                    ui.close();
+                                    self.find_duplicates();
+                                }
+                                if ui.button("Export View to Folder...").clicked() {
+                                    ui.close();
+                                    let tracks = self
+                                        .visible_indices()
+                                        .into_iter()
+                                        .map(|i| self.core.playlist[i].clone())
+                                        .collect();
+                                    self.begin_export(tracks);
+                                }
+                                ui.separator();
+                                if ui.checkbox(&mut self.core.reverse, "Play in Reverse Order").changed() {
+                                    self.core.sequential_run = false;
+                                }
+                            });
+                        });
                     });
                 });
 
                 ui.add_space(4.0);
 
-                let drag_handle_width = 24.0;
+                let drag_handle_width = self.settings.row_density.handle_width();
+                let row_height = self.settings.row_density.row_height();
+                let font_scale = self.settings.playlist_font_scale;
+                let row_colors = self.accent.current().row_colors(ui.visuals().dark_mode);
 
-                let remaining = (ui.available_height() - 24.0).max(60.0);
-                egui::ScrollArea::vertical()
+                let visible = self.visible_indices();
+                let remaining = (ui.available_height() - 24.0 - self.tasks_panel_height).max(60.0);
+                let playlist_area = egui::ScrollArea::vertical()
                     .max_height(remaining)
                     .show(ui, |ui| {
                         ui.set_min_width(panel_width);
-                        if self.playlist.is_empty() {
+                        if visible.is_empty() {
+                            let empty_text = if matches!(self.view, PlaylistView::Smart(_)) {
+                                "No songs match this smart playlist"
+                            } else {
+                                "No songs found in playlist"
+                            };
                             ui.add_space(24.0);
                             ui.vertical_centered(|ui| {
                                 ui.label(
-                                    egui::RichText::new("No songs found in playlist")
+                                    egui::RichText::new(empty_text)
                                         .size(13.0)
                                         .color(egui::Color32::GRAY),
                                 );
                             });
                         } else {
-                            let songs: Vec<PathBuf> = self.playlist.clone();
+                            let songs: Vec<PathBuf> = self.core.playlist.clone();
                             let mut row_rects: Vec<egui::Rect> = Vec::new();
                             let mut remove_index: Option<usize> = None;
                             let delete_btn_width = 28.0;
+                            let waveform_width = 56.0;
+                            let current_cue = self.current_cue_index();
+                            let number_width = if self.settings.row_numbers {
+                                let digits = visible.len().to_string().len() as f32;
+                                digits * 7.5 * font_scale + 6.0
+                            } else {
+                                0.0
+                            };
+                            let mut row_number = 0;
+
+                            let mut rows: Vec<(usize, Option<usize>, String)> = Vec::new();
+                            for &i in &visible {
+                                let cues = self.cue_tracks(&songs[i]);
+                                if cues.is_empty() {
+                                    rows.push((i, None, Self::display_name(&songs[i])));
+                                } else {
+                                    for (k, cue) in cues.iter().enumerate() {
+                                        rows.push((i, Some(k), format!("{:02}. {}", k + 1, cue.title)));
+                                    }
+                                }
+                            }
 
-                            for (i, song) in songs.iter().enumerate() {
-                                let name = Self::display_name(song);
-                                let is_current = current_file.as_ref() == Some(song);
+                            for (i, cue, name) in &rows {
+                                let (i, cue) = (*i, *cue);
+                                let song = &songs[i];
+                                let is_current = current_file.as_ref() == Some(song)
+                                    && (cue.is_none() || cue == current_cue);
                                 let is_dragged = self.drag_index == Some(i);
+                                let has_delete = cue.is_none_or(|k| k == 0);
+                                let is_selected = has_delete && self.selected_index == Some(i);
+                                if has_delete {
+                                    row_number += 1;
+                                }
 
                                 let row_width = ui.available_width();
-                                let row_height = 32.0;
 
-                                let (handle_rect, handle_response) = ui.allocate_exact_size(
+                                let (handle_rect, mut handle_response) = ui.allocate_exact_size(
                                     egui::vec2(row_width, row_height),
                                     egui::Sense::click_and_drag(),
                                 );
+                                if handle_response.hovered()
+                                    && let Some(tip) = self.row_tooltip(song)
+                                {
+                                    handle_response = handle_response.on_hover_text(tip);
+                                }
                                 row_rects.push(handle_rect);
+                                if is_selected && self.scroll_to_selection {
+                                    ui.scroll_to_rect(handle_rect, Some(egui::Align::Center));
+                                    self.scroll_to_selection = false;
+                                }
 
                                 if ui.is_rect_visible(handle_rect) {
                                     if is_dragged {
-                                        ui.painter().rect_filled(
-                                            handle_rect,
-                                            4.0,
-                                            egui::Color32::from_rgba_premultiplied(80, 60, 20, 60),
-                                        );
+                                        ui.painter().rect_filled(handle_rect, 4.0, row_colors.dragged_bg);
                                     } else if is_current {
-                                        ui.painter().rect_filled(
-                                            handle_rect,
-                                            4.0,
-                                            egui::Color32::from_white_alpha(22),
-                                        );
+                                        ui.painter().rect_filled(handle_rect, 4.0, row_colors.current_bg);
                                     }
                                     if handle_response.hovered() && !is_dragged {
-                                        ui.painter().rect_filled(
-                                            handle_rect,
+                                        ui.painter().rect_filled(handle_rect, 4.0, row_colors.hover_bg);
+                                    }
+                                    if is_selected && self.playlist_focused {
+                                        ui.painter().rect_stroke(
+                                            handle_rect.shrink(1.0),
                                             4.0,
-                                            egui::Color32::from_white_alpha(13),
+                                            egui::Stroke::new(1.0, row_colors.focus),
+                                            egui::StrokeKind::Inside,
+                                        );
+                                    }
+
+                                    if number_width > 0.0 && has_delete {
+                                        ui.painter().text(
+                                            egui::pos2(handle_rect.left() + number_width, handle_rect.center().y),
+                                            egui::Align2::RIGHT_CENTER,
+                                            row_number.to_string(),
+                                            egui::FontId::new(11.0 * font_scale, egui::FontFamily::Monospace),
+                                            egui::Color32::from_gray(130),
                                         );
                                     }
 
-                                    let hx = handle_rect.left() + 12.0;
+                                    let hx = handle_rect.left() + number_width + drag_handle_width / 2.0;
                                     let hy = handle_rect.center().y;
                                     let line_color = if is_dragged {
-                                        egui::Color32::from_rgb(255, 200, 80)
+                                        row_colors.dragged_text
                                     } else {
-                                        egui::Color32::from_rgb(140, 110, 45)
+                                        row_colors.handle
                                     };
                                     for dy in [-4.0, 0.0, 4.0] {
                                         ui.painter().line_segment(
@@ -637,39 +5898,88 @@ impl eframe::App for KiraboshiApp {
                                     }
 
                                     let color = if is_dragged {
-                                        egui::Color32::from_rgb(255, 200, 80)
+                                        row_colors.dragged_text
                                     } else if is_current {
-                                        egui::Color32::from_rgb(255, 210, 80)
+                                        row_colors.current_text
                                     } else {
                                         ui.visuals().text_color()
                                     };
 
                                     let font = if is_current {
-                                        egui::FontId::new(14.0, egui::FontFamily::Proportional)
+                                        egui::FontId::new(14.0 * font_scale, egui::FontFamily::Proportional)
                                     } else {
-                                        egui::FontId::new(13.0, egui::FontFamily::Proportional)
+                                        egui::FontId::new(13.0 * font_scale, egui::FontFamily::Proportional)
                                     };
 
-                                    ui.painter().text(
+                                    let mut text_painter = ui.painter().clone();
+                                    let mut text_right = handle_rect.right() - delete_btn_width * 2.0 - 6.0;
+                                    if self.settings.row_waveforms && has_delete {
+                                        let wave_rect = egui::Rect::from_min_max(
+                                            egui::pos2(text_right - waveform_width, handle_rect.top() + 5.0),
+                                            egui::pos2(text_right, handle_rect.bottom() - 5.0),
+                                        );
+                                        let wave_color = if is_current { row_colors.current_text } else { row_colors.handle };
+                                        let peaks = self.waveform_thumb(song);
+                                        Self::paint_waveform_thumb(ui.painter(), wave_rect, peaks, wave_color);
+                                        text_right = wave_rect.left() - 6.0;
+                                    }
+                                    if self.settings.row_bpm
+                                        && has_delete
+                                        && let Some(label) = self.bpm_label(song)
+                                    {
+                                        let label_rect = ui.painter().text(
+                                            egui::pos2(text_right, handle_rect.center().y),
+                                            egui::Align2::RIGHT_CENTER,
+                                            label,
+                                            egui::FontId::new(11.0 * font_scale, egui::FontFamily::Monospace),
+                                            egui::Color32::from_gray(130),
+                                        );
+                                        text_right = label_rect.left() - 8.0;
+                                    }
+                                    if self.settings.row_waveforms || self.settings.row_bpm {
+                                        text_painter.set_clip_rect(
+                                            text_painter.clip_rect().intersect(handle_rect.with_max_x(text_right)),
+                                        );
+                                    }
+                                    text_painter.text(
                                         egui::pos2(
-                                            handle_rect.left() + drag_handle_width + 8.0,
+                                            handle_rect.left() + number_width + drag_handle_width + 8.0,
                                             handle_rect.center().y,
                                         ),
                                         egui::Align2::LEFT_CENTER,
-                                        &name,
+                                        name,
                                         font,
                                         color,
                                     );
+                                }
 
+                                if has_delete && ui.is_rect_visible(handle_rect) {
                                     let del_rect = egui::Rect::from_min_size(
                                         egui::pos2(handle_rect.right() - delete_btn_width, handle_rect.top()),
                                         egui::vec2(delete_btn_width, row_height),
                                     );
-                                    let del_resp = ui.interact(del_rect, ui.id().with(("del", i)), egui::Sense::click());
+                                    let del_sense = if self.read_only { egui::Sense::hover() } else { egui::Sense::click() };
+                                    let del_resp = ui.interact(del_rect, ui.id().with(("del", i, cue)), del_sense);
                                     if del_resp.clicked() {
                                         remove_index = Some(i);
                                     }
-                                    if handle_response.hovered() || del_resp.hovered() {
+                                    let heart_rect = del_rect.translate(egui::vec2(-delete_btn_width, 0.0));
+                                    let heart_resp = ui.interact(heart_rect, ui.id().with(("heart", i)), egui::Sense::click());
+                                    if heart_resp.clicked() {
+                                        self.toggle_favorite(song);
+                                    }
+                                    let favorite = self.is_favorite(song);
+                                    if favorite || handle_response.hovered() || heart_resp.hovered() {
+                                        let heart_color = if favorite {
+                                            egui::Color32::from_rgb(220, 80, 90)
+                                        } else if heart_resp.hovered() {
+                                            egui::Color32::from_rgb(150, 70, 80)
+                                        } else {
+                                            egui::Color32::from_gray(70)
+                                        };
+                                        Self::paint_heart(ui.painter(), heart_rect.center(), 6.0, heart_color);
+                                    }
+                                    if !self.read_only && (handle_response.hovered() || del_resp.hovered() || heart_resp.hovered()) {
                                         let del_color = if del_resp.hovered() {
                                             egui::Color32::from_rgb(255, 80, 80)
                                         } else {
@@ -682,16 +5992,109 @@ impl eframe::App for KiraboshiApp {
                                     }
                                 }
 
-                                if handle_response.drag_started() {
+                                handle_response.context_menu(|ui| {
+                                    if ui.button("Play from here").clicked() {
+                                        let result = match cue {
+                                            Some(k) => self.play_cue_track(song, k),
+                                            None => self.select_track(song),
+                                        };
+                                        match result {
+                                            Ok(_) => {
+                                                self.error_message = None;
+                                                self.core.sequential_run = true;
+                                            }
+                                            Err(e) => self.error_message = Some(e),
+                                        }
+                                        ui.close();
+                                    }
+                                    let targets: Vec<String> = self
+                                        .playlist_names
+                                        .iter()
+                                        .filter(|name| self.active_playlist.as_ref() != Some(name))
+                                        .cloned()
+                                        .collect();
+                                    if !targets.is_empty() {
+                                        ui.menu_button("Add to Playlist", |ui| {
+                                            for name in targets {
+                                                if ui.button(&name).clicked() {
+                                                    self.add_to_playlist(&name, song);
+                                                    ui.close();
+                                                }
+                                            }
+                                        });
+                                    }
+                                    let favorite_label = if self.is_favorite(song) { "Remove from Favorites" } else { "Add to Favorites" };
+                                    if ui.button(favorite_label).clicked() {
+                                        self.toggle_favorite(song);
+                                        ui.close();
+                                    }
+                                    if ui.button("Export to Folder...").clicked() {
+                                        ui.close();
+                                        self.begin_export(vec![song.clone()]);
+                                    }
+                                    if current_file.as_ref() == Some(song)
+                                        && self.loop_selection().is_some()
+                                        && ui.button("Export Loop...").clicked()
+                                    {
+                                        ui.close();
+                                        self.export_current_loop();
+                                    }
+                                    ui.separator();
+                                    ui.label(egui::RichText::new("Rating").size(12.0));
+                                    if let Some(rating) = Self::star_rating(ui, self.rating(song)) {
+                                        self.set_rating(song, rating);
+                                    }
+                                    ui.separator();
+                                    let mut gain = self.track_gain(song);
+                                    ui.label(egui::RichText::new("Track Gain").size(12.0));
+                                    let slider = ui.add(
+                                        egui::Slider::new(&mut gain, -12.0..=12.0)
+                                            .step_by(0.5)
+                                            .suffix(" dB"),
+                                    );
+                                    let released = slider.drag_stopped() || (slider.changed() && !slider.dragged());
+                                    if slider.changed() || released {
+                                        self.set_track_gain(song, gain, released);
+                                    }
+                                    if ui.button("Reset Gain").clicked() {
+                                        self.set_track_gain(song, 0.0, true);
+                                        ui.close();
+                                    }
+                                    if let Some(point) = Self::album_folder(song)
+                                        .and_then(|folder| self.resume_points.get(&folder))
+                                        .cloned()
+                                    {
+                                        ui.separator();
+                                        if ui.button("Resume Album").clicked() {
+                                            self.resume_album(&point);
+                                            ui.close();
+                                        }
+                                    }
+                                });
+
+                                if handle_response.drag_started()
+                                    && !self.read_only
+                                    && !matches!(self.view, PlaylistView::RecentlyAdded | PlaylistView::Bpm)
+                                {
                                     self.drag_index = Some(i);
                                 }
                                 if handle_response.clicked() {
-                                    let clicked_in_del = ui.input(|i| i.pointer.interact_pos())
+                                    let clicked_in_del = has_delete && ui.input(|i| i.pointer.interact_pos())
                                         .map(|p| p.x > handle_rect.right() - delete_btn_width)
                                         .unwrap_or(false);
                                     if !clicked_in_del {
-                                        match self.audio.play_song(song) {
-                                            Ok(_) => self.error_message = None,
+                                        self.selected_index = Some(i);
+                                        self.core.sequential_run = false;
+                                        let previous = self.core.audio.current_file().cloned();
+                                        let result = match cue {
+                                            Some(k) => self.play_cue_track(song, k),
+                                            None => self.select_track(song),
+                                        };
+                                        match result {
+                                            Ok(_) => {
+                                                self.error_message = None;
+                                                self.offer_resume(song, previous.as_ref());
+                                            }
                                             Err(e) => self.error_message = Some(e),
                                         }
                                     }
@@ -699,14 +6102,7 @@ impl eframe::App for KiraboshiApp {
                             }
 
                             if let Some(idx) = remove_index {
-                                let path = self.playlist.remove(idx);
-                                let is_current = self.audio.current_file() == Some(&path);
-                                if is_current {
-                                    self.audio.unload();
-                                    self.seek_position = 0.0;
-                                }
-                                let _ = std::fs::remove_file(&path);
-                                self.save_playlist();
+                                self.remove_track(idx);
                             }
 
                             if let Some(drag_from) = self.drag_index {
@@ -717,10 +6113,12 @@ impl eframe::App for KiraboshiApp {
                                         let drop_to = row_rects
                                             .iter()
                                             .position(|r| r.contains(pointer))
+                                            .map(|row| rows[row].0)
                                             .unwrap_or(drag_from);
                                         if drag_from != drop_to {
-                                            let item = self.playlist.remove(drag_from);
-                                            self.playlist.insert(drop_to, item);
+                                            let item = self.core.playlist.remove(drag_from);
+                                            self.core.playlist.insert(drop_to, item);
+                                            self.selected_index = Some(drop_to);
                                             self.save_playlist();
                                         }
                                     }
@@ -729,8 +6127,11 @@ impl eframe::App for KiraboshiApp {
                             }
                         }
                     });
+                self.playlist_rect = playlist_area.inner_rect;
                 }
 
+                self.show_tasks_panel(ui);
+
                 if let Some(error) = &self.error_message {
                     ui.add_space(8.0);
                     ui.label(
@@ -742,4 +6143,120 @@ impl eframe::App for KiraboshiApp {
             });
         });
     }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.save_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kiraboshi-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn deleting_a_playlist_leaves_the_library_file_untouched() {
+        let dir = temp_dir("delete-playlist");
+        let tracks: Vec<PathBuf> = ["b.mp3", "a.mp3", "c.mp3"].iter().map(|n| dir.join(n)).collect();
+        for track in &tracks {
+            std::fs::write(track, b"").unwrap();
+        }
+        let library_file = dir.join(".kiraboshi");
+        PlaylistFile::save(&library_file, &tracks, &HashMap::new()).unwrap();
+        let before = std::fs::read(&library_file).unwrap();
+        let playlists = dir.join("playlists");
+        NamedPlaylist { name: "Mix".to_string(), paths: vec![tracks[2].clone()] }.save(&playlists).unwrap();
+
+        let library = delete_named_playlist(&playlists, &library_file, "Mix").unwrap();
+
+        assert_eq!(library, tracks);
+        assert_eq!(std::fs::read(&library_file).unwrap(), before);
+        assert!(!NamedPlaylist::exists(&playlists, "Mix"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn previous_stays_inside_the_album_when_looping_it() {
+        let playlist: Vec<PathBuf> = ["a/1.mp3", "b/1.mp3", "b/2.mp3", "c/1.mp3"].iter().map(PathBuf::from).collect();
+        let previous = |current: usize, loop_mode| previous_path(&playlist, Some(&playlist[current]), loop_mode, false);
+        assert_eq!(previous(2, LoopMode::Album), Some(playlist[1].clone()));
+        assert_eq!(previous(1, LoopMode::Album), Some(playlist[2].clone()));
+        assert_eq!(previous(1, LoopMode::Off), Some(playlist[0].clone()));
+        assert_eq!(previous(0, LoopMode::Off), None);
+    }
+
+    #[test]
+    fn previous_follows_reverse_order() {
+        let playlist: Vec<PathBuf> = ["1.mp3", "2.mp3", "3.mp3"].iter().map(PathBuf::from).collect();
+        let previous = |current: usize, loop_mode| previous_path(&playlist, Some(&playlist[current]), loop_mode, true);
+        assert_eq!(previous(0, LoopMode::Off), Some(playlist[1].clone()));
+        assert_eq!(previous(2, LoopMode::Off), None);
+        assert_eq!(previous(2, LoopMode::All), Some(playlist[0].clone()));
+    }
+
+    #[test]
+    fn a_corrupt_library_file_is_never_overwritten() {
+        let dir = temp_dir("corrupt-library");
+        for name in ["a.mp3", "b.flac"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        let library_file = dir.join(".kiraboshi");
+        std::fs::write(&library_file, "{\"version\": 1, \"tracks\": [{\"path\": ").unwrap();
+        let before = std::fs::read(&library_file).unwrap();
+
+        let load_failed = PlaylistFile::load(&library_file).is_err();
+        let scanned = KiraboshiApp::library_files(&dir);
+        let saved = write_library(&library_file, load_failed, &scanned, &HashMap::new()).unwrap();
+
+        assert!(load_failed);
+        assert_eq!(scanned.len(), 2);
+        assert!(!saved);
+        assert_eq!(std::fs::read(&library_file).unwrap(), before);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn same_named_imports_are_renamed_instead_of_overwritten() {
+        let dir = temp_dir("unique-destination");
+        let data = dir.join("data");
+        std::fs::create_dir_all(&data).unwrap();
+        let first = dir.join("one").join("song.mp3");
+        let second = dir.join("two").join("song.mp3");
+        for (file, contents) in [(&first, b"first"), (&second, b"other")] {
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(file, contents).unwrap();
+        }
+
+        let first_dest = KiraboshiApp::unique_destination(&data, &first, Path::new("song.mp3"));
+        std::fs::copy(&first, &first_dest).unwrap();
+        let second_dest = KiraboshiApp::unique_destination(&data, &second, Path::new("song.mp3"));
+        std::fs::copy(&second, &second_dest).unwrap();
+
+        assert_eq!(first_dest, data.join("song.mp3"));
+        assert_eq!(second_dest, data.join("song (2).mp3"));
+        assert_eq!(std::fs::read(&first_dest).unwrap(), b"first");
+        assert_eq!(std::fs::read(&second_dest).unwrap(), b"other");
+        assert_eq!(KiraboshiApp::unique_destination(&data, &first, Path::new("song.mp3")), first_dest);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn library_scan_survives_symlink_loops() {
+        let dir = temp_dir("symlink-loop");
+        let album = dir.join("album");
+        std::fs::create_dir_all(&album).unwrap();
+        std::fs::write(album.join("track.mp3"), b"").unwrap();
+        std::fs::write(album.join("cover.jpg"), b"").unwrap();
+        std::os::unix::fs::symlink(&dir, album.join("loop")).unwrap();
+
+        assert_eq!(KiraboshiApp::library_files(&dir), vec![album.join("track.mp3")]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
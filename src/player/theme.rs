@@ -0,0 +1,150 @@
+use eframe::egui::{self, Color32};
+use std::time::{Duration, Instant};
+
+const AMBER: [Color32; 8] = [
+    Color32::from_rgb(170, 120, 25),
+    Color32::from_rgb(220, 175, 55),
+    Color32::from_rgb(145, 115, 35),
+    Color32::from_rgb(160, 135, 60),
+    Color32::from_rgb(195, 158, 50),
+    Color32::from_rgb(215, 175, 65),
+    Color32::from_rgb(220, 178, 60),
+    Color32::from_rgb(230, 190, 75),
+];
+
+const TRANSITION: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy)]
+pub struct Palette([Color32; 8]);
+
+#[derive(Clone, Copy)]
+pub struct RowColors {
+    pub current_bg: Color32,
+    pub current_text: Color32,
+    pub hover_bg: Color32,
+    pub dragged_bg: Color32,
+    pub dragged_text: Color32,
+    pub handle: Color32,
+    pub focus: Color32,
+}
+
+impl Palette {
+    pub fn for_accent(accent: Option<Color32>) -> Self {
+        let Some(accent) = accent else {
+            return Self(AMBER);
+        };
+        let peak = accent.r().max(accent.g()).max(accent.b()).max(1) as f32;
+        Self(AMBER.map(|amber| {
+            let scale = amber.r().max(amber.g()).max(amber.b()) as f32 / peak;
+            let channel = |c: u8| (c as f32 * scale).round().min(255.0) as u8;
+            Color32::from_rgb(channel(accent.r()), channel(accent.g()), channel(accent.b()))
+        }))
+    }
+
+    fn brightest(color: Color32) -> Color32 {
+        let scale = 255.0 / color.r().max(color.g()).max(color.b()).max(1) as f32;
+        let channel = |c: u8| (c as f32 * scale).round().min(255.0) as u8;
+        Color32::from_rgb(channel(color.r()), channel(color.g()), channel(color.b()))
+    }
+
+    pub fn row_colors(&self, dark_mode: bool) -> RowColors {
+        let [selection_bg, _, inactive_bg, _, _, _, active_bg, active_fg] = self.0;
+        let (overlay, text, dragged) = if dark_mode {
+            (Color32::WHITE, Self::brightest(active_fg), Self::brightest(active_bg))
+        } else {
+            (Color32::BLACK, selection_bg, selection_bg)
+        };
+        RowColors {
+            current_bg: overlay.gamma_multiply(22.0 / 255.0),
+            current_text: text,
+            hover_bg: overlay.gamma_multiply(13.0 / 255.0),
+            dragged_bg: Color32::from_rgba_unmultiplied(selection_bg.r(), selection_bg.g(), selection_bg.b(), 60),
+            dragged_text: dragged,
+            handle: inactive_bg,
+            focus: inactive_bg,
+        }
+    }
+
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self(std::array::from_fn(|i| self.0[i].lerp_to_gamma(other.0[i], t)))
+    }
+
+    pub fn apply(&self, ctx: &egui::Context) {
+        let [selection_bg, selection, inactive_bg, inactive_fg, hovered_bg, hovered_fg, active_bg, active_fg] = self.0;
+        let mut visuals = egui::Visuals::dark();
+        visuals.selection.bg_fill = selection_bg;
+        visuals.selection.stroke = egui::Stroke::new(1.0, selection);
+        visuals.widgets.inactive.bg_stroke = egui::Stroke::new(1.0, inactive_bg);
+        visuals.widgets.inactive.fg_stroke = egui::Stroke::new(1.0, inactive_fg);
+        visuals.widgets.hovered.bg_stroke = egui::Stroke::new(1.0, hovered_bg);
+        visuals.widgets.hovered.fg_stroke = egui::Stroke::new(1.0, hovered_fg);
+        visuals.widgets.active.bg_stroke = egui::Stroke::new(1.0, active_bg);
+        visuals.widgets.active.fg_stroke = egui::Stroke::new(1.0, active_fg);
+        ctx.set_visuals(visuals);
+    }
+}
+
+pub struct AccentTheme {
+    from: Palette,
+    target: Option<Color32>,
+    started: Instant,
+    settled: bool,
+}
+
+impl AccentTheme {
+    pub fn new() -> Self {
+        Self {
+            from: Palette::for_accent(None),
+            target: None,
+            started: Instant::now(),
+            settled: false,
+        }
+    }
+
+    fn progress(&self) -> f32 {
+        (self.started.elapsed().as_secs_f32() / TRANSITION.as_secs_f32()).min(1.0)
+    }
+
+    pub fn current(&self) -> Palette {
+        self.from.lerp(&Palette::for_accent(self.target), self.progress())
+    }
+
+    pub fn update(&mut self, ctx: &egui::Context, target: Option<Color32>) {
+        if target != self.target {
+            self.from = self.current();
+            self.target = target;
+            self.started = Instant::now();
+            self.settled = false;
+        }
+        if self.settled {
+            return;
+        }
+        self.current().apply(ctx);
+        self.settled = self.progress() >= 1.0;
+    }
+}
+
+pub fn dominant_color(image_bytes: &[u8]) -> Option<Color32> {
+    let image = image::load_from_memory(image_bytes).ok()?.thumbnail(32, 32).to_rgb8();
+    let mut sum = [0.0f32; 3];
+    let mut total = 0.0;
+    for pixel in image.pixels() {
+        let [r, g, b] = pixel.0.map(|c| c as f32 / 255.0);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        if max < 0.2 {
+            continue;
+        }
+        let saturation = (max - min) / max;
+        let weight = saturation * saturation * max;
+        sum[0] += r * weight;
+        sum[1] += g * weight;
+        sum[2] += b * weight;
+        total += weight;
+    }
+    if total < 1.0 {
+        return None;
+    }
+    let [r, g, b] = sum.map(|c| (c / total * 255.0).round() as u8);
+    Some(Color32::from_rgb(r, g, b))
+}
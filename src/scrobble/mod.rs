@@ -0,0 +1,3 @@
+mod scrobble;
+
+pub use scrobble::*;
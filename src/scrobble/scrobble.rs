@@ -0,0 +1,269 @@
+use crate::library::atomic_write;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+const API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+const BATCH_SIZE: usize = 50;
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Scrobble {
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+    pub duration: u32,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct LastFmCredentials {
+    pub api_key: String,
+    pub api_secret: String,
+    pub session_key: String,
+}
+
+enum Message {
+    NowPlaying(Scrobble),
+    Scrobble(Scrobble),
+}
+
+enum Failure {
+    Offline(String),
+    Auth(String),
+    Rejected(String),
+}
+
+#[derive(Default)]
+struct ScrobblerState {
+    queued: usize,
+    offline: bool,
+    auth_failed: bool,
+    error: Option<String>,
+}
+
+pub struct Scrobbler {
+    credentials: LastFmCredentials,
+    sender: Sender<Message>,
+    state: Arc<Mutex<ScrobblerState>>,
+    shutdown: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Drop for Scrobbler {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Scrobbler {
+    pub fn start(credentials: LastFmCredentials, queue_file: Option<PathBuf>, previous: Option<Scrobbler>) -> Self {
+        let previous = previous.and_then(Scrobbler::stop);
+        let (sender, receiver) = mpsc::channel();
+        let state = Arc::new(Mutex::new(ScrobblerState::default()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let thread_credentials = credentials.clone();
+        let thread_state = Arc::clone(&state);
+        let thread_shutdown = Arc::clone(&shutdown);
+        let worker = std::thread::spawn(move || {
+            if let Some(previous) = previous {
+                let _ = previous.join();
+            }
+            Self::run(&thread_credentials, receiver, &thread_state, &thread_shutdown, queue_file.as_deref());
+        });
+        Self { credentials, sender, state, shutdown, worker: Some(worker) }
+    }
+
+    fn stop(mut self) -> Option<JoinHandle<()>> {
+        self.shutdown.store(true, Ordering::Relaxed);
+        self.worker.take()
+    }
+
+    pub fn credentials(&self) -> &LastFmCredentials {
+        &self.credentials
+    }
+
+    pub fn now_playing(&self, track: Scrobble) {
+        let _ = self.sender.send(Message::NowPlaying(track));
+    }
+
+    pub fn scrobble(&self, track: Scrobble) {
+        let _ = self.sender.send(Message::Scrobble(track));
+    }
+
+    pub fn queued(&self) -> usize {
+        self.state.lock().map(|s| s.queued).unwrap_or(0)
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.state.lock().map(|s| s.offline).unwrap_or(false)
+    }
+
+    pub fn take_error(&self) -> Option<String> {
+        self.state.lock().ok().and_then(|mut s| s.error.take())
+    }
+
+    pub fn auth_failed(&self) -> bool {
+        self.state.lock().map(|s| s.auth_failed).unwrap_or(false)
+    }
+
+    fn run(
+        credentials: &LastFmCredentials,
+        receiver: Receiver<Message>,
+        state: &Mutex<ScrobblerState>,
+        shutdown: &AtomicBool,
+        queue_file: Option<&Path>,
+    ) {
+        let mut queue: Vec<Scrobble> = queue_file
+            .and_then(|file| std::fs::read_to_string(file).ok())
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        loop {
+            match receiver.recv_timeout(RETRY_INTERVAL) {
+                Ok(Message::NowPlaying(track)) => {
+                    let result = Self::send_now_playing(credentials, &track);
+                    Self::record(state, result.err(), queue.len());
+                }
+                Ok(Message::Scrobble(track)) => {
+                    queue.push(track);
+                    Self::save_queue(queue_file, &queue);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            let mut isolate: usize = 0;
+            while !queue.is_empty() && !shutdown.load(Ordering::Relaxed) {
+                let batch = if isolate > 0 { 1 } else { queue.len().min(BATCH_SIZE) };
+                match Self::send_scrobbles(credentials, &queue[..batch]) {
+                    Ok(()) => {
+                        queue.drain(..batch);
+                        isolate = isolate.saturating_sub(1);
+                        Self::save_queue(queue_file, &queue);
+                        Self::record(state, None, queue.len());
+                    }
+                    Err(Failure::Rejected(_)) if batch > 1 => isolate = batch,
+                    Err(Failure::Rejected(e)) => {
+                        queue.drain(..1);
+                        isolate = isolate.saturating_sub(1);
+                        Self::save_queue(queue_file, &queue);
+                        Self::record(state, Some(Failure::Rejected(e)), queue.len());
+                    }
+                    Err(failure) => {
+                        Self::record(state, Some(failure), queue.len());
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn record(state: &Mutex<ScrobblerState>, failure: Option<Failure>, queued: usize) {
+        let Ok(mut state) = state.lock() else {
+            return;
+        };
+        state.queued = queued;
+        state.offline = matches!(failure, Some(Failure::Offline(_)));
+        state.auth_failed |= matches!(failure, Some(Failure::Auth(_)));
+        if let Some(Failure::Auth(e) | Failure::Rejected(e)) = failure {
+            state.error = Some(e);
+        }
+    }
+
+    fn save_queue(queue_file: Option<&Path>, queue: &[Scrobble]) {
+        if let Some(queue_file) = queue_file
+            && let Ok(contents) = serde_json::to_string_pretty(queue)
+        {
+            let _ = atomic_write(queue_file, contents);
+        }
+    }
+
+    fn send_now_playing(credentials: &LastFmCredentials, track: &Scrobble) -> Result<(), Failure> {
+        let mut params = BTreeMap::new();
+        params.insert("method".to_string(), "track.updateNowPlaying".to_string());
+        params.insert("sk".to_string(), credentials.session_key.clone());
+        params.insert("artist".to_string(), track.artist.clone());
+        params.insert("track".to_string(), track.title.clone());
+        if let Some(album) = &track.album {
+            params.insert("album".to_string(), album.clone());
+        }
+        if track.duration > 0 {
+            params.insert("duration".to_string(), track.duration.to_string());
+        }
+        call(&credentials.api_key, &credentials.api_secret, params).map(|_| ())
+    }
+
+    fn send_scrobbles(credentials: &LastFmCredentials, tracks: &[Scrobble]) -> Result<(), Failure> {
+        let mut params = BTreeMap::new();
+        params.insert("method".to_string(), "track.scrobble".to_string());
+        params.insert("sk".to_string(), credentials.session_key.clone());
+        for (i, track) in tracks.iter().enumerate() {
+            params.insert(format!("artist[{}]", i), track.artist.clone());
+            params.insert(format!("track[{}]", i), track.title.clone());
+            params.insert(format!("timestamp[{}]", i), track.timestamp.to_string());
+            if let Some(album) = &track.album {
+                params.insert(format!("album[{}]", i), album.clone());
+            }
+            if track.duration > 0 {
+                params.insert(format!("duration[{}]", i), track.duration.to_string());
+            }
+        }
+        call(&credentials.api_key, &credentials.api_secret, params).map(|_| ())
+    }
+}
+
+pub fn authenticate(api_key: &str, api_secret: &str, username: &str, password: &str) -> Result<String, String> {
+    let mut params = BTreeMap::new();
+    params.insert("method".to_string(), "auth.getMobileSession".to_string());
+    params.insert("username".to_string(), username.to_string());
+    params.insert("password".to_string(), password.to_string());
+    let response = call(api_key, api_secret, params).map_err(|failure| match failure {
+        Failure::Offline(e) | Failure::Auth(e) | Failure::Rejected(e) => e,
+    })?;
+    response["session"]["key"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "Last.fm did not return a session key".to_string())
+}
+
+fn call(api_key: &str, api_secret: &str, mut params: BTreeMap<String, String>) -> Result<serde_json::Value, Failure> {
+    params.insert("api_key".to_string(), api_key.to_string());
+    let mut signature: String = params.iter().map(|(k, v)| format!("{}{}", k, v)).collect();
+    signature.push_str(api_secret);
+    params.insert("api_sig".to_string(), format!("{:x}", md5::compute(signature)));
+    params.insert("format".to_string(), "json".to_string());
+
+    let mut response = ureq::post(API_URL)
+        .config()
+        .http_status_as_error(false)
+        .timeout_global(Some(REQUEST_TIMEOUT))
+        .build()
+        .send_form(params.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+        .map_err(|e| Failure::Offline(format!("Could not reach Last.fm: {}", e)))?;
+    let status = response.status();
+    let body = response
+        .body_mut()
+        .read_to_string()
+        .map_err(|e| Failure::Offline(format!("Could not read Last.fm response: {}", e)))?;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap_or_default();
+    if let Some(code) = json["error"].as_u64() {
+        let message = format!("Last.fm error {}: {}", code, json["message"].as_str().unwrap_or("Unknown error"));
+        return Err(match code {
+            8 | 11 | 16 | 29 => Failure::Offline(message),
+            4 | 9 | 10 | 13 | 14 | 26 => Failure::Auth(message),
+            _ => Failure::Rejected(message),
+        });
+    }
+    if status.is_server_error() {
+        return Err(Failure::Offline(format!("Last.fm returned {}", status)));
+    }
+    if !status.is_success() {
+        return Err(Failure::Rejected(format!("Last.fm returned {}", status)));
+    }
+    Ok(json)
+}
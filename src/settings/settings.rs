@@ -0,0 +1,445 @@
+use crate::audio::{EqPoint, EqPreset, sort_points};
+use crate::library::{SmartFilter, atomic_write};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RowDensity {
+    Compact,
+    Standard,
+    Comfortable,
+}
+
+impl RowDensity {
+    pub const ALL: [RowDensity; 3] = [RowDensity::Compact, RowDensity::Standard, RowDensity::Comfortable];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RowDensity::Compact => "Compact",
+            RowDensity::Standard => "Standard",
+            RowDensity::Comfortable => "Comfortable",
+        }
+    }
+
+    pub fn row_height(self) -> f32 {
+        match self {
+            RowDensity::Compact => 24.0,
+            RowDensity::Standard => 32.0,
+            RowDensity::Comfortable => 40.0,
+        }
+    }
+
+    pub fn handle_width(self) -> f32 {
+        match self {
+            RowDensity::Compact => 20.0,
+            RowDensity::Standard => 24.0,
+            RowDensity::Comfortable => 28.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShuffleMode {
+    Uniform,
+    LeastPlayed,
+}
+
+impl ShuffleMode {
+    pub const ALL: [ShuffleMode; 2] = [ShuffleMode::Uniform, ShuffleMode::LeastPlayed];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ShuffleMode::Uniform => "Uniform",
+            ShuffleMode::LeastPlayed => "Favor less played and higher rated",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TitleDrag {
+    Full,
+    Grip,
+    Off,
+}
+
+impl TitleDrag {
+    pub const ALL: [TitleDrag; 3] = [TitleDrag::Full, TitleDrag::Grip, TitleDrag::Off];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TitleDrag::Full => "Whole title bar",
+            TitleDrag::Grip => "Icon only",
+            TitleDrag::Off => "Disabled",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TitleDoubleClick {
+    Maximize,
+    Minimize,
+    MiniPlayer,
+    Nothing,
+}
+
+impl TitleDoubleClick {
+    pub const ALL: [TitleDoubleClick; 4] = [
+        TitleDoubleClick::Maximize,
+        TitleDoubleClick::Minimize,
+        TitleDoubleClick::MiniPlayer,
+        TitleDoubleClick::Nothing,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TitleDoubleClick::Maximize => "Maximize",
+            TitleDoubleClick::Minimize => "Minimize",
+            TitleDoubleClick::MiniPlayer => "Toggle mini player",
+            TitleDoubleClick::Nothing => "Do nothing",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OsdPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl OsdPosition {
+    pub const ALL: [OsdPosition; 4] = [
+        OsdPosition::TopLeft,
+        OsdPosition::TopRight,
+        OsdPosition::BottomLeft,
+        OsdPosition::BottomRight,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            OsdPosition::TopLeft => "Top left",
+            OsdPosition::TopRight => "Top right",
+            OsdPosition::BottomLeft => "Bottom left",
+            OsdPosition::BottomRight => "Bottom right",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FileOpenMode {
+    Standalone,
+    Library,
+}
+
+impl FileOpenMode {
+    pub const ALL: [FileOpenMode; 2] = [FileOpenMode::Standalone, FileOpenMode::Library];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FileOpenMode::Standalone => "Compact player",
+            FileOpenMode::Library => "Add to library",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StandaloneEnd {
+    Rewind,
+    Hold,
+    Close,
+    Loop,
+}
+
+impl StandaloneEnd {
+    pub const ALL: [StandaloneEnd; 4] =
+        [StandaloneEnd::Rewind, StandaloneEnd::Hold, StandaloneEnd::Close, StandaloneEnd::Loop];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StandaloneEnd::Rewind => "Stop and rewind",
+            StandaloneEnd::Hold => "Stop at end",
+            StandaloneEnd::Close => "Close the window",
+            StandaloneEnd::Loop => "Play again",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FadeCurve {
+    Linear,
+    Exponential,
+    Ease,
+}
+
+impl FadeCurve {
+    pub const ALL: [FadeCurve; 3] = [FadeCurve::Linear, FadeCurve::Exponential, FadeCurve::Ease];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FadeCurve::Linear => "Linear",
+            FadeCurve::Exponential => "Exponential",
+            FadeCurve::Ease => "Gentle ease",
+        }
+    }
+
+    pub fn gain(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            FadeCurve::Linear => 1.0 - t,
+            FadeCurve::Exponential => 10f32.powf(-3.0 * t) * (1.0 - t),
+            FadeCurve::Ease => (1.0 + (t * std::f32::consts::PI).cos()) / 2.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputLatency {
+    Default,
+    Low,
+    Balanced,
+    High,
+}
+
+impl OutputLatency {
+    pub const ALL: [OutputLatency; 4] =
+        [OutputLatency::Default, OutputLatency::Low, OutputLatency::Balanced, OutputLatency::High];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            OutputLatency::Default => "Device default",
+            OutputLatency::Low => "Low",
+            OutputLatency::Balanced => "Balanced",
+            OutputLatency::High => "High",
+        }
+    }
+
+    pub fn buffer_frames(self) -> Option<u32> {
+        match self {
+            OutputLatency::Default => None,
+            OutputLatency::Low => Some(256),
+            OutputLatency::Balanced => Some(1024),
+            OutputLatency::High => Some(4096),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Settings {
+    pub now_playing_export: bool,
+    pub now_playing_path: Option<PathBuf>,
+    pub now_playing_template: String,
+    pub now_playing_paused_text: String,
+    pub status_server: bool,
+    pub status_port: u16,
+    pub status_cors: bool,
+    pub status_paths: bool,
+    pub smart_filters: Vec<SmartFilter>,
+    pub default_volume: f32,
+    pub max_volume: f32,
+    pub seek_fade: bool,
+    pub crossfade_on_select: bool,
+    pub crossfade_secs: f32,
+    pub crossfade_on_advance: bool,
+    pub crossfade_on_next: bool,
+    pub gapless_albums: bool,
+    pub playlist_height_ratio: f32,
+    pub row_density: RowDensity,
+    pub playlist_font_scale: f32,
+    pub ui_scale: f32,
+    pub fade_in_on_launch: bool,
+    pub advance_on_remove: bool,
+    pub limiter: bool,
+    pub limiter_threshold_db: f32,
+    pub crossfeed: bool,
+    pub crossfeed_cutoff_hz: f32,
+    pub crossfeed_level_db: f32,
+    pub compressor: bool,
+    pub active_playlist: Option<String>,
+    pub shuffle_mode: ShuffleMode,
+    pub skip_silence: bool,
+    pub silence_threshold_db: f32,
+    pub silence_min_secs: f32,
+    pub title_drag: TitleDrag,
+    pub title_double_click: TitleDoubleClick,
+    pub artwork_accent: bool,
+    pub end_tolerance_secs: f32,
+    pub min_window_width: f32,
+    pub min_window_height: f32,
+    pub osd: bool,
+    pub osd_position: OsdPosition,
+    pub reduced_motion: bool,
+    pub file_open_mode: FileOpenMode,
+    pub standalone_end: StandaloneEnd,
+    pub eq_enabled: bool,
+    pub eq_points: Vec<EqPoint>,
+    pub eq_presets: Vec<EqPreset>,
+    pub device_volumes: HashMap<String, f32>,
+    pub output_sample_rate: Option<u32>,
+    pub output_latency: OutputLatency,
+    pub idle_pause: bool,
+    pub idle_pause_minutes: f32,
+    pub idle_fade_curve: FadeCurve,
+    pub idle_fade_secs: f32,
+    pub mini_player: bool,
+    pub mini_player_on_top: bool,
+    pub full_window_size: Option<(f32, f32)>,
+    pub scrobble: bool,
+    pub lastfm_api_key: String,
+    pub lastfm_api_secret: String,
+    pub lastfm_username: String,
+    pub lastfm_session_key: Option<String>,
+    pub prescan_metadata: bool,
+    pub row_waveforms: bool,
+    pub row_bpm: bool,
+    pub row_numbers: bool,
+    pub estimate_bpm: bool,
+    pub restore_session: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            now_playing_export: false,
+            now_playing_path: None,
+            now_playing_template: "{artist} - {title}".to_string(),
+            now_playing_paused_text: String::new(),
+            status_server: false,
+            status_port: 9870,
+            status_cors: false,
+            status_paths: false,
+            smart_filters: Vec::new(),
+            default_volume: 0.5,
+            max_volume: 2.0,
+            seek_fade: true,
+            crossfade_on_select: false,
+            crossfade_secs: 2.0,
+            crossfade_on_advance: false,
+            crossfade_on_next: false,
+            gapless_albums: true,
+            playlist_height_ratio: 0.5,
+            row_density: RowDensity::Standard,
+            playlist_font_scale: 1.0,
+            ui_scale: 1.0,
+            fade_in_on_launch: false,
+            advance_on_remove: false,
+            limiter: false,
+            limiter_threshold_db: -1.0,
+            crossfeed: false,
+            crossfeed_cutoff_hz: 700.0,
+            crossfeed_level_db: 6.0,
+            compressor: false,
+            active_playlist: None,
+            shuffle_mode: ShuffleMode::Uniform,
+            skip_silence: false,
+            silence_threshold_db: -50.0,
+            silence_min_secs: 0.5,
+            title_drag: TitleDrag::Full,
+            title_double_click: TitleDoubleClick::Maximize,
+            artwork_accent: false,
+            end_tolerance_secs: 0.1,
+            min_window_width: 900.0,
+            min_window_height: 620.0,
+            osd: false,
+            osd_position: OsdPosition::BottomRight,
+            reduced_motion: false,
+            file_open_mode: FileOpenMode::Standalone,
+            standalone_end: StandaloneEnd::Hold,
+            eq_enabled: false,
+            eq_points: Vec::new(),
+            eq_presets: Vec::new(),
+            device_volumes: HashMap::new(),
+            output_sample_rate: None,
+            output_latency: OutputLatency::Default,
+            idle_pause: false,
+            idle_pause_minutes: 30.0,
+            idle_fade_curve: FadeCurve::Exponential,
+            idle_fade_secs: 60.0,
+            mini_player: false,
+            mini_player_on_top: true,
+            full_window_size: None,
+            scrobble: false,
+            lastfm_api_key: String::new(),
+            lastfm_api_secret: String::new(),
+            lastfm_username: String::new(),
+            lastfm_session_key: None,
+            prescan_metadata: false,
+            row_waveforms: false,
+            row_bpm: false,
+            row_numbers: false,
+            estimate_bpm: false,
+            restore_session: true,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load(path: &Path) -> Self {
+        let mut settings: Self = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        settings.clamp_volumes();
+        settings.playlist_height_ratio = settings.playlist_height_ratio.clamp(0.2, 0.8);
+        settings.playlist_font_scale = settings.playlist_font_scale.clamp(0.8, 1.5);
+        settings.ui_scale = settings.ui_scale.clamp(0.75, 2.0);
+        settings.limiter_threshold_db = settings.limiter_threshold_db.clamp(-12.0, 0.0);
+        settings.crossfeed_cutoff_hz = settings.crossfeed_cutoff_hz.clamp(300.0, 1000.0);
+        settings.crossfeed_level_db = settings.crossfeed_level_db.clamp(1.0, 15.0);
+        settings.silence_threshold_db = settings.silence_threshold_db.clamp(-80.0, -20.0);
+        settings.silence_min_secs = settings.silence_min_secs.clamp(0.1, 5.0);
+        settings.end_tolerance_secs = settings.end_tolerance_secs.clamp(0.0, 1.0);
+        settings.min_window_width = settings.min_window_width.clamp(600.0, 3840.0);
+        settings.min_window_height = settings.min_window_height.clamp(400.0, 2160.0);
+        settings.idle_pause_minutes = settings.idle_pause_minutes.clamp(1.0, 240.0);
+        settings.idle_fade_secs = settings.idle_fade_secs.clamp(0.0, 300.0);
+        for point in settings
+            .eq_points
+            .iter_mut()
+            .chain(settings.eq_presets.iter_mut().flat_map(|p| p.points.iter_mut()))
+        {
+            *point = point.clamped();
+        }
+        sort_points(&mut settings.eq_points);
+        for preset in &mut settings.eq_presets {
+            sort_points(&mut preset.points);
+        }
+        settings
+    }
+
+    pub fn clamp_volumes(&mut self) {
+        self.max_volume = self.max_volume.clamp(0.1, 2.0);
+        self.default_volume = self.default_volume.clamp(0.0, self.max_volume);
+        for volume in self.device_volumes.values_mut() {
+            *volume = volume.clamp(0.0, self.max_volume);
+        }
+    }
+
+    pub fn volume_for(&self, device: Option<&str>) -> f32 {
+        device
+            .and_then(|d| self.device_volumes.get(d))
+            .copied()
+            .unwrap_or(self.default_volume)
+    }
+
+    pub fn startup_volume(&self, device: Option<&str>) -> f32 {
+        self.volume_for(device).clamp(0.0, self.max_volume.clamp(0.1, 2.0))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+        atomic_write(path, contents).map_err(|e| format!("Failed to save settings: {}", e))
+    }
+}
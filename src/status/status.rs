@@ -0,0 +1,115 @@
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+#[derive(Serialize, Clone, Default)]
+pub struct StatusSnapshot {
+    pub track: Option<String>,
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub playing: bool,
+    pub state: String,
+    pub position: f64,
+    pub duration: f64,
+    pub volume: f32,
+    pub loop_mode: String,
+    pub shuffle: bool,
+    pub reverse: bool,
+}
+
+pub struct StatusServer {
+    port: u16,
+    allow_cors: Arc<AtomicBool>,
+    snapshot: Arc<Mutex<StatusSnapshot>>,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StatusServer {
+    pub fn start(port: u16, allow_cors: bool) -> Result<Self, String> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .map_err(|e| format!("Failed to start status server on port {}: {}", port, e))?;
+        let snapshot = Arc::new(Mutex::new(StatusSnapshot::default()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let allow_cors = Arc::new(AtomicBool::new(allow_cors));
+
+        let thread_snapshot = Arc::clone(&snapshot);
+        let thread_shutdown = Arc::clone(&shutdown);
+        let thread_cors = Arc::clone(&allow_cors);
+        let thread = std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thread_shutdown.load(Ordering::Relaxed) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    Self::respond(stream, &thread_snapshot, thread_cors.load(Ordering::Relaxed));
+                }
+            }
+        });
+
+        Ok(Self { port, allow_cors, snapshot, shutdown, thread: Some(thread) })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn set_allow_cors(&self, allow: bool) {
+        self.allow_cors.store(allow, Ordering::Relaxed);
+    }
+
+    pub fn update(&self, snapshot: StatusSnapshot) {
+        if let Ok(mut current) = self.snapshot.lock() {
+            *current = snapshot;
+        }
+    }
+
+    fn respond(mut stream: TcpStream, snapshot: &Mutex<StatusSnapshot>, allow_cors: bool) {
+        let _ = stream.set_read_timeout(Some(Duration::from_millis(500)));
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 8192 {
+            match stream.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => request.extend_from_slice(&buf[..n]),
+            }
+        }
+
+        let is_get = request.starts_with(b"GET ");
+        let (status, body) = if is_get {
+            let json = snapshot
+                .lock()
+                .ok()
+                .and_then(|s| serde_json::to_string(&*s).ok())
+                .unwrap_or_else(|| "{}".to_string());
+            ("200 OK", json)
+        } else {
+            ("405 Method Not Allowed", "{\"error\":\"method not allowed\"}".to_string())
+        };
+        let cors = if allow_cors { "Access-Control-Allow-Origin: *\r\n" } else { "" };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Cache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            cors,
+            body,
+        );
+        let _ = stream.write_all(response.as_bytes());
+    }
+}
+
+impl Drop for StatusServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        let _ = TcpStream::connect((Ipv4Addr::LOCALHOST, self.port));
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,3 @@
+mod tasks;
+
+pub use tasks::*;
@@ -0,0 +1,225 @@
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+const MAX_FINISHED_TASKS: usize = 20;
+
+enum TaskEvent {
+    Progress { id: u64, done: usize, total: usize },
+    Note { id: u64, text: String },
+    Finished { id: u64, error: Option<String> },
+}
+
+#[derive(Clone, PartialEq)]
+pub enum TaskState {
+    Running,
+    Done,
+    Failed(String),
+}
+
+pub struct Task {
+    pub id: u64,
+    pub name: String,
+    pub progress: Option<(usize, usize)>,
+    pub note: Option<String>,
+    pub state: TaskState,
+}
+
+impl Task {
+    pub fn fraction(&self) -> Option<f32> {
+        self.progress
+            .filter(|(_, total)| *total > 0)
+            .map(|(done, total)| done as f32 / total as f32)
+    }
+}
+
+#[derive(Clone)]
+pub struct TaskReporter {
+    id: u64,
+    sender: Sender<TaskEvent>,
+}
+
+impl TaskReporter {
+    pub fn progress(&self, done: usize, total: usize) {
+        let _ = self.sender.send(TaskEvent::Progress { id: self.id, done, total });
+    }
+
+    pub fn note(&self, text: impl Into<String>) {
+        let _ = self.sender.send(TaskEvent::Note { id: self.id, text: text.into() });
+    }
+}
+
+pub struct TaskHandle<T> {
+    id: u64,
+    receiver: Receiver<Result<T, String>>,
+}
+
+impl<T> TaskHandle<T> {
+    pub fn poll(&self) -> Option<Result<T, String>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(Err("The task stopped unexpectedly".to_string())),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+pub struct TaskRegistry {
+    sender: Sender<TaskEvent>,
+    receiver: Receiver<TaskEvent>,
+    tasks: VecDeque<Task>,
+    next_id: u64,
+}
+
+impl TaskRegistry {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self { sender, receiver, tasks: VecDeque::new(), next_id: 0 }
+    }
+
+    pub fn spawn<T, F>(&mut self, name: impl Into<String>, work: F) -> TaskHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&TaskReporter) -> Result<T, String> + Send + 'static,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.tasks.push_back(Task {
+            id,
+            name: name.into(),
+            progress: None,
+            note: None,
+            state: TaskState::Running,
+        });
+        let reporter = TaskReporter { id, sender: self.sender.clone() };
+        let (result_sender, result_receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| work(&reporter))).unwrap_or_else(|payload| {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown error".to_string());
+                Err(format!("The task crashed: {}", message))
+            });
+            let error = result.as_ref().err().cloned();
+            let _ = result_sender.send(result);
+            let _ = reporter.sender.send(TaskEvent::Finished { id, error });
+        });
+        TaskHandle { id, receiver: result_receiver }
+    }
+
+    pub fn drain(&mut self) {
+        while let Ok(event) = self.receiver.try_recv() {
+            match event {
+                TaskEvent::Progress { id, done, total } => {
+                    if let Some(task) = self.task_mut(id) {
+                        task.progress = Some((done, total));
+                    }
+                }
+                TaskEvent::Note { id, text } => {
+                    if let Some(task) = self.task_mut(id) {
+                        task.note = Some(text);
+                    }
+                }
+                TaskEvent::Finished { id, error } => {
+                    if let Some(task) = self.task_mut(id) {
+                        task.state = match error {
+                            Some(e) => TaskState::Failed(e),
+                            None => TaskState::Done,
+                        };
+                    }
+                }
+            }
+        }
+        while self.tasks.iter().filter(|t| t.state != TaskState::Running).count() > MAX_FINISHED_TASKS {
+            if let Some(pos) = self.tasks.iter().position(|t| t.state != TaskState::Running) {
+                self.tasks.remove(pos);
+            }
+        }
+    }
+
+    fn task_mut(&mut self, id: u64) -> Option<&mut Task> {
+        self.tasks.iter_mut().find(|t| t.id == id)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Task> {
+        self.tasks.iter().find(|t| t.id == id)
+    }
+
+    pub fn tasks(&self) -> impl Iterator<Item = &Task> {
+        self.tasks.iter().rev()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    pub fn active_count(&self) -> usize {
+        self.tasks.iter().filter(|t| t.state == TaskState::Running).count()
+    }
+
+    pub fn clear_finished(&mut self) {
+        self.tasks.retain(|t| t.state == TaskState::Running);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn wait<T>(handle: &TaskHandle<T>) -> Result<T, String> {
+        let started = Instant::now();
+        loop {
+            if let Some(result) = handle.poll() {
+                return result;
+            }
+            assert!(started.elapsed() < Duration::from_secs(5), "task never finished");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn successful_task_returns_its_value() {
+        let mut tasks = TaskRegistry::new();
+        let handle = tasks.spawn("ok", |reporter| {
+            reporter.progress(1, 1);
+            Ok(42)
+        });
+        assert_eq!(wait(&handle), Ok(42));
+        tasks.drain();
+        assert!(tasks.get(handle.id()).is_some_and(|t| t.state == TaskState::Done));
+    }
+
+    #[test]
+    fn panicking_task_reports_failure() {
+        let mut tasks = TaskRegistry::new();
+        let handle: TaskHandle<()> = tasks.spawn("boom", |_| panic!("bad file"));
+        let result = wait(&handle);
+        assert!(result.is_err_and(|e| e.contains("bad file")));
+        tasks.drain();
+        assert!(matches!(tasks.get(handle.id()).map(|t| &t.state), Some(TaskState::Failed(_))));
+    }
+
+    #[test]
+    fn panicked_task_no_longer_counts_as_running() {
+        let mut tasks = TaskRegistry::new();
+        let handle: TaskHandle<()> = tasks.spawn("Finding duplicates", |reporter| {
+            reporter.progress(3, 10);
+            panic!("unreadable file")
+        });
+        assert_eq!(tasks.active_count(), 1);
+        assert!(wait(&handle).is_err());
+        let started = Instant::now();
+        while tasks.active_count() > 0 {
+            assert!(started.elapsed() < Duration::from_secs(5), "task still running");
+            std::thread::sleep(Duration::from_millis(5));
+            tasks.drain();
+        }
+    }
+}